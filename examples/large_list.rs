@@ -0,0 +1,58 @@
+//! Compares marking items of a 100k line list done by reading and writing the whole list with
+//! doing it through an `IndexedList`, the way `todo done` does for large lists. Run with
+//! `cargo run --release --example large_list`
+
+use std::{env, fs, time::Instant};
+
+use todo::{indexed::IndexedList, TodoItem, TodoList};
+
+const LINES: usize = 100_000;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = env::temp_dir().join(format!("todo-large-list.{}.md", std::process::id()));
+    let mut contents = String::from("# Large list\n");
+    for i in 1..LINES {
+        if i % 10 == 0 {
+            contents.push_str(&format!("\n## Section {i}\n"));
+        } else {
+            contents.push_str(&format!("- [ ] item number {i} with a #tag\n"));
+        }
+    }
+    fs::write(&path, &contents)?;
+    let middle = TodoList::from_file(&path)?.len() / 2;
+    // `todo done` adds `done:<date>` to the title, so the line gets longer and the file is
+    // copied around it
+    let numbers = [middle, middle + 1, middle + 2];
+
+    let start = Instant::now();
+    let mut list = TodoList::from_file(&path)?;
+    list.mark_item_done(middle)?;
+    list.write(&path)?;
+    println!("whole list, 1 item:    {:?}", start.elapsed());
+
+    fs::write(&path, &contents)?;
+    let start = Instant::now();
+    let mut list = IndexedList::open(&path)?;
+    list.update(middle, TodoItem::mark_done)?;
+    println!("indexed list, 1 item:  {:?}", start.elapsed());
+
+    fs::write(&path, &contents)?;
+    let start = Instant::now();
+    let mut list = TodoList::from_file(&path)?;
+    for number in numbers {
+        list.mark_item_done(number)?;
+    }
+    list.write(&path)?;
+    println!("whole list, 3 items:   {:?}", start.elapsed());
+
+    fs::write(&path, &contents)?;
+    let start = Instant::now();
+    let mut list = IndexedList::open(&path)?;
+    for number in numbers {
+        list.update(number, TodoItem::mark_done)?;
+    }
+    println!("indexed list, 3 items: {:?}", start.elapsed());
+
+    fs::remove_file(&path)?;
+    Ok(())
+}
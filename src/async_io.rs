@@ -0,0 +1,135 @@
+//! Async variants of reading and writing list files, for servers and daemons running on an
+//! async runtime. The file IO runs on a thread of its own so it doesn't block the runtime.
+//! The futures don't depend on a particular runtime, tokio works as well as any other. Unlike
+//! `tokio::fs`, which uses the runtime's pool of blocking threads, every call starts a thread,
+//! which is fine for the few list files read at a time
+
+use std::{
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use crate::{write_atomically, TodoError, TodoList};
+
+impl TodoList {
+    /// Like [`TodoList::from_file`], without blocking the caller
+    pub async fn from_file_async(path: impl Into<PathBuf>) -> Result<Self, TodoError> {
+        let path = path.into();
+        Blocking::spawn(move || TodoList::from_file(&path)).await
+    }
+
+    /// Like [`TodoList::write`], without blocking the caller
+    pub async fn write_async(&self, path: impl Into<PathBuf>) -> Result<(), TodoError> {
+        let path = path.into();
+        let contents = self.file_contents();
+        let source = self.source.clone();
+        Blocking::spawn(move || {
+            TodoList::check_unchanged(source.as_ref(), &path)?;
+            write_atomically(&path, contents.as_bytes())
+        })
+        .await
+    }
+}
+
+/// Runs a function on its own thread and finishes with what it returns
+struct Blocking<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+struct State<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> Blocking<T> {
+    fn spawn<F>(f: F) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(State {
+            result: None,
+            waker: None,
+        }));
+        let shared = Arc::clone(&state);
+        thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        Self { state }
+    }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            Some(Err(panic)) => panic::resume_unwind(panic),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, task::Wake, thread::Thread};
+
+    use super::*;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls `future` on this thread, sleeping until it's woken
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn reads_and_writes_lists() {
+        let path = env::temp_dir().join(format!("todo-async.{}.md", std::process::id()));
+        fs::write(&path, "# List\n- [ ] one\n- [ ] two\n").unwrap();
+        let mut list = block_on(TodoList::from_file_async(&path)).unwrap();
+        assert_eq!(list.len(), 2);
+        list.mark_item_done(2).unwrap();
+        block_on(list.write_async(&path)).unwrap();
+        let written = TodoList::from_file(&path).unwrap();
+        assert!(written.get_item(2).unwrap().is_done());
+
+        // lists changed by others since they were read aren't overwritten
+        let stale = block_on(TodoList::from_file_async(&path)).unwrap();
+        fs::write(&path, "- [ ] changed\n").unwrap();
+        let result = block_on(stale.write_async(&path));
+        assert!(matches!(result, Err(TodoError::ChangedOnDisk(_))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "- [ ] changed\n");
+
+        fs::remove_file(&path).unwrap();
+        assert!(block_on(TodoList::from_file_async(&path)).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use getset::Getters;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Getters, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    /// copy lists into the backup directory before destructive changes
+    #[getset(get = "pub")]
+    enabled: bool,
+    /// number of backups kept for every list. Older ones are deleted
+    #[getset(get = "pub")]
+    keep: usize,
+    /// where backups are stored, defaults to the data directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dir: Option<PathBuf>,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keep: 20,
+            dir: None,
+        }
+    }
+}
+
+impl BackupConfig {
+    pub fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(|| {
+            dirs::data_dir()
+                .expect("OS data directory not found")
+                .join(env!("CARGO_PKG_NAME"))
+                .join("backups")
+        })
+    }
+}
+
+/// A backup file, named `<list>.<unix timestamp>.md`. The `/` in names of lists in
+/// subdirectories is written as `%2F`, and `%` as `%25`
+pub struct Backup {
+    pub path: PathBuf,
+    pub list: String,
+    pub timestamp: u64,
+}
+
+impl Backup {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let stem = file_name.strip_suffix(".md")?;
+        let (list, timestamp) = stem.rsplit_once('.')?;
+        Some(Self {
+            list: list.replace("%2F", "/").replace("%25", "%"),
+            timestamp: timestamp.parse().ok()?,
+            path,
+        })
+    }
+
+    fn file_name_for(list: &str, timestamp: u64) -> String {
+        let list = list.replace('%', "%25").replace('/', "%2F");
+        format!("{list}.{timestamp}.md")
+    }
+
+    pub fn file_name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// Copies the list at `path` into the backup directory if backups are enabled, then deletes
+/// the oldest backups of the list beyond the retention limit
+pub fn backup_list(config: &Config, name: &str, path: &Path) -> Result<()> {
+    let backups = config.backups();
+    if !backups.enabled() || !path.exists() {
+        return Ok(());
+    }
+    let dir = backups.dir();
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Couldn't create the backup directory '{}'", dir.display()))?;
+    let mut timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    // several backups of a list can be made in the same second
+    while dir.join(Backup::file_name_for(name, timestamp)).exists() {
+        timestamp += 1;
+    }
+    let backup_path = dir.join(Backup::file_name_for(name, timestamp));
+    fs::copy(path, &backup_path)
+        .with_context(|| format!("Couldn't back up '{}'", path.display()))?;
+
+    let list_backups = find_backups(config, Some(name))?;
+    let excess = list_backups.len().saturating_sub(*backups.keep());
+    for old in &list_backups[..excess] {
+        fs::remove_file(&old.path)
+            .with_context(|| format!("Couldn't delete the old backup '{}'", old.path.display()))?;
+    }
+    Ok(())
+}
+
+/// Backups of the list `name`, or of all lists, oldest first
+pub fn find_backups(config: &Config, name: Option<&str>) -> Result<Vec<Backup>> {
+    let dir = config.backups().dir();
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut backups = fs::read_dir(&dir)
+        .with_context(|| format!("Couldn't read the backups in '{}'", dir.display()))?
+        .filter_map(|entry| Backup::from_path(entry.ok()?.path()))
+        .filter(|backup| name.is_none_or(|name| backup.list == name))
+        .collect::<Vec<_>>();
+    backups.sort_by(|a, b| (&a.list, a.timestamp).cmp(&(&b.list, b.timestamp)));
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(list: &str) -> Backup {
+        let path = Path::new("backups").join(Backup::file_name_for(list, 1791968826));
+        Backup::from_path(path).unwrap()
+    }
+
+    #[test]
+    fn names_with_dots_and_slashes() {
+        for list in ["example.com", "journal/2026-10-13", "a/b.c/100%", "a%2F"] {
+            let backup = round_trip(list);
+            assert_eq!(backup.list, list);
+            assert_eq!(backup.timestamp, 1791968826);
+            assert!(!backup.file_name().contains('/'));
+        }
+        assert_eq!(
+            round_trip("journal/2026-10-13").file_name(),
+            "journal%2F2026-10-13.1791968826.md"
+        );
+    }
+}
@@ -1,21 +1,260 @@
 use std::{
-    fs, io,
+    collections::BTreeMap,
+    env, fs, io,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
+use crate::{backup::BackupConfig, sync::SyncConfig, webhook::Webhook};
+use anyhow::{bail, Context, Result};
 use getset::Getters;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use todo::{
+    style::{Glyphs, Theme},
+    text::{edit_distance, is_subsequence},
+    LineEnding, Overflow, TodoItem, TodoList,
+};
 
-#[derive(Debug, Getters, Deserialize)]
+#[derive(Debug, Getters, Deserialize, Serialize)]
 pub struct Config {
+    /// file this config was read from
+    #[serde(skip)]
+    #[getset(get = "pub")]
+    path: Option<PathBuf>,
     /// all lists live in the main dir
-    #[getset(get)]
+    #[getset(get = "pub")]
     main_dir: PathBuf,
     /// general list - random items with no list specified will be in this list
     #[serde(default = "Config::default_general_list_name")]
     #[getset(get = "pub")]
     general_list: String,
+    /// colors used when displaying lists
+    #[serde(default)]
+    #[getset(get = "pub")]
+    theme: Theme,
+    /// markers shown for done and open items
+    #[serde(default)]
+    #[getset(get = "pub")]
+    glyphs: Glyphs,
+    /// whether titles too long for the terminal are wrapped or truncated
+    #[serde(default)]
+    #[getset(get = "pub")]
+    overflow: Overflow,
+    /// output plain text without colors or emoji
+    #[serde(default)]
+    #[getset(get = "pub")]
+    plain: bool,
+    /// show links as terminal hyperlinks when colors are enabled
+    #[serde(default = "Config::default_hyperlinks")]
+    #[getset(get = "pub")]
+    hyperlinks: bool,
+    /// leave `#tag` words out of the titles shown
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    hide_tags: bool,
+    /// columns item numbers are right-aligned in. They fit the highest number shown if 0
+    #[serde(default)]
+    #[getset(get = "pub")]
+    number_width: usize,
+    /// show the IDs of items, which don't change like their numbers, next to the numbers
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    show_ids: bool,
+    /// show the dates of `due:`, `created:` and `done:` words as they are instead of like
+    /// `due in 3 days`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    absolute_dates: bool,
+    /// how absolute dates are shown, with chrono's specifiers like `%a %e %b`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[getset(get = "pub")]
+    date_format: Option<String>,
+    /// time zone of the dates and times in the lists, like `+02:00` or `UTC`, so machines in
+    /// different time zones agree on what's due. The system's time zone if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[getset(get = "pub")]
+    time_zone: Option<String>,
+    /// show a progress bar above the items of a list
+    #[serde(default = "Config::default_show_progress")]
+    #[getset(get = "pub")]
+    show_progress: bool,
+    /// lists that live outside the main dir and settings of lists, by name
+    #[serde(default)]
+    #[getset(get = "pub")]
+    lists: BTreeMap<String, ListMetadata>,
+    /// short names for lists, e.g. `w` for `work`
+    #[serde(default)]
+    #[getset(get = "pub")]
+    aliases: BTreeMap<String, String>,
+    /// lists of the items of all lists matching a query, e.g. `"urgent": "tag:urgent"`
+    #[serde(default)]
+    #[getset(get = "pub")]
+    smart_lists: BTreeMap<String, String>,
+    /// items added to every new list, e.g. a reminder to review it
+    #[serde(default)]
+    #[getset(get = "pub")]
+    seed_items: Vec<String>,
+    /// queries that can be used with `list --filter`, e.g. `"today": "due<=today OR pinned"`
+    #[serde(default)]
+    #[getset(get = "pub")]
+    filters: BTreeMap<String, String>,
+    /// use the TODO.md in the current directory when no list is specified
+    #[serde(default = "Config::default_detect_cwd_list")]
+    #[getset(get = "pub")]
+    detect_cwd_list: bool,
+    /// use today's journal, once `todo journal` created it, when no list is specified
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    journal_by_default: bool,
+    /// show done items in `list` without passing `--all`
+    #[serde(default)]
+    #[getset(get = "pub")]
+    show_done_by_default: bool,
+    /// move items to a `## Done` section at the end of their list once they're done
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    move_done_items: bool,
+    /// how the metadata of items like due dates is written, e.g. `obsidian` for the emoji of
+    /// the Obsidian Tasks plugin
+    #[serde(default)]
+    #[getset(get = "pub")]
+    dialect: Dialect,
+    /// line endings used when writing lists
+    #[serde(default)]
+    #[getset(get = "pub")]
+    line_endings: LineEndings,
+    /// backups of lists made before destructive changes
+    #[serde(default)]
+    #[getset(get = "pub")]
+    backups: BackupConfig,
+    /// commit every change to a list that lives in a git repository
+    #[serde(default)]
+    #[getset(get = "pub")]
+    git_commit: bool,
+    /// keep the items of all lists in an index in the data dir, so commands showing the items
+    /// of every list only read the lists that changed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    index_lists: bool,
+    /// how `todo sync` syncs the main dir
+    #[serde(default)]
+    #[getset(get = "pub")]
+    sync: SyncConfig,
+    /// times of day like `09:00` that `todo remind` schedules reminders of due items at
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[getset(get = "pub")]
+    reminder_times: Vec<String>,
+    /// URLs notified when items are added, done or removed
+    #[serde(default)]
+    #[getset(get = "pub")]
+    webhooks: Vec<Webhook>,
+    /// commands run around writes of lists without write hooks of their own
+    #[serde(default)]
+    #[getset(get = "pub")]
+    write_hooks: WriteHooks,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEndings {
+    /// keep whatever the list file already uses
+    #[default]
+    Preserve,
+    Lf,
+    Crlf,
+}
+
+impl LineEndings {
+    pub fn apply(&self, list: &mut TodoList) {
+        match self {
+            LineEndings::Preserve => {}
+            LineEndings::Lf => list.set_line_ending(LineEnding::Lf),
+            LineEndings::Crlf => list.set_line_ending(LineEnding::Crlf),
+        }
+    }
+}
+
+/// Conventions for the metadata of items, which other apps read from list files too
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dialect {
+    /// `key:value` words like `due:2025-07-01`
+    #[default]
+    Plain,
+    /// the emoji of the Obsidian Tasks plugin like `📅 2025-07-01` and `⏫`, for lists in an
+    /// Obsidian vault
+    Obsidian,
+}
+
+impl Dialect {
+    pub fn apply(&self, list: &mut TodoList) {
+        match self {
+            // items with emoji are read either way, so they're left as they are
+            Dialect::Plain => {}
+            Dialect::Obsidian => list.map_items_mut(TodoItem::use_emoji_fields),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Getters, Deserialize, Serialize)]
+pub struct ListMetadata {
+    /// where the list lives if it's not in the main dir
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    path: Option<PathBuf>,
+    /// refuse to change the list unless `--unlock` is passed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    readonly: bool,
+    /// leave the list out of `todo lists` unless `--hidden` is passed
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    hidden: bool,
+    /// move the list to the archive once all its items are done
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[getset(get = "pub")]
+    archive_when_done: bool,
+    #[serde(flatten)]
+    #[getset(get = "pub")]
+    settings: ListSettings,
+}
+
+impl ListMetadata {
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+}
+
+/// Defaults for the commands working on a list. Unset values fall back to the global config
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ListSettings {
+    /// show done items in `list` without passing `--all`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_done: Option<bool>,
+    /// sync this list on its own instead of with the main dir
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncConfig>,
+    /// commands run around writes of this list, instead of the global ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_hooks: Option<WriteHooks>,
+    /// move done items to a `## Done` section, instead of doing what the global config says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub move_done_items: Option<bool>,
+    /// how the metadata of items is written, instead of the global `dialect`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dialect: Option<Dialect>,
+}
+
+/// Shell commands run around writing a list. They get the path of the list in `TODO_LIST_PATH`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WriteHooks {
+    /// gets the new contents of the list on stdin and can print changed contents. The list
+    /// isn't written if it fails
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_write: Option<String>,
+    /// run after the list is written, e.g. to sync it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_write: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -25,7 +264,36 @@ struct OptionalConfig {
     general_list: Option<String>,
 }
 
+/// List names this close to a name that doesn't exist are suggested instead
+const MAX_TYPO_DISTANCE: usize = 2;
+
+/// Overrides `main_dir` of the config
+const MAIN_DIR_VAR: &str = "TODO_MAIN_DIR";
+/// Overrides `general_list` of the config
+const GENERAL_LIST_VAR: &str = "TODO_GENERAL_LIST";
+/// Path of the config file to use instead of the default one
+pub const CONFIG_PATH_VAR: &str = "TODO_CONFIG";
+
 impl Config {
+    /// Builds a default config for the main dir in `TODO_MAIN_DIR`, if that's set.
+    /// The other overrides are applied by [`Config::with_env_overrides`].
+    pub fn from_env() -> Option<Self> {
+        let main_dir = env::var_os(MAIN_DIR_VAR)?;
+        let config = serde_json::json!({ "main_dir": PathBuf::from(main_dir) });
+        serde_json::from_value(config).ok()
+    }
+
+    /// Replaces values in the config with the ones set in the environment
+    pub fn with_env_overrides(mut self) -> Self {
+        if let Some(main_dir) = env::var_os(MAIN_DIR_VAR) {
+            self.main_dir = PathBuf::from(main_dir);
+        }
+        if let Ok(general_list) = env::var(GENERAL_LIST_VAR) {
+            self.general_list = general_list;
+        }
+        self
+    }
+
     pub fn read_from_default() -> Result<Self> {
         let config_file = Config::default_config_path();
 
@@ -49,13 +317,71 @@ impl Config {
         let config_file = fs::read_to_string(path)
             .with_context(|| format!("Couldn't read the config at '{}'", &path.display()))?;
 
-        serde_json::from_str(&config_file).context("Invalid config file")
+        let mut config: Config =
+            serde_json::from_str(&config_file).context("Invalid config file")?;
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Lets `edit` change the config file at `path` as JSON. The file is only written if the
+    /// result is still a valid config.
+    pub fn edit_file<F>(path: &Path, edit: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        let config_file = fs::read_to_string(path)
+            .with_context(|| format!("Couldn't read the config at '{}'", &path.display()))?;
+        let mut value: Value = serde_json::from_str(&config_file).context("Invalid config file")?;
+        edit(&mut value)?;
+        serde_json::from_value::<Config>(value.clone())
+            .context("The config would be invalid after this change")?;
+        fs::write(path, serde_json::to_string_pretty(&value)?)
+            .with_context(|| format!("Couldn't write the config at '{}'", path.display()))
+    }
+
+    /// Like [`Config::edit_file`] on the file this config was read from
+    pub fn edit<F>(&self, edit: F) -> Result<()>
+    where
+        F: FnOnce(&mut Value) -> Result<()>,
+    {
+        let Some(path) = &self.path else {
+            bail!("The config wasn't read from a file. Use --config to pick the file to change")
+        };
+        Config::edit_file(path, edit)
+    }
+
+    /// Value of `key` in the config, written as for [`key_path`], e.g. `theme.done`
+    pub fn get(&self, key: &str) -> Result<Value> {
+        let value = serde_json::to_value(self)?;
+        value
+            .pointer(&Config::key_pointer(&key_path(&value, key)?))
+            .cloned()
+            .with_context(|| format!("'{key}' is not set in the config"))
+    }
+
+    /// JSON pointer for the parts of a config key
+    fn key_pointer(path: &[String]) -> String {
+        path.iter()
+            .map(|part| format!("/{}", part.replace('~', "~0").replace('/', "~1")))
+            .collect()
     }
 
     fn default_general_list_name() -> String {
         "general".to_string()
     }
 
+    fn default_hyperlinks() -> bool {
+        true
+    }
+
+    fn default_show_progress() -> bool {
+        true
+    }
+
+    fn default_detect_cwd_list() -> bool {
+        true
+    }
+
     /// Prompts the user for the config
     pub fn read_interactive() -> Result<Self> {
         let main_dir = Config::prompt("Where should the todo lists be located?", None)?;
@@ -92,9 +418,368 @@ impl Config {
         Ok(data.trim().to_owned())
     }
 
+    /// Names of all the lists in the main dir and the ones registered in the config
+    pub fn list_names(&self) -> Result<Vec<String>> {
+        let entries = fs::read_dir(&self.main_dir)
+            .with_context(|| format!("Couldn't read the lists in '{}'", self.main_dir.display()))?;
+        let mut names = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "md") {
+                if let Some(name) = path.file_stem() {
+                    names.push(name.to_string_lossy().to_string());
+                }
+            }
+        }
+        names.extend(
+            self.lists
+                .iter()
+                .filter(|(_, list)| list.path.is_some())
+                .map(|(name, _)| name.clone()),
+        );
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Saves `path` in the config file as the list `name`
+    pub fn register_list(&self, name: &str, path: &Path) -> Result<()> {
+        self.edit(|c| set_key(c, &["lists", name, "path"], serde_json::json!(path)))
+    }
+
+    /// `name`, or `name` with a number appended if a list called `name` already exists
+    pub fn unique_list_name(&self, name: &str) -> String {
+        let taken = |n: &str| self.lists.contains_key(n) || self.list_path(n).exists();
+        if !taken(name) {
+            return name.to_string();
+        }
+        (2..)
+            .map(|i| format!("{name}-{i}"))
+            .find(|n| !taken(n))
+            .expect("some number is free")
+    }
+
+    /// Templates are lists in the `templates` directory of the main dir
+    pub fn template_path(&self, name: &str) -> PathBuf {
+        self.main_dir.join("templates").join(format!("{name}.md"))
+    }
+
+    /// Executables run on events, e.g. `on-done`, are kept in the `hooks` directory next to the
+    /// config file
+    pub fn hooks_dir(&self) -> PathBuf {
+        self.path
+            .as_deref()
+            .and_then(Path::parent)
+            .map_or_else(Config::default_config_dir_path, Path::to_path_buf)
+            .join("hooks")
+    }
+
+    /// Archived lists are moved to the `archive` directory of the main dir
+    pub fn archive_dir(&self) -> PathBuf {
+        self.main_dir.join("archive")
+    }
+
+    /// Path in the archive directory for the list `name` that isn't taken yet
+    pub fn archive_path(&self, name: &str) -> PathBuf {
+        free_path(&self.archive_dir(), name)
+    }
+
+    /// Path of the list `name` if it is registered and lives outside the main dir
+    pub fn external_list_path(&self, name: &str) -> Option<&PathBuf> {
+        self.lists.get(name).and_then(ListMetadata::path)
+    }
+
+    /// Settings for the list `name`
+    pub fn list_settings(&self, name: &str) -> ListSettings {
+        self.lists
+            .get(self.resolve_alias(name))
+            .map(|list| list.settings.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.lists.get(name).is_some_and(|list| list.hidden)
+    }
+
+    /// The write hooks of the list at `path`
+    pub fn write_hooks_at(&self, path: &Path) -> &WriteHooks {
+        self.lists
+            .iter()
+            .find(|(name, _)| self.list_path(name) == path)
+            .and_then(|(_, list)| list.settings.write_hooks.as_ref())
+            .unwrap_or(&self.write_hooks)
+    }
+
+    /// Whether done items of the list at `path` are moved to its `## Done` section
+    pub fn moves_done_items_at(&self, path: &Path) -> bool {
+        self.lists
+            .iter()
+            .find(|(name, _)| self.list_path(name) == path)
+            .and_then(|(_, list)| list.settings.move_done_items)
+            .unwrap_or(self.move_done_items)
+    }
+
+    /// How the metadata of items of the list at `path` is written
+    pub fn dialect_at(&self, path: &Path) -> Dialect {
+        self.lists
+            .iter()
+            .find(|(name, _)| self.list_path(name) == path)
+            .and_then(|(_, list)| list.settings.dialect)
+            .unwrap_or(self.dialect)
+    }
+
+    /// Name of the read-only list whose file is at `path`, if there is one
+    pub fn readonly_list_at(&self, path: &Path) -> Option<&str> {
+        self.lists
+            .iter()
+            .find(|(name, list)| list.readonly && self.list_path(name) == path)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Whether `name` is a list, a registered list or an alias
+    pub fn is_known_list(&self, name: &str) -> bool {
+        self.aliases.contains_key(name)
+            || self.smart_lists.contains_key(name)
+            || self.lists.contains_key(name)
+            || self.list_path(name).exists()
+    }
+
+    /// For a list name that doesn't exist, the known list it most likely refers to: the only
+    /// one it is a prefix of, or else the only one it is close to
+    pub fn suggest_list_name(&self, name: &str) -> Option<String> {
+        if self.is_known_list(name) {
+            return None;
+        }
+        let mut candidates = self.list_names().unwrap_or_default();
+        candidates.extend(self.aliases.keys().cloned());
+        let only = |matches: Vec<&String>| (matches.len() == 1).then(|| matches[0].clone());
+        only(candidates.iter().filter(|c| c.starts_with(name)).collect())
+            .or_else(|| {
+                only(
+                    candidates
+                        .iter()
+                        .filter(|c| is_subsequence(name, c))
+                        .collect(),
+                )
+            })
+            .or_else(|| {
+                only(
+                    candidates
+                        .iter()
+                        .filter(|c| edit_distance(name, c) <= MAX_TYPO_DISTANCE)
+                        .collect(),
+                )
+            })
+    }
+
+    /// The list name `name` stands for if it's an alias, `name` itself otherwise
+    pub fn resolve_alias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
     pub fn list_path(&self, name: &str) -> PathBuf {
+        let name = self.resolve_alias(name);
+        if let Some(path) = self.external_list_path(name) {
+            return path.clone();
+        }
         let mut list_path = self.main_dir.clone();
         list_path.push(format!("{}.md", name));
         list_path
     }
 }
+
+/// Path in `dir` for the list `name` that isn't taken yet, numbered if `name.md` is
+pub fn free_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(format!("{name}.md"));
+    if !path.exists() {
+        return path;
+    }
+    (2..)
+        .map(|i| dir.join(format!("{name}-{i}.md")))
+        .find(|p| !p.exists())
+        .expect("some number is free")
+}
+
+/// Sets the key at `path` to `value` in the JSON of a config, creating the objects on the way
+/// if needed. Each part of the path is a key of its own, so it can have dots, like the name of
+/// a list called `example.com`
+pub fn set_key(config: &mut Value, path: &[impl AsRef<str>], value: Value) -> Result<()> {
+    let key = join_key(path);
+    let mut current = config;
+    let mut parts = path.iter().map(AsRef::as_ref).peekable();
+    while let Some(part) = parts.next() {
+        let Value::Object(map) = current else {
+            bail!("Can't set '{key}' since '{part}' is not inside an object")
+        };
+        if parts.peek().is_none() {
+            map.insert(part.to_string(), value);
+            return Ok(());
+        }
+        current = map
+            .entry(part)
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+    bail!("Empty config key")
+}
+
+/// Removes the key at `path` from the JSON of a config, see [`set_key`]
+pub fn unset_key(config: &mut Value, path: &[impl AsRef<str>]) -> Result<()> {
+    let not_set = || format!("'{}' is not set in the config file", join_key(path));
+    let (last, parents) = path.split_last().context("Empty config key")?;
+    let mut current = config;
+    for part in parents {
+        current = current.get_mut(part.as_ref()).with_context(not_set)?;
+    }
+    current
+        .as_object_mut()
+        .and_then(|map| map.remove(last.as_ref()))
+        .with_context(not_set)?;
+    Ok(())
+}
+
+/// The parts of a key written with dots, like `sync.directory`, for [`set_key`]. A part with
+/// dots is written in brackets and quotes, like `lists["example.com"].readonly`. Without them,
+/// dotted parts that together are a key `config` already has, like the name of the list
+/// `example.com`, are taken as that key, the longest one if several are
+pub fn key_path(config: &Value, key: &str) -> Result<Vec<String>> {
+    let invalid = |reason: &str| format!("Invalid config key '{key}'. {reason}");
+    // the parts as written, and whether each is quoted
+    let mut parts = vec![];
+    let mut rest = key;
+    loop {
+        if let Some(bracketed) = rest.strip_prefix("[\"") {
+            let (part, after) = bracketed
+                .split_once("\"]")
+                .with_context(|| invalid("A '[\"' isn't closed with '\"]'"))?;
+            parts.push((part, true));
+            rest = after;
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let part = &rest[..end];
+            if part.is_empty() {
+                bail!(invalid("Parts can't be empty"));
+            }
+            parts.push((part, false));
+            rest = &rest[end..];
+        }
+        if rest.is_empty() {
+            break;
+        }
+        if !rest.starts_with('[') {
+            rest = rest
+                .strip_prefix('.')
+                .with_context(|| invalid("Parts are separated by dots"))?;
+        }
+    }
+
+    let mut path = vec![];
+    let mut current = Some(config);
+    let mut i = 0;
+    while i < parts.len() {
+        let (part, quoted) = parts[i];
+        let unquoted_len = parts[i..].iter().take_while(|(_, quoted)| !quoted).count();
+        let existing = (!quoted)
+            .then(|| current.and_then(Value::as_object))
+            .flatten()
+            .and_then(|map| {
+                (2..=unquoted_len).rev().find_map(|len| {
+                    let joined = parts[i..i + len]
+                        .iter()
+                        .map(|(p, _)| *p)
+                        .collect::<Vec<_>>();
+                    let joined = joined.join(".");
+                    map.contains_key(&joined).then_some((joined, len))
+                })
+            });
+        let (part, len) = existing.unwrap_or_else(|| (part.to_string(), 1));
+        current = current.and_then(|value| value.get(&part));
+        path.push(part);
+        i += len;
+    }
+    Ok(path)
+}
+
+/// A key as it's written for [`key_path`]
+fn join_key(path: &[impl AsRef<str>]) -> String {
+    let mut key = String::new();
+    for part in path.iter().map(AsRef::as_ref) {
+        if part.contains(['.', '[']) {
+            key.push_str(&format!("[\"{part}\"]"));
+        } else {
+            if !key.is_empty() {
+                key.push('.');
+            }
+            key.push_str(part);
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn path(config: &Value, key: &str) -> Vec<String> {
+        key_path(config, key).unwrap()
+    }
+
+    #[test]
+    fn key_paths() {
+        let config = json!({ "lists": { "example.com": "/tmp/e.md", "a.b.c": "/tmp/a.md" } });
+        assert_eq!(path(&config, "sync.directory"), ["sync", "directory"]);
+        assert_eq!(path(&config, "lists.example.com"), ["lists", "example.com"]);
+        assert_eq!(
+            path(&config, "lists.example.com.readonly"),
+            ["lists", "example.com", "readonly"]
+        );
+        assert_eq!(path(&config, "lists.a.b.c"), ["lists", "a.b.c"]);
+        assert_eq!(path(&config, "lists.a.b"), ["lists", "a", "b"]);
+        assert_eq!(
+            path(&config, r#"lists["new.list"].readonly"#),
+            ["lists", "new.list", "readonly"]
+        );
+        assert_eq!(path(&config, r#"["a.b"]["c"]"#), ["a.b", "c"]);
+        for invalid in [
+            "",
+            "lists.",
+            ".lists",
+            "lists..a",
+            r#"lists["a"#,
+            r#"lists["a"]b"#,
+        ] {
+            assert!(key_path(&config, invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn key_pointers_escape_slashes_and_tildes() {
+        let path = ["lists".to_string(), "journal/~2026".to_string()];
+        assert_eq!(Config::key_pointer(&path), "/lists/journal~1~02026");
+        let config = json!({ "lists": { "journal/~2026": 1 } });
+        assert_eq!(config.pointer(&Config::key_pointer(&path)), Some(&json!(1)));
+    }
+
+    #[test]
+    fn sets_and_unsets_keys() {
+        let mut config = json!({ "theme": { "done": "green" } });
+        set_key(&mut config, &["lists", "example.com"], json!("/tmp/e.md")).unwrap();
+        set_key(&mut config, &["theme", "done"], json!("blue")).unwrap();
+        assert_eq!(
+            config,
+            json!({ "theme": { "done": "blue" }, "lists": { "example.com": "/tmp/e.md" } })
+        );
+        let error = set_key(&mut config, &["theme", "done", "bold"], json!(true)).unwrap_err();
+        assert!(error.to_string().contains("'theme.done.bold'"), "{error}");
+
+        unset_key(&mut config, &["lists", "example.com"]).unwrap();
+        assert_eq!(config, json!({ "theme": { "done": "blue" }, "lists": {} }));
+        let error = unset_key(&mut config, &["lists", "example.com"]).unwrap_err();
+        assert!(
+            error.to_string().contains(r#"'lists["example.com"]'"#),
+            "{error}"
+        );
+        assert!(unset_key(&mut config, &["sync", "directory"]).is_err());
+        assert!(unset_key(&mut config, &[] as &[&str]).is_err());
+    }
+}
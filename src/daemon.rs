@@ -0,0 +1,160 @@
+use std::{collections::HashSet, env, fs, path::PathBuf, process::Command, thread, time::Duration};
+
+use anyhow::{Context, Result};
+use todo::{
+    date::{self, Date, Time},
+    TodoItem, TodoList,
+};
+
+use crate::config::Config;
+
+/// How often the lists are checked for reminders that are due
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reminders of items due on a day without a time go off at this time
+pub const DEFAULT_REMINDER_TIME: Time = Time { hour: 9, minute: 0 };
+
+/// Checks the lists for open items whose reminder time has come and shows a desktop
+/// notification for each of them, once. Runs until the process is stopped
+pub fn run(config: &Config) -> Result<()> {
+    let mut notified = HashSet::new();
+    println!("Watching the lists for reminders");
+    loop {
+        let now = (Date::today(), Time::now());
+        for name in config.list_names()? {
+            let path = config.list_path(&name);
+            let Ok(list) = TodoList::from_file(&path) else {
+                continue;
+            };
+            for (_, item) in list.numbered_items() {
+                let Some(at) = reminder_time(item).filter(|at| *at <= now) else {
+                    continue;
+                };
+                if notified.insert(format!("{}\t{}\t{at:?}", path.display(), item.name)) {
+                    if let Err(e) = notify(&name, &item.name) {
+                        eprintln!("Couldn't show a notification: {e:#}");
+                    }
+                }
+            }
+        }
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+/// When the reminder of an open item goes off: at its `snooze:` time if it has one, when it's
+/// due otherwise
+fn reminder_time(item: &TodoItem) -> Option<(Date, Time)> {
+    if item.is_done() {
+        return None;
+    }
+    let (date, time) = match item.field("snooze") {
+        Some(snooze) => date::parse_date_time(snooze).ok()?,
+        None => item.due()?,
+    };
+    Some((date, time.unwrap_or(DEFAULT_REMINDER_TIME)))
+}
+
+pub fn notify(list: &str, title: &str) -> Result<()> {
+    let summary = format!("todo: {list}");
+    let status = if cfg!(target_os = "macos") {
+        let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        Command::new("osascript")
+            .arg("-e")
+            .arg(format!(
+                "display notification \"{}\" with title \"{}\"",
+                quote(title),
+                quote(&summary)
+            ))
+            .status()
+    } else {
+        Command::new("notify-send").args([&summary, title]).status()
+    };
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        // print the reminder where the daemon logs to so it isn't lost
+        _ => {
+            println!("{summary}: {title}");
+            Ok(())
+        }
+    }
+}
+
+/// The arguments running the todo `command` with the config in use
+pub fn todo_command(config: &Config, command: &str) -> Result<Vec<String>> {
+    let exe = env::current_exe().context("Couldn't find the path of the todo executable")?;
+    let mut args = vec![exe.display().to_string()];
+    if let Some(config_path) = config.path() {
+        let config_path = std::path::absolute(config_path)?;
+        args.extend(["--config".to_string(), config_path.display().to_string()]);
+    }
+    args.push(command.to_string());
+    Ok(args)
+}
+
+/// `args` quoted for the `ExecStart` of a systemd unit
+pub fn systemd_exec(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Where systemd looks for the units of the user
+pub fn systemd_user_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Couldn't find the home directory")?;
+    Ok(dirs::config_dir()
+        .unwrap_or_else(|| home.join(".config"))
+        .join("systemd/user"))
+}
+
+/// Writes a systemd user unit, or a launchd agent on macOS, that runs the daemon on login
+pub fn install(config: &Config) -> Result<()> {
+    let args = todo_command(config, "daemon")?;
+    let home = dirs::home_dir().context("Couldn't find the home directory")?;
+    let (path, contents, enable): (PathBuf, String, String) = if cfg!(target_os = "macos") {
+        let label = "com.github.maheshbansod.todo";
+        let arguments = args
+            .iter()
+            .map(|arg| format!("        <string>{arg}</string>\n"))
+            .collect::<String>();
+        (
+            home.join("Library/LaunchAgents")
+                .join(format!("{label}.plist")),
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{label}</string>
+    <key>ProgramArguments</key>
+    <array>
+{arguments}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <true/>
+</dict>
+</plist>
+"#
+            ),
+            format!("launchctl load ~/Library/LaunchAgents/{label}.plist"),
+        )
+    } else {
+        let exec = systemd_exec(&args);
+        (
+            systemd_user_dir()?.join("todo-daemon.service"),
+            format!(
+                "[Unit]\nDescription=Reminders for todo lists\n\n[Service]\nExecStart={exec}\nRestart=on-failure\n\n[Install]\nWantedBy=default.target\n"
+            ),
+            "systemctl --user daemon-reload && systemctl --user enable --now todo-daemon".to_string(),
+        )
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Couldn't create '{}'", dir.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("Couldn't write '{}'", path.display()))?;
+    println!("Wrote '{}'", path.display());
+    println!("Start the daemon with `{enable}`");
+    Ok(())
+}
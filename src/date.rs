@@ -0,0 +1,525 @@
+//! Calendar dates written as `YYYY-MM-DD` and times of day written as `HH:MM`. They are in the
+//! time zone set with [`set_time_zone`], the system's by default, unless a time has an offset
+//! like `09:30+02:00`
+
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::OnceLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::TodoError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    pub hour: u32,
+    pub minute: u32,
+}
+
+/// The time zone dates and times without an offset are in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeZone {
+    /// The system's, which changes with the machine's settings, e.g. when travelling
+    #[default]
+    Local,
+    /// A fixed number of minutes ahead of UTC
+    Fixed(i64),
+}
+
+static TIME_ZONE: OnceLock<TimeZone> = OnceLock::new();
+
+/// Makes `zone` the time zone of dates and times without an offset, so machines in different
+/// time zones agree on what's due when. Only the first call has an effect
+pub fn set_time_zone(zone: TimeZone) {
+    let _ = TIME_ZONE.set(zone);
+}
+
+impl TimeZone {
+    fn current() -> Self {
+        TIME_ZONE.get().copied().unwrap_or_default()
+    }
+
+    /// Seconds the zone is ahead of UTC at `timestamp`
+    fn offset(&self, timestamp: i64) -> i64 {
+        match self {
+            TimeZone::Local => utc_offset(timestamp),
+            TimeZone::Fixed(minutes) => minutes * 60,
+        }
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = TodoError;
+
+    /// Parses `local`, `UTC` or `Z`, and offsets like `+02:00`, `-0530` or `+09`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => return Ok(TimeZone::Local),
+            "UTC" | "utc" | "Z" => return Ok(TimeZone::Fixed(0)),
+            _ => {}
+        }
+        let invalid = || {
+            TodoError::ParseError(format!(
+                "Expected a time zone like +02:00, UTC or local. Found '{s}'"
+            ))
+        };
+        let (sign, offset) = match s.split_at_checked(1) {
+            Some(("+", offset)) => (1, offset),
+            Some(("-", offset)) => (-1, offset),
+            _ => return Err(invalid()),
+        };
+        let digits = offset.replace(':', "");
+        if !matches!(digits.len(), 2 | 4) || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let hours = digits[..2].parse::<i64>().map_err(|_| invalid())?;
+        let minutes = digits
+            .get(2..)
+            .filter(|minutes| !minutes.is_empty())
+            .map_or(Ok(0), str::parse)
+            .map_err(|_| invalid())?;
+        if hours > 14 || minutes > 59 {
+            return Err(invalid());
+        }
+        Ok(TimeZone::Fixed(sign * (hours * 60 + minutes)))
+    }
+}
+
+/// Seconds since 1970-01-01 00:00 in the time zone of the lists
+fn local_timestamp() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    now + TimeZone::current().offset(now)
+}
+
+impl Date {
+    /// Today in the local time zone
+    pub fn today() -> Self {
+        Self::from_days(local_timestamp().div_euclid(86_400))
+    }
+
+    /// The date `days` days after 1970-01-01
+    pub fn from_days(days: i64) -> Self {
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+        Self { year, month, day }
+    }
+
+    /// Number of days since 1970-01-01
+    pub fn days(&self) -> i64 {
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+            + i64::from(self.day)
+            - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    pub fn add_days(&self, days: i64) -> Self {
+        Self::from_days(self.days() + days)
+    }
+
+    /// How far the date is from today, like `today`, `in 3 days` or `2w ago`
+    pub fn relative(&self) -> String {
+        let span = |days: i64| match days {
+            ..14 => format!("{days} days"),
+            14..60 => format!("{}w", days / 7),
+            60..365 => format!("{}mo", days / 30),
+            _ => format!("{}y", days / 365),
+        };
+        match self.days() - Self::today().days() {
+            0 => "today".to_string(),
+            1 => "tomorrow".to_string(),
+            -1 => "yesterday".to_string(),
+            days if days > 0 => format!("in {}", span(days)),
+            days => format!("{} ago", span(-days)),
+        }
+    }
+
+    /// The date written as `format` says, with the `%Y`, `%y`, `%m`, `%d`, `%e`, `%j`, `%b`,
+    /// `%B`, `%a`, `%A`, `%F` and `%%` specifiers of chrono and strftime. Other specifiers are
+    /// left as they are
+    pub fn format(&self, format: &str) -> String {
+        let month = MONTHS[self.month as usize - 1];
+        // 1970-01-01 was a Thursday
+        let weekday = WEEKDAYS[(self.days() + 3).rem_euclid(7) as usize];
+        let mut formatted = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                formatted.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => formatted.push_str(&format!("{:04}", self.year)),
+                Some('y') => formatted.push_str(&format!("{:02}", self.year.rem_euclid(100))),
+                Some('m') => formatted.push_str(&format!("{:02}", self.month)),
+                Some('d') => formatted.push_str(&format!("{:02}", self.day)),
+                Some('e') => formatted.push_str(&format!("{:>2}", self.day)),
+                Some('j') => {
+                    let first = Self {
+                        month: 1,
+                        day: 1,
+                        ..*self
+                    };
+                    formatted.push_str(&format!("{:03}", self.days() - first.days() + 1))
+                }
+                Some('b') => formatted.push_str(&month[..3]),
+                Some('B') => formatted.push_str(month),
+                Some('a') => formatted.push_str(&weekday[..3]),
+                Some('A') => formatted.push_str(weekday),
+                Some('F') => formatted.push_str(&self.to_string()),
+                Some('%') => formatted.push('%'),
+                Some(other) => {
+                    formatted.push('%');
+                    formatted.push(other);
+                }
+                None => formatted.push('%'),
+            }
+        }
+        formatted
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+impl Time {
+    /// The current time of day in the local time zone
+    pub fn now() -> Self {
+        let seconds = local_timestamp().rem_euclid(86_400) as u32;
+        Self {
+            hour: seconds / 3600,
+            minute: seconds % 3600 / 60,
+        }
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+impl FromStr for Time {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TodoError::ParseError(format!("Expected a time like 09:30. Found '{s}'"));
+        let (hour, minute) = s.split_once(':').ok_or_else(invalid)?;
+        if hour.is_empty() || hour.len() > 2 || minute.len() != 2 {
+            return Err(invalid());
+        }
+        let time = Self {
+            hour: hour.parse().map_err(|_| invalid())?,
+            minute: minute.parse().map_err(|_| invalid())?,
+        };
+        if time.hour > 23 || time.minute > 59 {
+            return Err(invalid());
+        }
+        Ok(time)
+    }
+}
+
+/// Parses `YYYY-MM-DD` with an optional `THH:MM` time. A time can have an offset from UTC like
+/// `THH:MM+02:00` or `THH:MMZ`, in which case the date and time are converted to the time zone
+/// of the lists
+pub fn parse_date_time(s: &str) -> Result<(Date, Option<Time>), TodoError> {
+    let Some((date, time)) = s.split_once('T') else {
+        return Ok((s.parse()?, None));
+    };
+    let date = date.parse::<Date>()?;
+    let Some(at) = time.find(['+', '-', 'Z']) else {
+        return Ok((date, Some(time.parse()?)));
+    };
+    let TimeZone::Fixed(offset) = time[at..].parse()? else {
+        return Err(TodoError::ParseError(format!(
+            "Expected an offset like +02:00. Found '{}'",
+            &time[at..]
+        )));
+    };
+    let time = time[..at].parse::<Time>()?;
+    let utc = date.days() * 86_400 + i64::from(time.hour * 3600 + time.minute * 60) - offset * 60;
+    let local = utc + TimeZone::current().offset(utc);
+    let seconds = local.rem_euclid(86_400) as u32;
+    Ok((
+        Date::from_days(local.div_euclid(86_400)),
+        Some(Time {
+            hour: seconds / 3600,
+            minute: seconds % 3600 / 60,
+        }),
+    ))
+}
+
+/// Parses a date, or a span before today like `3d`, `2w` or `1m` (30 days)
+pub fn parse_since(s: &str) -> Result<Date, TodoError> {
+    let days = match s.char_indices().last() {
+        Some((i, unit @ ('d' | 'w' | 'm'))) => s[..i].parse::<i64>().ok().map(|n| match unit {
+            'd' => n,
+            'w' => n * 7,
+            _ => n * 30,
+        }),
+        _ => None,
+    };
+    match days {
+        Some(days) => Ok(Date::today().add_days(-days)),
+        None => s.parse().map_err(|_| {
+            TodoError::ParseError(format!("Expected a date or a span like 1w. Found '{s}'"))
+        }),
+    }
+}
+
+/// Seconds the local time zone is ahead of UTC at `timestamp`
+fn utc_offset(timestamp: i64) -> i64 {
+    #[cfg(unix)]
+    {
+        let time = timestamp as libc::time_t;
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        // SAFETY: localtime_r only reads `time` and writes into `tm`
+        if !unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+            return tm.tm_gmtoff as i64;
+        }
+    }
+    let _ = timestamp;
+    0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl FromStr for Date {
+    type Err = TodoError;
+
+    /// Parses `YYYY-MM-DD` as well as `today`, `tomorrow` and `yesterday`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "today" => return Ok(Self::today()),
+            "tomorrow" => return Ok(Self::today().add_days(1)),
+            "yesterday" => return Ok(Self::today().add_days(-1)),
+            _ => {}
+        }
+        let invalid =
+            || TodoError::ParseError(format!("Expected a date like 2025-07-01. Found '{s}'"));
+        let mut parts = s.splitn(3, '-');
+        let mut part = || parts.next().ok_or_else(invalid);
+        let (year, month, day) = (part()?, part()?, part()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(invalid());
+        }
+        let date = Self {
+            year: year.parse().map_err(|_| invalid())?,
+            month: month.parse().map_err(|_| invalid())?,
+            day: day.parse().map_err(|_| invalid())?,
+        };
+        if !(1..=12).contains(&date.month)
+            || !(1..=days_in_month(date.year, date.month)).contains(&date.day)
+        {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(
+            date("2026-10-14"),
+            Date {
+                year: 2026,
+                month: 10,
+                day: 14
+            }
+        );
+        for valid in [
+            "2024-02-29",
+            "2000-02-29",
+            "2026-01-31",
+            "2026-04-30",
+            "0001-01-01",
+        ] {
+            assert_eq!(date(valid).to_string(), valid);
+        }
+        for invalid in [
+            "2025-02-29",
+            "1900-02-29",
+            "2026-04-31",
+            "2026-13-01",
+            "2026-00-10",
+            "2026-10-00",
+            "2026-4-01",
+            "26-10-14",
+            "2026-10-14x",
+            "2026/10/14",
+            "",
+        ] {
+            assert!(invalid.parse::<Date>().is_err(), "{invalid}");
+        }
+        assert_eq!(date("today"), Date::today());
+        assert_eq!(date("tomorrow"), Date::today().add_days(1));
+        assert_eq!(date("yesterday"), Date::today().add_days(-1));
+    }
+
+    #[test]
+    fn counts_days() {
+        assert_eq!(date("1970-01-01").days(), 0);
+        assert_eq!(date("1969-12-31").days(), -1);
+        assert_eq!(date("2000-03-01").days(), 11_017);
+        for days in [-800_000, -1, 0, 59, 60, 11_016, 20_740, 800_000] {
+            assert_eq!(Date::from_days(days).days(), days);
+        }
+    }
+
+    #[test]
+    fn adds_days_across_month_and_year_ends() {
+        assert_eq!(date("2024-02-28").add_days(1), date("2024-02-29"));
+        assert_eq!(date("2024-02-28").add_days(2), date("2024-03-01"));
+        assert_eq!(date("2023-02-28").add_days(1), date("2023-03-01"));
+        assert_eq!(date("2024-03-01").add_days(-1), date("2024-02-29"));
+        assert_eq!(date("2025-12-31").add_days(1), date("2026-01-01"));
+        assert_eq!(date("2026-01-01").add_days(-1), date("2025-12-31"));
+        assert_eq!(date("2024-01-31").add_days(30), date("2024-03-01"));
+        assert_eq!(date("2024-01-01").add_days(366), date("2025-01-01"));
+    }
+
+    #[test]
+    fn shows_dates_relative_to_today() {
+        let relative = |days| Date::today().add_days(days).relative();
+        assert_eq!(relative(0), "today");
+        assert_eq!(relative(1), "tomorrow");
+        assert_eq!(relative(-1), "yesterday");
+        assert_eq!(relative(3), "in 3 days");
+        assert_eq!(relative(-13), "13 days ago");
+        assert_eq!(relative(20), "in 2w");
+        assert_eq!(relative(-90), "3mo ago");
+        assert_eq!(relative(400), "in 1y");
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(
+            date("2024-02-29").format("%Y %y %m %d %e %j %b %B %a %A %F %% %q %"),
+            "2024 24 02 29 29 060 Feb February Thu Thursday 2024-02-29 % %q %"
+        );
+        assert_eq!(date("2026-03-05").format("%e.%m."), " 5.03.");
+        assert_eq!(date("2023-12-31").format("%j %a"), "365 Sun");
+        assert_eq!(date("2024-12-31").format("%j %a"), "366 Tue");
+        assert_eq!(date("2005-01-01").format("%y"), "05");
+    }
+
+    #[test]
+    fn parses_times_and_time_zones() {
+        assert_eq!("9:30".parse::<Time>().unwrap().to_string(), "09:30");
+        for invalid in ["24:00", "12:60", "12", "123:00", "12:5", ":30"] {
+            assert!(invalid.parse::<Time>().is_err(), "{invalid}");
+        }
+        assert_eq!("+02:00".parse::<TimeZone>().unwrap(), TimeZone::Fixed(120));
+        assert_eq!("-0530".parse::<TimeZone>().unwrap(), TimeZone::Fixed(-330));
+        assert_eq!("+09".parse::<TimeZone>().unwrap(), TimeZone::Fixed(540));
+        assert_eq!("Z".parse::<TimeZone>().unwrap(), TimeZone::Fixed(0));
+        assert_eq!("local".parse::<TimeZone>().unwrap(), TimeZone::Local);
+        for invalid in ["+15:00", "+02:60", "02:00", "+2", "+02:0a"] {
+            assert!(invalid.parse::<TimeZone>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_date_times_with_offsets() {
+        let parse = |s| parse_date_time(s).unwrap();
+        assert_eq!(parse("2026-10-14"), (date("2026-10-14"), None));
+        assert_eq!(
+            parse("2026-10-14T09:30"),
+            (date("2026-10-14"), Some("09:30".parse().unwrap()))
+        );
+        // the same moments written with different offsets, even across days and years
+        assert_eq!(parse("2026-10-14T09:30+02:00"), parse("2026-10-14T07:30Z"));
+        assert_eq!(parse("2026-10-14T23:30-01:00"), parse("2026-10-15T00:30Z"));
+        assert_eq!(parse("2026-01-01T01:00+05:30"), parse("2025-12-31T19:30Z"));
+        assert_eq!(parse("2024-03-01T00:15+01:00"), parse("2024-02-29T23:15Z"));
+        assert_ne!(parse("2026-10-14T09:30+02:00"), parse("2026-10-14T09:30Z"));
+        for invalid in [
+            "2026-10-14T",
+            "2026-10-14T25:00",
+            "2026-02-30T09:30",
+            "2026-10-14T09:30+2",
+        ] {
+            assert!(parse_date_time(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_spans_since_today() {
+        let today = Date::today();
+        assert_eq!(parse_since("3d").unwrap(), today.add_days(-3));
+        assert_eq!(parse_since("2w").unwrap(), today.add_days(-14));
+        assert_eq!(parse_since("1m").unwrap(), today.add_days(-30));
+        assert_eq!(parse_since("0d").unwrap(), today);
+        assert_eq!(parse_since("2024-02-29").unwrap(), date("2024-02-29"));
+        assert_eq!(parse_since("yesterday").unwrap(), today.add_days(-1));
+        for invalid in ["d", "xd", "3y", "", "2024-02-30"] {
+            assert!(parse_since(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use todo::{date::Date, TodoItem, TodoList};
+
+use crate::config::Config;
+
+/// What happened in the lists since a day, and what's still open
+pub struct Digest {
+    since: Date,
+    completed: Vec<(String, TodoItem)>,
+    overdue: Vec<(String, TodoItem)>,
+    priorities: Vec<(String, TodoItem)>,
+}
+
+impl Digest {
+    /// Collects the items of the lists that aren't hidden
+    pub fn collect(config: &Config, since: Date) -> Result<Self> {
+        let today = Date::today();
+        let mut digest = Digest {
+            since,
+            completed: vec![],
+            overdue: vec![],
+            priorities: vec![],
+        };
+        for (name, item) in visible_items(config)? {
+            if item.is_done() {
+                if item.done_date().is_some_and(|done| done >= since) {
+                    digest.completed.push((name, item));
+                }
+                continue;
+            }
+            if item.due().is_some_and(|(due, _)| due < today) {
+                digest.overdue.push((name, item));
+            } else if item.field("priority").is_some() {
+                digest.priorities.push((name, item));
+            }
+        }
+        Ok(digest)
+    }
+
+    fn sections(&self) -> [(&str, &[(String, TodoItem)]); 3] {
+        [
+            ("Completed", &self.completed),
+            ("Overdue", &self.overdue),
+            ("Open priorities", &self.priorities),
+        ]
+    }
+
+    fn heading(&self) -> String {
+        format!("Digest for {} to {}", self.since, Date::today())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = self.heading();
+        for (title, items) in self.sections() {
+            text.push_str(&format!("\n\n{title} ({})", items.len()));
+            for (list, item) in items {
+                text.push_str(&format!("\n  - {} ({list})", item.name));
+            }
+        }
+        text
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = format!("<h1>{}</h1>\n", self.heading());
+        for (title, items) in self.sections() {
+            html.push_str(&format!("<h2>{title} ({})</h2>\n", items.len()));
+            if items.is_empty() {
+                continue;
+            }
+            html.push_str("<ul>\n");
+            for (list, item) in items {
+                html.push_str(&format!(
+                    "  <li>{} <small>({})</small></li>\n",
+                    escape_html(&item.name),
+                    escape_html(list)
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+        html
+    }
+}
+
+/// The items of the lists that aren't hidden, with the names of their lists
+pub fn visible_items(config: &Config) -> Result<Vec<(String, TodoItem)>> {
+    let mut items = vec![];
+    for name in config.list_names()? {
+        if config.is_hidden(&name) {
+            continue;
+        }
+        let list = TodoList::from_file(&config.list_path(&name))?;
+        items.extend(
+            list.numbered_items()
+                .map(|(_, item)| (name.clone(), item.clone())),
+        );
+    }
+    Ok(items)
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
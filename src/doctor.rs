@@ -0,0 +1,155 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Result};
+use todo::TodoList;
+
+use crate::config::Config;
+
+/// Collects the results of the checks done by `todo doctor`
+#[derive(Default)]
+struct Report {
+    problems: usize,
+    warnings: usize,
+}
+
+impl Report {
+    fn ok(&self, message: &str) {
+        println!("[ok] {message}");
+    }
+
+    /// Something that doesn't stop todo from working, but is most likely a mistake
+    fn warning(&mut self, message: &str, fix: &str) {
+        self.warnings += 1;
+        println!("[warning] {message}");
+        println!("          fix: {fix}");
+    }
+
+    fn problem(&mut self, message: &str, fix: &str) {
+        self.problems += 1;
+        println!("[problem] {message}");
+        println!("          fix: {fix}");
+    }
+}
+
+/// Checks the config at `config_path` (or the default one) and every list it knows of,
+/// printing what's wrong and how to fix it
+pub fn run(config_path: Option<&Path>) -> Result<()> {
+    let mut report = Report::default();
+    let config_path = config_path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(Config::default_config_path);
+
+    let config = match Config::read_from(&config_path) {
+        Ok(config) => {
+            report.ok(&format!("config at '{}' is valid", config_path.display()));
+            config
+        }
+        Err(e) => match Config::from_env() {
+            Some(config) if !config_path.exists() => {
+                report.ok("using the config from the environment");
+                config
+            }
+            _ => {
+                report.problem(
+                    &format!("config at '{}' can't be used: {e:#}", config_path.display()),
+                    "edit the file, or delete it and run `todo` to create a new one",
+                );
+                bail!("Found {} problem(s)", report.problems);
+            }
+        },
+    }
+    .with_env_overrides();
+
+    check_main_dir(&config, &mut report);
+    check_lists(&config, &mut report);
+
+    if report.problems > 0 {
+        bail!("Found {} problem(s)", report.problems);
+    }
+    match report.warnings {
+        0 => println!("No problems found"),
+        warnings => println!("No problems found, {warnings} warning(s)"),
+    }
+    Ok(())
+}
+
+fn check_main_dir(config: &Config, report: &mut Report) {
+    let main_dir = config.main_dir();
+    if !main_dir.is_dir() {
+        report.problem(
+            &format!("main dir '{}' doesn't exist", main_dir.display()),
+            &format!("create it with `mkdir -p '{}'`", main_dir.display()),
+        );
+        return;
+    }
+    let probe = main_dir.join(".todo-doctor");
+    match fs::write(&probe, "") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            report.ok(&format!("main dir '{}' is writable", main_dir.display()));
+        }
+        Err(e) => report.problem(
+            &format!("main dir '{}' is not writable: {e}", main_dir.display()),
+            "change the permissions of the directory, or `main_dir` with `todo config set`",
+        ),
+    }
+}
+
+fn check_lists(config: &Config, report: &mut Report) {
+    for (name, list) in config.lists() {
+        let Some(path) = list.path() else {
+            continue;
+        };
+        if config.main_dir().join(format!("{name}.md")).exists() {
+            report.problem(
+                &format!("'{name}' is both a registered list and a list in the main dir"),
+                &format!(
+                    "rename one of them, e.g. `todo config remove-list {name}` and add it back under another name"
+                ),
+            );
+        }
+        if !path.exists() {
+            report.problem(
+                &format!(
+                    "list '{name}' points to '{}' which doesn't exist",
+                    path.display()
+                ),
+                &format!("remove it with `todo config remove-list {name}`"),
+            );
+        }
+    }
+
+    let Ok(names) = config.list_names() else {
+        return;
+    };
+    for name in names {
+        let path = config.list_path(&name);
+        if !path.exists() {
+            continue;
+        }
+        match TodoList::from_file(&path) {
+            Ok(list) if list.invalid_lines().is_empty() => {
+                report.ok(&format!("list '{name}' is valid"))
+            }
+            Ok(list) => {
+                for error in list.invalid_lines() {
+                    report.warning(
+                        &format!(
+                            "list '{name}' at '{}' has a line {} (byte {}) that isn't read as an item: {}\n    {}",
+                            path.display(),
+                            error.line,
+                            error.offset,
+                            error.message,
+                            error.text
+                        ),
+                        &format!("edit line {} so it's an item or plain text", error.line),
+                    );
+                }
+            }
+            Err(e) => report.problem(
+                &format!("list '{name}' at '{}' can't be read: {e}", path.display()),
+                "edit the file so it can be read",
+            ),
+        }
+    }
+}
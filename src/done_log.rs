@@ -0,0 +1,43 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+use todo::{date::Date, TodoList};
+
+/// The items of `lists` done since `since` as markdown, grouped under the headings they are
+/// under in their lists, or under their first tag if `by_tag`
+pub fn render(lists: &[(String, PathBuf)], since: Date, by_tag: bool) -> Result<String> {
+    // groups keep the order they first show up in
+    let mut groups = Vec::<(String, Vec<String>)>::new();
+    let mut positions = BTreeMap::<String, usize>::new();
+    for (name, path) in lists {
+        let list = TodoList::from_file(path)?;
+        for (section, item) in list.sectioned_items() {
+            let Some(done) = item.done_date().filter(|done| *done >= since) else {
+                continue;
+            };
+            let group = if by_tag {
+                item.tags()
+                    .first()
+                    .map_or("Other".to_string(), |tag| format!("#{tag}"))
+            } else {
+                section.unwrap_or(name).to_string()
+            };
+            let position = *positions.entry(group.clone()).or_insert_with(|| {
+                groups.push((group, vec![]));
+                groups.len() - 1
+            });
+            groups[position]
+                .1
+                .push(format!("- {} ({done})", item.name_without_field("done")));
+        }
+    }
+
+    if groups.is_empty() {
+        return Ok(format!("Nothing was done since {since}"));
+    }
+    let sections = groups
+        .into_iter()
+        .map(|(group, items)| format!("## {group}\n\n{}", items.join("\n")))
+        .collect::<Vec<_>>();
+    Ok(sections.join("\n\n"))
+}
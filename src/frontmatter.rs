@@ -0,0 +1,132 @@
+//! The settings a list file can have in a `---` block at its top, like
+//!
+//! ```text
+//! ---
+//! title: Work projects
+//! tags: [work, q3]
+//! color: blue
+//! readonly: false
+//! archive: ../old
+//! ---
+//! ```
+//!
+//! Only flat `key: value` pairs and lists of values are read, not all of YAML. The block is
+//! kept in the file as it is
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use crate::style::Color;
+
+const FENCE: &str = "---";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frontmatter {
+    /// What the list is called when it's shown, instead of its file name
+    pub title: Option<String>,
+    /// Tags given to the items added to the list
+    pub tags: Vec<String>,
+    /// Color the name of the list is shown in
+    pub color: Option<Color>,
+    /// Whether the list needs `--unlock` to be changed
+    pub readonly: bool,
+    /// Where the list is moved when it's archived, relative to its file. A directory if it ends
+    /// with `/` or is one
+    pub archive: Option<PathBuf>,
+}
+
+impl Frontmatter {
+    /// The settings of the list file at `path`, reading only its block. `None` if it has none
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut lines = reader.lines();
+        if !lines.next().transpose()?.is_some_and(|l| is_fence(&l)) {
+            return Ok(None);
+        }
+        let mut block = vec![];
+        for line in lines {
+            let line = line?;
+            if is_fence(&line) {
+                return Ok(Some(Frontmatter::parse(&block)));
+            }
+            block.push(line);
+        }
+        Ok(None)
+    }
+
+    /// The settings in the lines between the fences. Other keys are left out, and so are values
+    /// that aren't valid for their key
+    pub fn parse(lines: &[impl AsRef<str>]) -> Self {
+        let mut frontmatter = Frontmatter::default();
+        let mut lines = lines.iter().map(AsRef::as_ref).peekable();
+        while let Some(line) = lines.next() {
+            let line = line.trim_end();
+            if line.trim_start().starts_with('#') || line.starts_with([' ', '\t', '-']) {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let mut values = vec![];
+            let value = value.trim();
+            if let Some(inline) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                values.extend(inline.split(',').map(unquote).filter(|v| !v.is_empty()));
+            } else if value.is_empty() {
+                // a list with an item on each of the following lines
+                while let Some(item) = lines.peek().and_then(|l| l.trim().strip_prefix('-')) {
+                    values.push(unquote(item));
+                    lines.next();
+                }
+            } else {
+                values.push(unquote(value));
+            }
+            let first = values.first().cloned();
+            match key.trim() {
+                "title" => frontmatter.title = first,
+                "tags" => {
+                    frontmatter.tags = values
+                        .iter()
+                        .flat_map(|v| v.split_whitespace())
+                        .map(str::to_string)
+                        .collect()
+                }
+                "color" => frontmatter.color = first.as_deref().and_then(Color::from_name),
+                "readonly" => {
+                    frontmatter.readonly = matches!(first.as_deref(), Some("true" | "yes" | "on"))
+                }
+                "archive" => frontmatter.archive = first.map(PathBuf::from),
+                _ => {}
+            }
+        }
+        frontmatter
+    }
+}
+
+fn is_fence(line: &str) -> bool {
+    line.trim_end() == FENCE
+}
+
+/// Number of lines the block at the start of `lines` takes, with its fences. 0 if the lines
+/// don't start with one or it isn't closed
+pub(crate) fn block_len(lines: impl IntoIterator<Item = impl AsRef<str>>) -> usize {
+    let mut lines = lines.into_iter();
+    if !lines.next().is_some_and(|l| is_fence(l.as_ref())) {
+        return 0;
+    }
+    lines
+        .position(|l| is_fence(l.as_ref()))
+        .map_or(0, |closing| closing + 2)
+}
+
+/// `value` without surrounding whitespace and quotes
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    ['"', '\'']
+        .into_iter()
+        .find_map(|q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+        .unwrap_or(value)
+        .to_string()
+}
@@ -0,0 +1,199 @@
+use std::{collections::BTreeSet, fs, path::Path, process::Command};
+
+use anyhow::{bail, Context, Result};
+
+use crate::sync;
+
+/// Runs git with `args` in `dir`, returning its stdout
+pub fn git(dir: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Couldn't run git. Is it installed?")?;
+    if !output.status.success() {
+        bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+pub fn is_in_repo(dir: &Path) -> bool {
+    git(dir, &["rev-parse", "--is-inside-work-tree"]).is_ok_and(|out| out.trim() == "true")
+}
+
+/// Commits the changes to `file`, and only those, if it is in a git repository
+pub fn commit_file(file: &Path, message: &str) -> Result<()> {
+    let Some(dir) = file.parent() else {
+        return Ok(());
+    };
+    if !is_in_repo(dir) {
+        return Ok(());
+    }
+    let file = file.to_string_lossy();
+    git(dir, &["add", "--", &file])?;
+    // nothing to commit if the file didn't change
+    if git(dir, &["diff", "--cached", "--quiet", "--", &file]).is_ok() {
+        return Ok(());
+    }
+    git(dir, &["commit", "--quiet", "-m", message, "--", &file])?;
+    Ok(())
+}
+
+/// The contents of `file` in the last commit
+pub fn head_version(file: &Path) -> Result<String> {
+    let (Some(dir), Some(name)) = (file.parent(), file.file_name()) else {
+        bail!("'{}' isn't a file", file.display());
+    };
+    if !is_in_repo(dir) {
+        bail!("'{}' is not in a git repository", file.display());
+    }
+    git(
+        dir,
+        &["show", &format!("HEAD:./{}", name.to_string_lossy())],
+    )
+}
+
+/// Commits the changes to the files in the repository at `dir` whose paths pass `include`,
+/// pulls the remote changes on top of them and pushes the result. Other changes are stashed
+/// while pulling
+pub fn sync(dir: &Path, include: &dyn Fn(&str) -> bool) -> Result<()> {
+    if !is_in_repo(dir) {
+        bail!(
+            "'{}' is not a git repository. Set it up with `git init` and `git remote add origin <url>`",
+            dir.display()
+        );
+    }
+    let changed = [
+        git(dir, &["diff", "--name-only", "--relative", "HEAD"]).unwrap_or_default(),
+        git(dir, &["ls-files", "--others", "--exclude-standard"])?,
+    ]
+    .concat();
+    let changed = changed
+        .lines()
+        .filter(|file| include(file))
+        .collect::<BTreeSet<_>>();
+    if !changed.is_empty() {
+        let mut add = vec!["add", "--all", "--"];
+        add.extend(&changed);
+        git(dir, &add)?;
+        let mut commit = vec!["commit", "--quiet", "-m", "todo: sync", "--"];
+        commit.extend(&changed);
+        git(dir, &commit)?;
+        println!("Committed the local changes");
+    }
+    let remotes = git(dir, &["remote"])?;
+    let Some(remote) = remotes
+        .lines()
+        .find(|r| *r == "origin")
+        .or(remotes.lines().next())
+    else {
+        bail!("There's no remote to sync with. Add one with `git remote add origin <url>`");
+    };
+    git(dir, &["fetch", "--quiet", remote])?;
+
+    // the remote branch doesn't exist yet on the first sync
+    if git(dir, &["rev-parse", "--verify", "--quiet", "@{upstream}"]).is_err() {
+        if git(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_err() {
+            println!("Nothing to sync yet");
+            return Ok(());
+        }
+        git(dir, &["push", "--quiet", "--set-upstream", remote, "HEAD"])?;
+        println!("Pushed the local changes");
+        return Ok(());
+    }
+
+    let has_commits = git(dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok();
+    let pulled = if has_commits {
+        git(dir, &["rebase", "--quiet", "--autostash", "@{upstream}"])
+    } else {
+        git(
+            dir,
+            &[
+                "merge",
+                "--quiet",
+                "--ff-only",
+                "--autostash",
+                "@{upstream}",
+            ],
+        )
+    };
+    if let Err(e) = pulled {
+        if !merge_conflicts(dir)? {
+            let conflicts = conflicted_files(dir)?;
+            if conflicts.is_empty() {
+                return Err(e);
+            }
+            let mut message = String::from("These lists were changed here and on the remote:\n");
+            for file in conflicts {
+                message.push_str(&format!("  {}\n", dir.join(file).display()));
+            }
+            message.push_str(&format!(
+                "Fix the lines between <<<<<<< and >>>>>>> in each of them, then run \
+                `git -C '{0}' add --all && git -C '{0}' rebase --continue` and `todo sync` again. \
+                To give up on syncing, run `git -C '{0}' rebase --abort`",
+                dir.display()
+            ));
+            bail!(message);
+        }
+    }
+    println!("Pulled the remote changes");
+
+    git(dir, &["push", "--quiet"])?;
+    println!("Pushed the local changes");
+    Ok(())
+}
+
+/// Files with conflicts in the repository at `dir`, relative to it
+fn conflicted_files(dir: &Path) -> Result<Vec<String>> {
+    let files = git(
+        dir,
+        &["diff", "--name-only", "--relative", "--diff-filter=U"],
+    )?;
+    Ok(files.lines().map(str::to_string).collect())
+}
+
+/// Merges the lists with conflicts in the rebase stopped in `dir` item by item, continuing the
+/// rebase until it's done. False if a conflict isn't in a list, leaving the rebase stopped
+fn merge_conflicts(dir: &Path) -> Result<bool> {
+    loop {
+        let conflicts = conflicted_files(dir)?;
+        if conflicts.is_empty() {
+            return Ok(false);
+        }
+        for file in conflicts {
+            if !file.ends_with(".md") {
+                return Ok(false);
+            }
+            // a missing version is a list deleted on that side
+            let stage = |n: u8| git(dir, &["show", &format!(":{n}:./{file}")]).unwrap_or_default();
+            // in a rebase, stage 2 is the upstream version and 3 the local one replayed on it
+            let merged = sync::merge_versions(
+                &file,
+                stage(1).as_bytes(),
+                stage(3).as_bytes(),
+                stage(2).as_bytes(),
+            )?;
+            let Some(merged) = merged else {
+                return Ok(false);
+            };
+            let path = dir.join(&file);
+            fs::write(&path, merged)
+                .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+            git(dir, &["add", "--", &file])?;
+        }
+        // the merged lists can be what upstream has already, leaving nothing to commit
+        let step = if git(dir, &["diff", "--cached", "--quiet"]).is_ok() {
+            "--skip"
+        } else {
+            "--continue"
+        };
+        if git(dir, &["-c", "core.editor=true", "rebase", step]).is_ok() {
+            return Ok(true);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use todo::TodoItem;
+
+use crate::{config::Config, webhook};
+
+/// Something that happened to items, that webhooks and hook scripts can act on
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemEvent {
+    Added,
+    Done,
+    Removed,
+}
+
+impl ItemEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ItemEvent::Added => "added",
+            ItemEvent::Done => "done",
+            ItemEvent::Removed => "removed",
+        }
+    }
+
+    /// The hook script run on this event
+    fn script(&self) -> &'static str {
+        match self {
+            ItemEvent::Added => "on-add",
+            ItemEvent::Done => "on-done",
+            ItemEvent::Removed => "on-remove",
+        }
+    }
+}
+
+/// Tells the webhooks and the hook script of `event` about `items` of the list `list`.
+/// Failures are only reported since the lists have already been written
+pub fn item_event(config: &Config, event: ItemEvent, list: &str, items: &[TodoItem]) {
+    if items.is_empty() {
+        return;
+    }
+    webhook::fire(config, event, list, items);
+    let script = config.hooks_dir().join(event.script());
+    if !script.is_file() {
+        return;
+    }
+    for item in items {
+        if let Err(e) = run_script(&script, event, list, item) {
+            eprintln!("The hook '{}' failed: {e:#}", script.display());
+        }
+    }
+}
+
+/// Env var with the path of the list written, for write hooks
+const LIST_PATH_VAR: &str = "TODO_LIST_PATH";
+
+/// Runs the pre-write `command` with `contents` on stdin. Returns what it printed, if
+/// anything, to be written instead. Fails if the command does, to stop the write
+pub fn pre_write(command: &str, path: &Path, contents: &str) -> Result<Option<String>> {
+    let mut child = shell(command)
+        .env(LIST_PATH_VAR, path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Couldn't run the pre-write hook '{command}'"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(contents.as_bytes());
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "The pre-write hook '{command}' rejected the change: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let printed = String::from_utf8(output.stdout)
+        .context("The pre-write hook printed something that isn't UTF-8")?;
+    Ok((!printed.trim().is_empty()).then_some(printed))
+}
+
+/// Runs the post-write `command`
+pub fn post_write(command: &str, path: &Path) -> Result<()> {
+    let status = shell(command)
+        .env(LIST_PATH_VAR, path)
+        .status()
+        .with_context(|| format!("Couldn't run the post-write hook '{command}'"))?;
+    if !status.success() {
+        bail!("The post-write hook '{command}' exited with {status}");
+    }
+    Ok(())
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    }
+}
+
+/// Runs `script` with the item as JSON on stdin
+fn run_script(script: &Path, event: ItemEvent, list: &str, item: &TodoItem) -> Result<()> {
+    let input = json!({
+        "event": event.name(),
+        "list": list,
+        "item": {
+            "title": item.name,
+            "done": item.is_done(),
+            "description": item.description,
+            "tags": item.tags(),
+        },
+    });
+    let mut child = Command::new(script)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Couldn't run it. Is it executable?")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // scripts that don't read their input close stdin early
+        let _ = stdin.write_all(input.to_string().as_bytes());
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("It exited with {status}");
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use todo::{TodoItem, TodoList};
+
+use crate::config::Config;
+
+/// The items of the lists read by commands showing items of every list, kept in the data dir
+/// when `index_lists` is on. Lists are only read again once their files changed
+#[derive(Default, Deserialize, Serialize)]
+pub struct ListIndex {
+    lists: BTreeMap<PathBuf, IndexedFile>,
+    #[serde(skip)]
+    enabled: bool,
+    /// whether lists were read since the index was loaded
+    #[serde(skip)]
+    changed: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct IndexedFile {
+    /// length and modification time in nanoseconds of the file when it was read
+    len: u64,
+    modified: u128,
+    items: Vec<TodoItem>,
+}
+
+impl ListIndex {
+    fn path() -> PathBuf {
+        dirs::data_dir()
+            .expect("OS data directory not found")
+            .join(env!("CARGO_PKG_NAME"))
+            .join("index.json")
+    }
+
+    /// The index saved in the data dir. An empty one that is never saved if `index_lists` is
+    /// off, and an empty one if the saved index can't be read
+    pub fn load(config: &Config) -> Self {
+        let enabled = *config.index_lists();
+        let saved = enabled
+            .then(|| fs::read_to_string(Self::path()).ok())
+            .flatten()
+            .and_then(|contents| serde_json::from_str::<ListIndex>(&contents).ok());
+        Self {
+            enabled,
+            ..saved.unwrap_or_default()
+        }
+    }
+
+    /// The items of the list at `path`, read from the file if it isn't in the index yet or
+    /// changed since it was indexed
+    pub fn items(&mut self, path: &Path) -> Result<Vec<TodoItem>> {
+        let metadata = fs::metadata(path)
+            .with_context(|| format!("Couldn't read the list at '{}'", path.display()))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos());
+        if let Some(indexed) = self.lists.get(path) {
+            if indexed.len == metadata.len() && indexed.modified == modified {
+                return Ok(indexed.items.clone());
+            }
+        }
+        let items = TodoList::from_file(path)?.into_iter().collect::<Vec<_>>();
+        if self.enabled {
+            self.lists.insert(
+                path.to_path_buf(),
+                IndexedFile {
+                    len: metadata.len(),
+                    modified,
+                    items: items.clone(),
+                },
+            );
+            self.changed = true;
+        }
+        Ok(items)
+    }
+
+    /// Saves the index if lists were read into it. Lists that are gone are dropped from it
+    pub fn save(mut self) -> Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        self.lists.retain(|path, _| path.exists());
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string(&self)?)
+            .with_context(|| format!("Couldn't save the list index to '{}'", path.display()))
+    }
+}
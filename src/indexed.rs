@@ -0,0 +1,177 @@
+//! Changing single items of large list files without reading the whole list into memory or
+//! writing all of the file again
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{frontmatter, CodeFences, TodoError, TodoItem};
+
+/// Where the items of a list file are. Items are read one at a time, and changing one writes only
+/// its line if the line keeps its length. Otherwise the rest of the file is copied around the new
+/// line into a new file that replaces the list, so a crash never leaves it half written.
+/// Descriptions are left as they are
+pub struct IndexedList {
+    path: PathBuf,
+    /// Byte range of the task line of every item, without the line break
+    items: Vec<Range<u64>>,
+    /// Length and modification time of the file when it was indexed or last written
+    stamp: (u64, Option<SystemTime>),
+}
+
+impl IndexedList {
+    /// Finds the items of the list at `path`. Lines that look like items but aren't valid are
+    /// left out, like [`crate::TodoList::from_file`] does
+    pub fn open(path: &Path) -> Result<Self, TodoError> {
+        let frontmatter = frontmatter::block_len(
+            BufReader::new(File::open(path)?)
+                .lines()
+                .map_while(Result::ok),
+        );
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut items = vec![];
+        let mut line = vec![];
+        let mut offset = 0;
+        let mut number = 0;
+        let mut fences = CodeFences::default();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            number += 1;
+            let text = std::str::from_utf8(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            let text = text
+                .strip_suffix('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                .unwrap_or(text);
+            let code = number <= frontmatter || fences.is_code(text);
+            if !code && text.parse::<TodoItem>().is_ok() {
+                items.push(offset..offset + text.len() as u64);
+            }
+            offset += read as u64;
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            items,
+            stamp: stamp(path)?,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The item `item_number`, without its description
+    pub fn get(&self, item_number: usize) -> Result<TodoItem, TodoError> {
+        let range = self.range(item_number)?;
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(range.start))?;
+        let mut line = vec![0; (range.end - range.start) as usize];
+        file.read_exact(&mut line)?;
+        String::from_utf8(line)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .parse()
+    }
+
+    /// Changes the item `item_number` with `change` and writes its line. Fails if the file
+    /// changed since it was indexed
+    pub fn update(
+        &mut self,
+        item_number: usize,
+        change: impl FnOnce(&mut TodoItem),
+    ) -> Result<TodoItem, TodoError> {
+        if stamp(&self.path)? != self.stamp {
+            return Err(TodoError::ChangedOnDisk(self.path.clone()));
+        }
+        let range = self.range(item_number)?;
+        let mut item = self.get(item_number)?;
+        change(&mut item);
+        let line = item.as_markdown();
+
+        let old_len = range.end - range.start;
+        let new_len = line.len() as u64;
+        if new_len == old_len {
+            let mut file = OpenOptions::new().write(true).open(&self.path)?;
+            file.seek(SeekFrom::Start(range.start))?;
+            file.write_all(line.as_bytes())?;
+            file.sync_data()?;
+        } else {
+            crate::write_atomically_with(&self.path, |new| {
+                let mut file = File::open(&self.path)?;
+                io::copy(&mut (&mut file).take(range.start), new)?;
+                new.write_all(line.as_bytes())?;
+                file.seek(SeekFrom::Start(range.end))?;
+                io::copy(&mut file, new)?;
+                Ok(())
+            })?;
+            self.items[item_number - 1].end = range.start + new_len;
+            for later in &mut self.items[item_number..] {
+                later.start = later.start + new_len - old_len;
+                later.end = later.end + new_len - old_len;
+            }
+        }
+        self.stamp = stamp(&self.path)?;
+        Ok(item)
+    }
+
+    /// Marks the item `item_number` done, or not done if it is
+    pub fn toggle(&mut self, item_number: usize) -> Result<TodoItem, TodoError> {
+        self.update(item_number, |item| {
+            if item.is_done() {
+                item.reopen()
+            } else {
+                item.mark_done()
+            }
+        })
+    }
+
+    fn range(&self, item_number: usize) -> Result<Range<u64>, TodoError> {
+        item_number
+            .checked_sub(1)
+            .and_then(|i| self.items.get(i))
+            .cloned()
+            .ok_or(TodoError::InvalidItemNumber(item_number))
+    }
+}
+
+fn stamp(path: &Path) -> Result<(u64, Option<SystemTime>), TodoError> {
+    let metadata = fs::metadata(path)?;
+    Ok((metadata.len(), metadata.modified().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::TodoList;
+
+    #[test]
+    fn numbers_items_like_the_whole_list() {
+        let path = env::temp_dir().join(format!("todo-indexed.{}.md", std::process::id()));
+        fs::write(
+            &path,
+            "---\ntags: [a]\n---\n> - [ ] quoted\n```\n- [ ] in code\n```\n1. [ ]\n2. [ ] numbered\n",
+        )
+        .unwrap();
+        let mut indexed = IndexedList::open(&path).unwrap();
+        assert_eq!(indexed.len(), TodoList::from_file(&path).unwrap().len());
+        assert_eq!(indexed.get(2).unwrap().name, "numbered");
+        indexed.toggle(2).unwrap();
+        let list = TodoList::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(list.get_item(2).unwrap().is_done());
+        assert!(!list.get_item(1).unwrap().is_done());
+    }
+}
@@ -0,0 +1,67 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Result;
+use todo::{date::Date, TodoList};
+
+use crate::{config::Config, create_list, ListWriter};
+
+/// Dir in the main dir the journals are kept in
+const JOURNAL_DIR: &str = "journal";
+
+/// Tag of the items carried over from an earlier journal
+const CARRIED_TAG: &str = "#carried";
+
+/// Name of the journal list of `date`, e.g. `journal/2025-06-10`
+pub fn list_name(date: Date) -> String {
+    format!("{JOURNAL_DIR}/{date}")
+}
+
+/// Today's journal, created with the open items of the last journal if it doesn't exist yet
+pub fn open_today(writer: &ListWriter) -> Result<(String, PathBuf)> {
+    let config = writer.config;
+    let today = Date::today();
+    let name = list_name(today);
+    let path = config.list_path(&name);
+    if path.exists() {
+        return Ok((name, path));
+    }
+
+    let carried = match last_journal(config, today) {
+        Some(last) => TodoList::from_file(&config.list_path(&list_name(last)))?
+            .numbered_items()
+            .filter(|(_, item)| !item.is_done())
+            .map(|(_, item)| {
+                let mut item = item.clone();
+                if !item.name.split_whitespace().any(|word| word == CARRIED_TAG) {
+                    item.name = format!("{} {CARRIED_TAG}", item.name);
+                }
+                item
+            })
+            .collect(),
+        None => vec![],
+    };
+    create_list(writer, &path, &name, None)?;
+    if !carried.is_empty() {
+        let mut list = TodoList::from_file(&path)?;
+        let count = carried.len();
+        list.add_items(carried);
+        writer.write(&mut list, &path, &format!("carry over {count} item(s)"))?;
+        println!("Carried over {count} open item(s)");
+    }
+    Ok((name, path))
+}
+
+/// The date of the latest journal before `date`
+fn last_journal(config: &Config, date: Date) -> Option<Date> {
+    fs::read_dir(config.main_dir().join(JOURNAL_DIR))
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "md" {
+                return None;
+            }
+            path.file_stem()?.to_str()?.parse::<Date>().ok()
+        })
+        .filter(|journal| *journal < date)
+        .max()
+}
@@ -1,15 +1,124 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fmt::{Debug, Display},
-    fs, io,
-    path::Path,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
     str::FromStr,
 };
 
+use frontmatter::Frontmatter;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use style::{DoneStyle, Glyphs, Style, Theme};
+use text::Inline;
 use thiserror::Error;
 
+#[cfg(feature = "async")]
+mod async_io;
+pub mod date;
+pub mod frontmatter;
+pub mod indexed;
+pub mod query;
+pub mod regex;
+pub mod style;
+pub mod text;
+
+/// Fuzzy duplicates may differ in this many characters
+const MAX_FUZZY_DUPLICATE_DISTANCE: usize = 2;
+/// Shorter titles only count as fuzzy duplicates if they are the same once normalized
+const MIN_FUZZY_DUPLICATE_LENGTH: usize = 8;
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TodoList {
     pub name: String,
-    list: Vec<TodoItem>,
+    #[cfg_attr(feature = "serde", serde(rename = "elements"))]
+    list: Vec<ListElement>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    line_ending: LineEnding,
+    /// The file the list was read from and a hash of its contents back then
+    #[cfg_attr(feature = "serde", serde(skip))]
+    source: Option<(PathBuf, u64)>,
+    /// Lines that look like items but couldn't be read as ones. They're kept as text
+    #[cfg_attr(feature = "serde", serde(skip))]
+    invalid_lines: Vec<LineError>,
+}
+
+/// What [`TodoList::merge`] does with items that are in both lists
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MergeStrategy {
+    /// Keep both copies
+    KeepBoth,
+    /// Keep one copy, the done one if either is
+    #[default]
+    PreferDone,
+    /// Keep the copy done last. The one merged in if that doesn't tell them apart
+    PreferNewer,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep-both" => Ok(MergeStrategy::KeepBoth),
+            "prefer-done" => Ok(MergeStrategy::PreferDone),
+            "prefer-newer" => Ok(MergeStrategy::PreferNewer),
+            _ => Err(TodoError::ParseError(format!(
+                "Expected keep-both, prefer-done or prefer-newer. Found '{s}'"
+            ))),
+        }
+    }
+}
+
+/// A difference between two versions of a list, from [`TodoList::diff`]. Lists of them are
+/// patches for [`TodoList::apply`]
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "change", rename_all = "snake_case")
+)]
+pub enum ListChange {
+    Added(TodoItem),
+    Removed(TodoItem),
+    /// The item got another title, and maybe its state changed too
+    Retitled {
+        from: TodoItem,
+        to: TodoItem,
+    },
+    /// The item was done or reopened
+    StateChanged {
+        from: TodoItem,
+        to: TodoItem,
+    },
+}
+
+/// Line ending used when writing a list file
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+/// A line (or a group of lines) of a list file
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+enum ListElement {
+    Item(TodoItem),
+    /// Anything that is not an item, like headings and notes. Written back as is
+    Text(String),
 }
 
 impl TodoList {
@@ -17,74 +126,345 @@ impl TodoList {
         Self {
             name: name.to_string(),
             list: vec![],
+            line_ending: LineEnding::default(),
+            source: None,
+            invalid_lines: vec![],
         }
     }
 
     pub fn from_file(path: &Path) -> Result<Self, TodoError> {
         let name = path.file_name().unwrap();
         let file_contents = fs::read_to_string(path)?;
-        let list = TodoList::list_from_str(&file_contents)?;
+        let mut list = TodoList::from_contents(&name.to_string_lossy(), &file_contents)?;
+        list.source = Some((path.to_path_buf(), hash_contents(&file_contents)));
+        Ok(list)
+    }
+
+    /// Reads a list from `reader`, e.g. from memory or a socket. Its name is empty, set `name`
+    /// if needed
+    pub fn from_reader(mut reader: impl BufRead) -> Result<Self, TodoError> {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        TodoList::from_contents("", &contents)
+    }
+
+    fn from_contents(name: &str, contents: &str) -> Result<Self, TodoError> {
+        let line_ending = if contents.contains("\r\n") {
+            LineEnding::Crlf
+        } else {
+            LineEnding::Lf
+        };
+        let (list, invalid_lines) = TodoList::list_from_str(contents);
         Ok(Self {
-            name: name.to_string_lossy().to_string(),
+            name: name.to_string(),
             list,
+            line_ending,
+            source: None,
+            invalid_lines,
         })
     }
 
-    fn list_from_str(s: &str) -> Result<Vec<TodoItem>, TodoError> {
+    /// Line ending the list is written with. Lists read from a file keep the file's line ending
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+
+    /// The elements of the list in markdown `s` and the lines of it that look like items but
+    /// aren't valid ones
+    fn list_from_str(s: &str) -> (Vec<ListElement>, Vec<LineError>) {
         // todo: maybe try nom or smn
-        let lines = s.lines();
-        let mut list: Vec<TodoItem> = vec![];
-        for line in lines {
-            let item: Result<TodoItem, _> = line.parse();
-            if let Err(err) = item {
-                // concat to last's desciption if invalid todo item
-                if let Some(last) = list.last_mut() {
-                    if let Some(desc) = &last.description {
-                        last.description = Some(format!("{}\n{}", desc, line));
-                    } else {
-                        last.description = Some(line.to_string());
+        let mut list: Vec<ListElement> = vec![];
+        let mut errors = vec![];
+        let mut offset = 0;
+        let mut fences = CodeFences::default();
+        let frontmatter = frontmatter::block_len(s.lines());
+        for (i, raw_line) in s.split_inclusive('\n').enumerate() {
+            let line_offset = offset;
+            offset += raw_line.len();
+            let line = raw_line
+                .strip_suffix('\n')
+                .map(|l| l.strip_suffix('\r').unwrap_or(l))
+                .unwrap_or(raw_line);
+            if i < frontmatter {
+                list.push(ListElement::Text(line.to_string()));
+                continue;
+            }
+            let item = (!fences.is_code(line)).then(|| line.parse::<TodoItem>());
+            match item {
+                Some(Ok(item)) => list.push(ListElement::Item(item)),
+                // lines that look like items but aren't valid are most likely typos. They're
+                // kept as they are, so the rest of the list can still be used
+                Some(Err(err)) if looks_like_item(line) => {
+                    errors.push(LineError::new(i + 1, line_offset, line, err));
+                    list.push(ListElement::Text(line.to_string()));
+                }
+                _ => {
+                    // lines right after an item indented deeper than it are its description
+                    if let Some(ListElement::Item(last)) = list.last_mut() {
+                        let indent = |s: &str| s.len() - s.trim_start().len();
+                        let item_indent = last.raw.as_deref().map_or(0, indent);
+                        if indent(line) > item_indent && !line.trim().is_empty() {
+                            last.description = Some(match &last.description {
+                                Some(desc) => format!("{desc}\n{line}"),
+                                None => line.to_string(),
+                            });
+                            continue;
+                        }
                     }
+                    list.push(ListElement::Text(line.to_string()));
+                }
+            }
+        }
+        (list, errors)
+    }
+
+    /// Lines of the list that look like items, like `- [ ]` without a title, but couldn't be
+    /// read as ones. `todo doctor` shows them
+    pub fn invalid_lines(&self) -> &[LineError] {
+        &self.invalid_lines
+    }
+
+    /// The settings in the `---` block at the top of the list, if it has one
+    pub fn frontmatter(&self) -> Option<Frontmatter> {
+        let head = self.list.iter().map_while(|element| match element {
+            ListElement::Text(line) => Some(line.as_str()),
+            ListElement::Item(_) => None,
+        });
+        let len = frontmatter::block_len(head.clone());
+        (len > 0).then(|| Frontmatter::parse(&head.skip(1).take(len - 2).collect::<Vec<_>>()))
+    }
+
+    /// The items in list order, without the headings and notes between them
+    pub fn items(&self) -> Items<'_> {
+        Items(self.list.iter())
+    }
+
+    pub fn items_mut(&mut self) -> ItemsMut<'_> {
+        ItemsMut(self.list.iter_mut())
+    }
+
+    /// Items with their indexes, which start at 0. Item numbers shown to users are one more
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &TodoItem)> {
+        self.items().enumerate()
+    }
+
+    /// Items with their numbers, which start at 1
+    pub fn numbered_items(&self) -> impl Iterator<Item = (usize, &TodoItem)> {
+        self.items().enumerate().map(|(i, item)| (i + 1, item))
+    }
+
+    /// Items with the markdown heading they are under, if any
+    pub fn sectioned_items(&self) -> impl Iterator<Item = (Option<&str>, &TodoItem)> {
+        let mut section = None;
+        self.list.iter().filter_map(move |e| match e {
+            ListElement::Text(line) => {
+                if line.starts_with('#') {
+                    section = Some(line.trim_start_matches('#').trim());
+                }
+                None
+            }
+            ListElement::Item(item) => Some((section, item)),
+        })
+    }
+
+    /// Groups of the numbers of items with the same title, ignoring when they were done. With
+    /// `fuzzy`, titles that differ in case, punctuation or a typo or two count as the same.
+    /// Each group is in list order and has at least two items
+    pub fn duplicates(&self, fuzzy: bool) -> Vec<Vec<usize>> {
+        let keys = self
+            .items()
+            .map(|item| {
+                let title = item.name_without_field("done");
+                if fuzzy {
+                    text::normalize(&title)
                 } else {
-                    return Err(err);
+                    title
                 }
-            } else {
-                list.push(item.unwrap());
+            })
+            .collect::<Vec<_>>();
+        let same = |a: &str, b: &str| {
+            a == b
+                || fuzzy
+                    && a.chars().count().min(b.chars().count()) >= MIN_FUZZY_DUPLICATE_LENGTH
+                    && text::edit_distance(a, b) <= MAX_FUZZY_DUPLICATE_DISTANCE
+        };
+        let mut grouped = vec![false; keys.len()];
+        let mut groups = vec![];
+        for i in 0..keys.len() {
+            if grouped[i] {
+                continue;
+            }
+            let group = (i..keys.len())
+                .filter(|&j| !grouped[j] && same(&keys[i], &keys[j]))
+                .collect::<Vec<_>>();
+            if group.len() > 1 {
+                group.iter().for_each(|&j| grouped[j] = true);
+                groups.push(group.into_iter().map(|j| j + 1).collect());
             }
         }
-        Ok(list)
+        groups
+    }
+
+    /// Paths of the lists included with `@include <path>` lines
+    pub fn includes(&self) -> impl Iterator<Item = &str> {
+        self.list.iter().filter_map(|e| match e {
+            ListElement::Text(line) => line.strip_prefix("@include ").map(str::trim),
+            ListElement::Item(_) => None,
+        })
+    }
+
+    /// Numbers of the items matching `query`
+    pub fn filter(&self, query: &query::Query) -> Vec<usize> {
+        self.numbers_where(|item| query.matches(item))
+    }
+
+    /// Numbers of the items whose title contains `text`, ignoring case and punctuation. If none
+    /// does, the ones with the characters of `text` in their title in order, so typing a few
+    /// letters of each word is enough
+    pub fn find_by_title(&self, text: &str) -> Vec<usize> {
+        let needle = text::normalize(text);
+        let found = self.numbers_where(|item| text::normalize(&item.name).contains(&needle));
+        if !found.is_empty() {
+            return found;
+        }
+        self.numbers_where(|item| text::is_subsequence(&needle, &text::normalize(&item.name)))
+    }
+
+    /// Numbers of the items whose title matches `regex`
+    pub fn find_regex(&self, regex: &regex::Regex) -> Vec<usize> {
+        self.numbers_where(|item| regex.is_match(&item.name))
+    }
+
+    /// Number of the item with the ID `id`, with or without its `@`. See [`TodoItem::id`]
+    pub fn find_by_id(&self, id: &str) -> Result<usize, TodoError> {
+        let id = id.trim_start_matches('@').to_lowercase();
+        match self.numbers_where(|item| item.id() == Some(&id)).as_slice() {
+            [] => Err(TodoError::UnknownItemId(id)),
+            [number] => Ok(*number),
+            _ => Err(TodoError::AmbiguousItemId(id)),
+        }
+    }
+
+    /// Gives the items without an ID, or with one an earlier item has too, an `id:` field with a
+    /// new ID. The number of items given one
+    pub fn assign_ids(&mut self) -> usize {
+        let mut taken = BTreeSet::new();
+        let mut assigned = 0;
+        for item in self.items_mut() {
+            if let Some(id) = item.id().filter(|id| !taken.contains(*id)) {
+                taken.insert(id.to_string());
+                continue;
+            }
+            let id = (0_u64..)
+                .map(|salt| {
+                    let hash = text::fnv1a(format!("{salt} {}", item.name).as_bytes());
+                    format!("{:06x}", (hash ^ (hash >> 32)) & 0xff_ffff)
+                })
+                .find(|id| !taken.contains(id))
+                .expect("not all IDs are taken");
+            item.set_field("id", &id);
+            taken.insert(id);
+            assigned += 1;
+        }
+        assigned
+    }
+
+    fn numbers_where<P>(&self, predicate: P) -> Vec<usize>
+    where
+        P: Fn(&TodoItem) -> bool,
+    {
+        self.numbered_items()
+            .filter(|(_, item)| predicate(item))
+            .map(|(number, _)| number)
+            .collect()
     }
 
-    pub fn display_with_numbers<P>(&self, predicate: P) -> String
+    pub fn display_with_numbers<P>(&self, predicate: P, options: &DisplayOptions) -> String
     where
         P: FnMut(&(usize, &TodoItem)) -> bool,
     {
-        self.list
-            .iter()
+        let mut shown = self
+            .items()
             .enumerate()
             .filter(predicate)
-            .map(|(i, item)| format!("{: >3} {item}", i + 1)) // padding will be good till 3
-            // digits - todo: check how we can remove this limit
+            .collect::<Vec<_>>();
+        let highest = shown.last().map_or(0, |(i, _)| i + 1);
+        if options.theme.done_items == DoneStyle::Bottom {
+            shown.sort_by_key(|(_, item)| item.is_done());
+        }
+        let options = options.fitting_numbers(highest);
+        shown
+            .into_iter()
+            .map(|(i, item)| item.render_numbered(i + 1, &options))
             .collect::<Vec<String>>()
             .join("\n")
     }
 
+    /// Number of items in the list
+    pub fn len(&self) -> usize {
+        self.items().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items().next().is_none()
+    }
+
+    /// Number of items marked done
+    pub fn done_count(&self) -> usize {
+        self.items().filter(|i| i.is_done()).count()
+    }
+
+    /// A bar like `[#####....] 5/9` showing how many items are done
+    pub fn progress_bar(&self, options: &DisplayOptions) -> String {
+        const BAR_WIDTH: usize = 10;
+        let (done, total) = (self.done_count(), self.len());
+        if options.glyphs.bar_done.is_empty() {
+            return format!("{done}/{total} done");
+        }
+        let filled = (done * BAR_WIDTH).checked_div(total).unwrap_or(0);
+        format!(
+            "[{}{}] {done}/{total}",
+            options.paint(
+                &options.theme.done_marker,
+                &options.glyphs.bar_done.repeat(filled)
+            ),
+            options.glyphs.bar_open.repeat(BAR_WIDTH - filled)
+        )
+    }
+
     pub fn as_markdown(&self) -> String {
         self.list
             .iter()
-            .map(|i| format!("- [{}] {}", i.state.as_markdown(), i.name))
+            .map(|e| match e {
+                ListElement::Item(i) => i.as_markdown(),
+                ListElement::Text(line) => line.clone(),
+            })
             .collect::<Vec<String>>()
             .join("\n")
     }
 
+    /// Replaces the contents of the list with the list in markdown `s`, e.g. after the file was
+    /// formatted. The file the list was read from is kept
+    pub fn set_markdown(&mut self, s: &str) -> Result<(), TodoError> {
+        (self.list, self.invalid_lines) = TodoList::list_from_str(s);
+        Ok(())
+    }
+
     pub fn get_item_mut(&mut self, item_number: usize) -> Result<&mut TodoItem, TodoError> {
-        self.list
-            .get_mut(item_number - 1)
-            .ok_or_else(|| TodoError::InvalidItemNumber(item_number))
+        item_number
+            .checked_sub(1)
+            .and_then(|i| self.items_mut().nth(i))
+            .ok_or(TodoError::InvalidItemNumber(item_number))
     }
     pub fn get_item(&self, item_number: usize) -> Result<&TodoItem, TodoError> {
-        self.list
-            .get(item_number - 1)
-            .ok_or_else(|| TodoError::InvalidItemNumber(item_number))
+        item_number
+            .checked_sub(1)
+            .and_then(|i| self.items().nth(i))
+            .ok_or(TodoError::InvalidItemNumber(item_number))
     }
 
     pub fn mark_item_done(&mut self, item_number: usize) -> Result<&TodoItem, TodoError> {
@@ -98,8 +478,14 @@ impl TodoList {
             name: item_title.to_string(),
             description: None,
             state: TodoItemState::Initial,
+            raw: None,
         };
-        self.list.push(item);
+        self.list.push(ListElement::Item(item));
+    }
+
+    /// Adds a line that is not an item, like a heading or a note
+    pub fn add_line(&mut self, line: &str) {
+        self.list.push(ListElement::Text(line.to_string()));
     }
 
     pub fn delete_items(&mut self, item_numbers: Vec<usize>) -> Result<Vec<TodoItem>, TodoError> {
@@ -107,25 +493,543 @@ impl TodoList {
             .iter()
             .map(|&i| self.get_item(i).cloned())
             .collect::<Result<Vec<_>, _>>()?;
-        // this implementation will remove items with the same name - is a fix to this needed?
-        self.list
-            .retain(|i| !items_to_remove.iter().any(|r| r.name == i.name));
+        let mut item_number = 0;
+        self.list.retain(|e| match e {
+            ListElement::Item(_) => {
+                item_number += 1;
+                !item_numbers.contains(&item_number)
+            }
+            ListElement::Text(_) => true,
+        });
         Ok(items_to_remove)
     }
 
-    pub fn add_items(&mut self, mut items: Vec<TodoItem>) {
-        self.list.append(&mut items);
+    pub fn add_items(&mut self, items: Vec<TodoItem>) {
+        self.list.extend(items.into_iter().map(ListElement::Item));
     }
 
+    /// Appends the items of `other`. Items with the same title as one already in the list,
+    /// ignoring when they were done, are handled as `strategy` says. With `keep_sections`, the
+    /// headings and notes of `other` are appended too, so its items stay in their sections.
+    /// Returns the copies of duplicates that were left out
+    pub fn merge(
+        &mut self,
+        other: &TodoList,
+        strategy: MergeStrategy,
+        keep_sections: bool,
+    ) -> Vec<TodoItem> {
+        let mut dropped = vec![];
+        for element in &other.list {
+            let incoming = match element {
+                ListElement::Item(item) => item,
+                ListElement::Text(_) if keep_sections => {
+                    self.list.push(element.clone());
+                    continue;
+                }
+                ListElement::Text(_) => continue,
+            };
+            let title = incoming.name_without_field("done");
+            let existing = (strategy != MergeStrategy::KeepBoth)
+                .then(|| {
+                    self.list.iter_mut().find_map(|e| match e {
+                        ListElement::Item(item) if item.name_without_field("done") == title => {
+                            Some(item)
+                        }
+                        _ => None,
+                    })
+                })
+                .flatten();
+            let Some(existing) = existing else {
+                self.list.push(element.clone());
+                continue;
+            };
+            let take_incoming = match strategy {
+                MergeStrategy::KeepBoth => unreachable!("duplicates are kept"),
+                MergeStrategy::PreferDone => incoming.is_done() && !existing.is_done(),
+                // `None` is older than any day, and ties go to the copy merged in
+                MergeStrategy::PreferNewer => incoming.done_date() >= existing.done_date(),
+            };
+            if take_incoming {
+                dropped.push(std::mem::replace(existing, incoming.clone()));
+            } else {
+                dropped.push(incoming.clone());
+            }
+        }
+        dropped
+    }
+
+    /// What changed from this list to `other`, in the order of `other`. Items are the same if
+    /// their titles are, ignoring when they were done. Items that were removed and added between
+    /// the same unchanged items count as retitled
+    pub fn diff(&self, other: &TodoList) -> Vec<ListChange> {
+        let old = self.items().collect::<Vec<_>>();
+        let new = other.items().collect::<Vec<_>>();
+        let old_titles = old
+            .iter()
+            .map(|item| item.name_without_field("done"))
+            .collect::<Vec<_>>();
+        // the item of `new` every item of `old` is matched to
+        let mut partner = vec![None; old.len()];
+        let mut matched = vec![false; new.len()];
+        for (j, item) in new.iter().enumerate() {
+            let title = item.name_without_field("done");
+            let found = (0..old.len()).find(|&i| partner[i].is_none() && old_titles[i] == title);
+            if let Some(i) = found {
+                partner[i] = Some(j);
+                matched[j] = true;
+            }
+        }
+
+        // unmatched items are grouped by the position in `new` after the last matched item
+        // before them
+        let mut removed = BTreeMap::<usize, Vec<&TodoItem>>::new();
+        let mut anchor = 0;
+        for (i, item) in old.iter().enumerate() {
+            match partner[i] {
+                Some(j) => anchor = j + 1,
+                None => removed.entry(anchor).or_default().push(item),
+            }
+        }
+        let mut added = BTreeMap::<usize, Vec<&TodoItem>>::new();
+        let mut anchor = 0;
+        for (j, item) in new.iter().enumerate() {
+            if matched[j] {
+                anchor = j + 1;
+            } else {
+                added.entry(anchor).or_default().push(item);
+            }
+        }
+
+        let mut changes = vec![];
+        for (anchor, removed) in removed {
+            let mut added = added.remove(&anchor).unwrap_or_default().into_iter();
+            for from in removed {
+                changes.push(match added.next() {
+                    Some(to) => (
+                        anchor,
+                        ListChange::Retitled {
+                            from: from.clone(),
+                            to: to.clone(),
+                        },
+                    ),
+                    None => (anchor, ListChange::Removed(from.clone())),
+                });
+            }
+            changes.extend(added.map(|to| (anchor, ListChange::Added(to.clone()))));
+        }
+        for (anchor, added) in added {
+            changes.extend(
+                added
+                    .into_iter()
+                    .map(|to| (anchor, ListChange::Added(to.clone()))),
+            );
+        }
+        // pushed last so the sort keeps them after items removed right before them
+        for (i, j) in partner.iter().enumerate() {
+            if let Some(j) = *j {
+                if old[i].is_done() != new[j].is_done() {
+                    changes.push((
+                        j,
+                        ListChange::StateChanged {
+                            from: old[i].clone(),
+                            to: new[j].clone(),
+                        },
+                    ));
+                }
+            }
+        }
+        changes.sort_by_key(|(position, _)| *position);
+        changes.into_iter().map(|(_, change)| change).collect()
+    }
+
+    /// Makes the `changes` from [`TodoList::diff`], e.g. ones made to another copy of the list.
+    /// Added items are appended. Fails without changing anything if an item that was removed or
+    /// changed isn't in the list
+    pub fn apply(&mut self, changes: &[ListChange]) -> Result<(), TodoError> {
+        let mut list = self.list.clone();
+        for change in changes {
+            let (from, to) = match change {
+                ListChange::Added(item) => {
+                    list.push(ListElement::Item(item.clone()));
+                    continue;
+                }
+                ListChange::Removed(item) => (item, None),
+                ListChange::Retitled { from, to } | ListChange::StateChanged { from, to } => {
+                    (from, Some(to))
+                }
+            };
+            let title = from.name_without_field("done");
+            let position = list
+                .iter()
+                .position(|e| {
+                    matches!(e, ListElement::Item(item) if item.name_without_field("done") == title)
+                })
+                .ok_or_else(|| TodoError::PatchConflict(format!("There's no item '{title}'")))?;
+            match to {
+                Some(to) => list[position] = ListElement::Item(to.clone()),
+                None => {
+                    list.remove(position);
+                }
+            }
+        }
+        self.list = list;
+        Ok(())
+    }
+
+    /// Adds `items` at the end of the section under the heading `section`, which is added at the
+    /// end of the list if there's none
+    pub fn add_items_to_section(&mut self, items: Vec<TodoItem>, section: &str) {
+        let heading_text = |e: &ListElement| match e {
+            ListElement::Text(line) if line.starts_with('#') => {
+                Some(line.trim_start_matches('#').trim().to_string())
+            }
+            _ => None,
+        };
+        let heading = self.list.iter().position(|e| {
+            heading_text(e).is_some_and(|heading| heading.eq_ignore_ascii_case(section.trim()))
+        });
+        let Some(heading) = heading else {
+            self.add_line(&format!("## {}", section.trim()));
+            self.add_items(items);
+            return;
+        };
+        let end = self.list[heading + 1..]
+            .iter()
+            .position(|e| heading_text(e).is_some())
+            .map_or(self.list.len(), |i| heading + 1 + i);
+        // after the last item of the section, so blank lines before the next heading stay there
+        let at = self.list[heading + 1..end]
+            .iter()
+            .rposition(|e| matches!(e, ListElement::Item(_)))
+            .map_or(heading + 1, |i| heading + 2 + i);
+        self.list
+            .splice(at..at, items.into_iter().map(ListElement::Item));
+    }
+
+    /// Moves the done items that aren't under the heading `section` to the end of it, adding the
+    /// heading at the end of the list if there's none. Returns how many were moved
+    pub fn move_done_items_to_section(&mut self, section: &str) -> usize {
+        let mut in_section = false;
+        let mut moved = vec![];
+        self.list.retain(|e| match e {
+            ListElement::Text(line) if line.starts_with('#') => {
+                in_section = line
+                    .trim_start_matches('#')
+                    .trim()
+                    .eq_ignore_ascii_case(section.trim());
+                true
+            }
+            ListElement::Item(item) if item.is_done() && !in_section => {
+                moved.push(item.clone());
+                false
+            }
+            _ => true,
+        });
+        let count = moved.len();
+        if count > 0 {
+            let has_section = self.list.iter().any(|e| {
+                matches!(e, ListElement::Text(line) if line.starts_with('#')
+                    && line.trim_start_matches('#').trim().eq_ignore_ascii_case(section.trim()))
+            });
+            let ends_blank =
+                matches!(self.list.last(), Some(ListElement::Text(line)) if line.trim().is_empty());
+            if !has_section && !ends_blank && !self.list.is_empty() {
+                self.add_line("");
+            }
+            self.add_items_to_section(moved, section);
+        }
+        count
+    }
+
+    /// The list cut at its `## ` headings into a list for each section, e.g. to turn them into
+    /// documents of their own. The heading of a section becomes its `# ` title, and headings
+    /// under it move up a level too. The part before the first section comes first, without a
+    /// heading, if it has any items
+    pub fn split_sections(&self) -> Vec<(Option<String>, TodoList)> {
+        let part = |name: &str| TodoList {
+            line_ending: self.line_ending,
+            ..TodoList::new(name)
+        };
+        let mut parts = vec![(None, part(&self.name))];
+        for e in &self.list {
+            match e {
+                ListElement::Text(line) if line.starts_with("## ") => {
+                    let heading = line[3..].trim().to_string();
+                    let mut section = part(&heading);
+                    section.add_line(&format!("# {heading}"));
+                    parts.push((Some(heading), section));
+                }
+                ListElement::Text(line) if parts.len() > 1 && line.starts_with("###") => parts
+                    .last_mut()
+                    .expect("there's a part")
+                    .1
+                    .add_line(&line[1..]),
+                e => parts
+                    .last_mut()
+                    .expect("there's a part")
+                    .1
+                    .list
+                    .push(e.clone()),
+            }
+        }
+        for (_, part) in &mut parts {
+            while matches!(part.list.last(), Some(ListElement::Text(line)) if line.trim().is_empty())
+            {
+                part.list.pop();
+            }
+        }
+        if parts[0].1.is_empty() {
+            parts.remove(0);
+        }
+        parts
+    }
+
+    /// Removes the lines that aren't items, like headings and notes, for which `remove` is true
+    pub fn remove_lines<P>(&mut self, remove: P)
+    where
+        P: Fn(&str) -> bool,
+    {
+        self.list.retain(|e| match e {
+            ListElement::Item(_) => true,
+            ListElement::Text(line) => !remove(line),
+        });
+    }
+
+    /// Keeps the items for which `keep` is true and removes the others, returning them. Headings
+    /// and notes are kept
+    pub fn retain_items<P>(&mut self, mut keep: P) -> Vec<TodoItem>
+    where
+        P: FnMut(&TodoItem) -> bool,
+    {
+        let mut removed = vec![];
+        self.list.retain(|e| match e {
+            ListElement::Item(item) if !keep(item) => {
+                removed.push(item.clone());
+                false
+            }
+            _ => true,
+        });
+        removed
+    }
+
+    /// Calls `f` on every item
+    pub fn map_items_mut<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut TodoItem),
+    {
+        self.items_mut().for_each(f);
+    }
+
+    /// Marks the open items for which `predicate` is true done, returning them
+    pub fn complete_where<P>(&mut self, mut predicate: P) -> Vec<TodoItem>
+    where
+        P: FnMut(&TodoItem) -> bool,
+    {
+        let mut completed = vec![];
+        for item in self.items_mut() {
+            if !item.is_done() && predicate(item) {
+                item.mark_done();
+                completed.push(item.clone());
+            }
+        }
+        completed
+    }
+
+    /// Sorts the items with `compare`, keeping equal items in their order. Items are only moved
+    /// within their runs of consecutive items, so headings, notes and empty lines stay where they
+    /// are and items stay in their sections
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(&TodoItem, &TodoItem) -> std::cmp::Ordering,
+    {
+        let is_item = |e: &ListElement| matches!(e, ListElement::Item(_));
+        for run in self.list.chunk_by_mut(|a, b| is_item(a) && is_item(b)) {
+            run.sort_by(|a, b| match (a, b) {
+                (ListElement::Item(a), ListElement::Item(b)) => compare(a, b),
+                _ => std::cmp::Ordering::Equal,
+            });
+        }
+    }
+
+    /// Marks every item as not done, e.g. to reuse a template
+    pub fn reset(&mut self) {
+        self.map_items_mut(TodoItem::reopen);
+    }
+
+    /// Writes the list to `path`. Fails if the list was read from `path` and the file has
+    /// changed since then, so changes made by others aren't lost
     pub fn write(&self, path: &Path) -> Result<(), TodoError> {
-        Ok(fs::write(path, self.as_markdown())?)
+        TodoList::check_unchanged(self.source.as_ref(), path)?;
+        self.overwrite(path)
     }
+
+    /// Fails if `path` is the file a list was read from, given as `source`, and it has changed
+    /// since then
+    fn check_unchanged(source: Option<&(PathBuf, u64)>, path: &Path) -> Result<(), TodoError> {
+        if let Some((source, hash)) = source {
+            if source == path {
+                match fs::read_to_string(path) {
+                    Ok(contents) if hash_contents(&contents) != *hash => {
+                        return Err(TodoError::ChangedOnDisk(path.to_path_buf()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the list to `path` without checking if the file changed since it was read
+    pub fn overwrite(&self, path: &Path) -> Result<(), TodoError> {
+        write_atomically(path, self.file_contents().as_bytes())
+    }
+
+    /// Writes the list to `writer` the way it's written to files
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), TodoError> {
+        Ok(writer.write_all(self.file_contents().as_bytes())?)
+    }
+
+    /// The markdown of the list ending in a newline, with the list's line endings
+    fn file_contents(&self) -> String {
+        let mut contents = self.as_markdown();
+        if !contents.is_empty() {
+            contents.push('\n');
+        }
+        if self.line_ending == LineEnding::Crlf {
+            contents = contents.replace('\n', "\r\n");
+        }
+        contents
+    }
+}
+
+/// An item [`merge3`] couldn't merge because both sides changed it differently
+pub struct MergeConflict {
+    pub base: TodoItem,
+    /// Our version of the item, `None` if we removed it
+    pub ours: Option<TodoItem>,
+    /// Their version of the item, `None` if they removed it
+    pub theirs: Option<TodoItem>,
+}
+
+/// The result of [`merge3`]
+pub struct Merged {
+    pub list: TodoList,
+    /// Items changed differently on both sides. The list has our version of them
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Merges the changes made to `base` in `ours` and in `theirs`. Their changes to items we left
+/// alone are made to our list, and items added on both sides are kept once. Lines other than
+/// items are kept as we have them
+pub fn merge3(base: &TodoList, ours: &TodoList, theirs: &TodoList) -> Result<Merged, TodoError> {
+    let key = |item: &TodoItem| item.name_without_field("done");
+    // what we made of the items of `base` we changed, by their title in `base`
+    let mut our_changes = BTreeMap::new();
+    for change in base.diff(ours) {
+        match change {
+            ListChange::Added(_) => {}
+            ListChange::Removed(from) => {
+                our_changes.insert(key(&from), None);
+            }
+            ListChange::Retitled { from, to } | ListChange::StateChanged { from, to } => {
+                our_changes.insert(key(&from), Some(to));
+            }
+        }
+    }
+    let our_titles = ours.items().map(key).collect::<BTreeSet<_>>();
+
+    let mut changes = vec![];
+    let mut conflicts = vec![];
+    for change in base.diff(theirs) {
+        let (from, theirs) = match &change {
+            ListChange::Added(item) => {
+                if !our_titles.contains(&key(item)) {
+                    changes.push(change);
+                }
+                continue;
+            }
+            ListChange::Removed(from) => (from, None),
+            ListChange::Retitled { from, to } | ListChange::StateChanged { from, to } => {
+                (from, Some(to))
+            }
+        };
+        let Some(ours) = our_changes.get(&key(from)) else {
+            changes.push(change);
+            continue;
+        };
+        let same = match (ours, theirs) {
+            (Some(ours), Some(theirs)) => ours.as_markdown() == theirs.as_markdown(),
+            (ours, theirs) => ours.is_none() && theirs.is_none(),
+        };
+        if !same {
+            conflicts.push(MergeConflict {
+                base: from.clone(),
+                ours: ours.clone(),
+                theirs: theirs.cloned(),
+            });
+        }
+    }
+
+    let mut list = TodoList {
+        name: ours.name.clone(),
+        list: ours.list.clone(),
+        line_ending: ours.line_ending,
+        source: ours.source.clone(),
+        invalid_lines: vec![],
+    };
+    list.apply(&changes)?;
+    Ok(Merged { list, conflicts })
+}
+
+fn hash_contents(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it over `path`, so `path`
+/// never ends up half written
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), TodoError> {
+    write_atomically_with(path, |file| file.write_all(contents))
+}
+
+/// Like [`write_atomically`], with the contents written to the temporary file by `write`
+pub(crate) fn write_atomically_with(
+    path: &Path,
+    write: impl FnOnce(&mut fs::File) -> io::Result<()>,
+) -> Result<(), TodoError> {
+    // write through symlinks instead of replacing them
+    let path = &fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+    let result = (|| {
+        let mut file = fs::File::create(&temp_path)?;
+        write(&mut file)?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(path) {
+            fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    Ok(result?)
 }
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum TodoItemState {
     Done,
+    #[cfg_attr(feature = "serde", serde(rename = "open"))]
     Initial,
+    /// A status of its own, like the `[/]` of in progress or `[-]` of cancelled that Obsidian
+    /// Tasks uses. Kept as it is and counted as open
+    Other(char),
 }
 
 impl TodoItemState {
@@ -133,25 +1037,719 @@ impl TodoItemState {
         match self {
             TodoItemState::Done => "x".to_string(),
             TodoItemState::Initial => " ".to_string(),
+            TodoItemState::Other(mark) => mark.to_string(),
         }
     }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TodoItem {
+    #[cfg_attr(feature = "serde", serde(rename = "title"))]
     pub name: String,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
     pub description: Option<String>,
     pub state: TodoItemState,
+    /// The line the item was read from. It's written back as is while the item is unchanged
+    #[cfg_attr(feature = "serde", serde(skip))]
+    raw: Option<String>,
+}
+
+/// Builds an item without going through markdown, from [`TodoItem::builder`]
+#[derive(Debug, Clone, Default)]
+pub struct TodoItemBuilder {
+    name: String,
+    description: Option<String>,
+    tags: Vec<String>,
+    done: bool,
+}
+
+impl TodoItemBuilder {
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// The description, which can have several lines. They're indented under the item in the
+    /// list file and empty lines are left out, since those would end the description
+    pub fn description(mut self, description: &str) -> Self {
+        let lines = description
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("  {line}"))
+            .collect::<Vec<_>>();
+        self.description = (!lines.is_empty()).then(|| lines.join("\n"));
+        self
+    }
+
+    /// Adds a tag, like [`TodoItem::add_tag`]
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Whether the item is done. Done items get today as the day they were done
+    pub fn done(mut self, done: bool) -> Self {
+        self.done = done;
+        self
+    }
+
+    /// The item. Fails if it has no name, since it couldn't be written to a list
+    pub fn build(self) -> Result<TodoItem, TodoError> {
+        if self.name.trim().is_empty() {
+            return Err(TodoError::ParseError(
+                "Item name can't be empty".to_string(),
+            ));
+        }
+        let mut item = TodoItem {
+            name: self.name.trim().to_string(),
+            description: self.description,
+            state: TodoItemState::Initial,
+            raw: None,
+        };
+        for tag in &self.tags {
+            item.add_tag(tag);
+        }
+        if self.done {
+            item.mark_done();
+        }
+        Ok(item)
+    }
 }
 
 impl TodoItem {
+    /// Starts building an item, e.g. `TodoItem::builder().name("Call Sam").tag("work").build()`
+    pub fn builder() -> TodoItemBuilder {
+        TodoItemBuilder::default()
+    }
+
+    /// Marks the item as done, noting the day in a `done:YYYY-MM-DD` word
     pub fn mark_done(&mut self) {
+        if !self.is_done() && self.field("done").is_none() {
+            self.name = format!("{} done:{}", self.name, date::Date::today());
+        }
         self.state = TodoItemState::Done;
     }
 
+    /// Marks the item as not done, dropping the day it was done
+    pub fn reopen(&mut self) {
+        if self.field("done").is_some() {
+            self.name = self.name_without_field("done");
+        }
+        self.state = TodoItemState::Initial;
+    }
+
     pub fn is_done(&self) -> bool {
         self.state == TodoItemState::Done
     }
+
+    /// The item's ID, kept in its `id:` field. Unlike its number, it stays the same when other
+    /// items are added or removed and when the item is changed. Items get one with
+    /// [`TodoList::assign_ids`]
+    pub fn id(&self) -> Option<&str> {
+        self.field("id")
+    }
+
+    /// The item as a markdown task line followed by its description
+    pub fn as_markdown(&self) -> String {
+        let unchanged = |raw: &String| {
+            raw.parse::<TodoItem>()
+                .is_ok_and(|original| original.name == self.name && original.state == self.state)
+        };
+        let line = match &self.raw {
+            Some(raw) if unchanged(raw) => raw.clone(),
+            // keep the indentation and bullet of the original line
+            raw => {
+                let prefix = raw
+                    .as_deref()
+                    .and_then(|raw| Some(&raw[..checkbox_start(raw)?]))
+                    .unwrap_or("- [");
+                format!("{prefix}{}] {}", self.state.as_markdown(), self.name)
+            }
+        };
+        match &self.description {
+            Some(desc) => format!("{line}\n{desc}"),
+            None => line,
+        }
+    }
+
+    /// Tags in the title, the `#tag` words, without the `#`. They're read from the title each
+    /// time, so they can't get out of step with it
+    pub fn tags(&self) -> Vec<&str> {
+        self.name.split_whitespace().filter_map(tag_of).collect()
+    }
+
+    /// Whether the item is tagged `tag`, ignoring case. A leading `#` is optional
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim().trim_start_matches('#');
+        self.tags().iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    /// Removes the `#tag` words of `tag` from the title. Returns whether there were any
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        let tag = tag.trim().trim_start_matches('#');
+        let words = self.name.split_whitespace().collect::<Vec<_>>();
+        let kept = words
+            .iter()
+            .filter(|word| !tag_of(word).is_some_and(|t| t.eq_ignore_ascii_case(tag)))
+            .copied()
+            .collect::<Vec<_>>();
+        if kept.len() == words.len() {
+            return false;
+        }
+        self.name = kept.join(" ");
+        true
+    }
+
+    /// The title without its `#tag` words
+    pub fn title_without_tags(&self) -> String {
+        self.name
+            .split_whitespace()
+            .filter(|word| tag_of(word).is_none())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Adds `#tag` to the title unless the item has the tag already. The tag is lowercased,
+    /// with a leading `#` dropped and spaces turned into `-`
+    pub fn add_tag(&mut self, tag: &str) {
+        let tag = tag
+            .trim()
+            .trim_start_matches('#')
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-")
+            .to_lowercase();
+        if tag.is_empty() || self.has_tag(&tag) {
+            return;
+        }
+        self.name = format!("{} #{tag}", self.name);
+    }
+
+    /// Value of the first `key:value` word in the title
+    /// Also found when it's written the way the Obsidian Tasks plugin does, like `📅 2025-07-01`
+    /// for `due` or `⏫` for `priority`
+    pub fn field(&self, key: &str) -> Option<&str> {
+        let mut words = self.name.split_whitespace();
+        let plain = words.clone().find_map(|word| {
+            let (k, value) = word.split_once(':')?;
+            (k == key && !value.is_empty()).then_some(value)
+        });
+        if plain.is_some() || key == "priority" {
+            return plain.or_else(|| self.name.split_whitespace().find_map(emoji_priority));
+        }
+        let emoji = emoji_of_field(key)?;
+        words.find(|word| without_variation(word) == emoji)?;
+        words.next()
+    }
+
+    /// The title without its `key:value` words, or the emoji and value of the field
+    pub fn name_without_field(&self, key: &str) -> String {
+        let prefix = format!("{key}:");
+        let emoji = emoji_of_field(key);
+        let mut words = self.name.split_whitespace();
+        let mut kept = vec![];
+        while let Some(word) = words.next() {
+            let is_priority = key == "priority" && emoji_priority(word).is_some();
+            if emoji.is_some_and(|emoji| without_variation(word) == emoji) {
+                words.next();
+            } else if !word.starts_with(&prefix) && !is_priority {
+                kept.push(word);
+            }
+        }
+        kept.join(" ")
+    }
+
+    /// Sets the value of the first `key:value` word in the title, or adds the word. A value
+    /// written with emoji is replaced by the word
+    pub fn set_field(&mut self, key: &str, value: &str) {
+        let prefix = format!("{key}:");
+        if !self.name.split_whitespace().any(|w| w.starts_with(&prefix)) {
+            self.name = self.name_without_field(key);
+        }
+        let mut words = self.name.split_whitespace().collect::<Vec<_>>();
+        let word = format!("{prefix}{value}");
+        match words.iter().position(|w| w.starts_with(&prefix)) {
+            Some(i) => words[i] = &word,
+            None => words.push(&word),
+        }
+        self.name = words.join(" ");
+    }
+
+    /// Writes the fields the Obsidian Tasks plugin has emoji for with them, e.g.
+    /// `due:2025-07-01` as `📅 2025-07-01` and `priority:high` as `⏫`
+    pub fn use_emoji_fields(&mut self) {
+        let name = self
+            .name
+            .split_whitespace()
+            .map(|word| {
+                let Some((key, value)) = word.split_once(':') else {
+                    return word.to_string();
+                };
+                if key == "priority" {
+                    if let Some((_, emoji)) = EMOJI_PRIORITIES.iter().find(|(p, _)| *p == value) {
+                        return emoji.to_string();
+                    }
+                }
+                match emoji_of_field(key) {
+                    Some(emoji) if !value.is_empty() => format!("{emoji} {value}"),
+                    _ => word.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        if name.split_whitespace().ne(self.name.split_whitespace()) {
+            self.name = name;
+        }
+    }
+
+    /// When the item is due, from a `due:2025-07-01` or `due:2025-07-01T09:30` word in the title
+    pub fn due(&self) -> Option<(date::Date, Option<date::Time>)> {
+        date::parse_date_time(self.field("due")?).ok()
+    }
+
+    /// When the item was done, from the `done:2025-07-01` word `mark_done` adds
+    pub fn done_date(&self) -> Option<date::Date> {
+        self.field("done")?.parse().ok()
+    }
+
+    /// URLs mentioned in the title or the description
+    pub fn urls(&self) -> Vec<&str> {
+        let mut urls = find_urls(&self.name);
+        if let Some(desc) = &self.description {
+            urls.extend(find_urls(desc));
+        }
+        urls
+    }
+
+    /// Renders the item without colors, hyperlinks or wrapping, e.g. for files and pipes
+    pub fn render_plain(&self) -> String {
+        self.render(&DisplayOptions {
+            absolute_dates: true,
+            ..Default::default()
+        })
+    }
+
+    /// Renders the item for the terminal, styled according to `options`
+    pub fn render(&self, options: &DisplayOptions) -> String {
+        self.render_indented(options, 0)
+    }
+
+    /// Renders the item after its number, right-aligned to `options.number_width`, like
+    /// `display_with_numbers` does. The ID follows the number with `options.show_ids`
+    pub fn render_numbered(&self, number: usize, options: &DisplayOptions) -> String {
+        let width = options.number_width;
+        let label = match self.id() {
+            Some(id) if options.show_ids => format!("{number: >width$} @{id}"),
+            _ => format!("{number: >width$}"),
+        };
+        self.render_labeled(&label, options)
+    }
+
+    /// Renders the item after `label`, which is styled like item numbers
+    pub fn render_labeled(&self, label: &str, options: &DisplayOptions) -> String {
+        let indent = text::display_width(label) + 1;
+        let label = options.paint(&options.theme.number, label);
+        format!("{label} {}", self.render_indented(options, indent))
+    }
+
+    /// Renders the item assuming `indent` columns of the line are already taken
+    fn render_indented(&self, options: &DisplayOptions, indent: usize) -> String {
+        let theme = &options.theme;
+        let (marker, marker_style, title_style) = match self.state {
+            TodoItemState::Done => (&options.glyphs.done, &theme.done_marker, &theme.done),
+            TodoItemState::Initial | TodoItemState::Other(_) => {
+                (&options.glyphs.open, &theme.open_marker, &theme.open)
+            }
+        };
+        let dimmed;
+        let title_style = if theme.done_items == DoneStyle::Dimmed && self.is_done() {
+            dimmed = Style {
+                dim: true,
+                strikethrough: false,
+                ..title_style.clone()
+            };
+            &dimmed
+        } else {
+            title_style
+        };
+        let state = options.paint(marker_style, marker);
+        let title_column = indent + text::display_width(marker) + 2;
+        let mut title = if options.hide_tags {
+            self.title_without_tags()
+        } else {
+            self.name.clone()
+        };
+        if self.id().is_some() {
+            // IDs are shown after the numbers, see `render_numbered`
+            let words = title
+                .split_whitespace()
+                .filter(|word| !word.starts_with("id:"));
+            title = words.collect::<Vec<_>>().join(" ");
+        }
+        if let (DoneStyle::Dated, Some(date)) = (theme.done_items, self.done_date()) {
+            let words = title
+                .split_whitespace()
+                .filter(|word| !word.starts_with("done:"))
+                .collect::<Vec<_>>();
+            title = format!("{} {}", options.show_date(&date), words.join(" "));
+        }
+        if !options.absolute_dates || options.date_format.is_some() {
+            title = show_dates(&title, options);
+        }
+        let name = match options.width {
+            Some(width) if width > title_column + MIN_TITLE_WIDTH => {
+                let available = width - title_column;
+                match options.overflow {
+                    Overflow::Truncate => {
+                        let title = text::truncate(&title, available, &options.glyphs.ellipsis);
+                        style_title(&title, title_style, options)
+                    }
+                    Overflow::Wrap => text::wrap(&title, available)
+                        .iter()
+                        .map(|line| style_title(line, title_style, options))
+                        .collect::<Vec<_>>()
+                        .join(&format!("\n{}", " ".repeat(title_column))),
+                }
+            }
+            _ => style_title(&title, title_style, options),
+        };
+        format!(
+            " {state} {name}{}",
+            if let Some(desc) = &self.description {
+                format!("\n{}", style_inline(desc, &theme.description, options))
+            } else {
+                "".to_string()
+            }
+        )
+    }
+}
+
+/// `title` with its `due:`, `created:` and `done:` words written like `due in 3 days`, or with
+/// the date format of `options`
+fn show_dates(title: &str, options: &DisplayOptions) -> String {
+    title
+        .split(' ')
+        .map(|word| {
+            let Some((key, value)) = word.split_once(':') else {
+                return word.to_string();
+            };
+            let label = match key {
+                "due" => "due",
+                "created" => "added",
+                "done" => "done",
+                _ => return word.to_string(),
+            };
+            match date::parse_date_time(value) {
+                Ok((date, Some(time))) => format!("{label} {} {time}", options.show_date(&date)),
+                Ok((date, None)) => format!("{label} {}", options.show_date(&date)),
+                Err(_) => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Emoji the Obsidian Tasks plugin writes before the values of fields
+const EMOJI_FIELDS: [(&str, &str); 5] = [
+    ("due", "📅"),
+    ("done", "✅"),
+    ("created", "➕"),
+    ("scheduled", "⏳"),
+    ("start", "🛫"),
+];
+
+/// Emoji the Obsidian Tasks plugin writes for the values of `priority`
+const EMOJI_PRIORITIES: [(&str, &str); 5] = [
+    ("highest", "🔺"),
+    ("high", "⏫"),
+    ("medium", "🔼"),
+    ("low", "🔽"),
+    ("lowest", "⏬"),
+];
+
+fn emoji_of_field(key: &str) -> Option<&'static str> {
+    EMOJI_FIELDS
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, emoji)| *emoji)
+}
+
+/// The priority `word` is the emoji of, if it's one
+fn emoji_priority(word: &str) -> Option<&'static str> {
+    EMOJI_PRIORITIES
+        .iter()
+        .find(|(_, emoji)| *emoji == without_variation(word))
+        .map(|(priority, _)| *priority)
+}
+
+/// `word` without the variation selector that asks for an emoji to be shown in color
+fn without_variation(word: &str) -> &str {
+    word.trim_end_matches('\u{fe0f}')
+}
+
+/// Styles a title for the terminal. `#tag` words get their style from the theme, links
+/// become terminal hyperlinks and everything else is painted with `style`
+fn style_title(title: &str, style: &Style, options: &DisplayOptions) -> String {
+    if !options.color && !options.hyperlinks {
+        return title.to_string();
+    }
+    let mut styled = String::new();
+    let mut rest = title;
+    while let Some(link) = options
+        .hyperlinks
+        .then(|| text::find_markdown_link(rest))
+        .flatten()
+    {
+        styled.push_str(&style_inline(&rest[..link.start], style, options));
+        let text = options.paint(&options.theme.link, link.text);
+        styled.push_str(&text::hyperlink(link.url, &text));
+        rest = &rest[link.end..];
+    }
+    styled.push_str(&style_inline(rest, style, options));
+    styled
+}
+
+/// Converts inline markdown (`**bold**`, `*italic*`, `` `code` ``) in `s` to terminal styles
+fn style_inline(s: &str, style: &Style, options: &DisplayOptions) -> String {
+    if !options.color {
+        return style_words(s, style, options);
+    }
+    text::inline_spans(s)
+        .into_iter()
+        .map(|(inline, span)| match inline {
+            Inline::Plain => style_words(span, style, options),
+            Inline::Bold => style_words(
+                span,
+                &Style {
+                    bold: true,
+                    ..style.clone()
+                },
+                options,
+            ),
+            Inline::Italic => style_words(
+                span,
+                &Style {
+                    italic: true,
+                    ..style.clone()
+                },
+                options,
+            ),
+            Inline::Code => options.theme.code.paint(span),
+        })
+        .collect()
+}
+
+fn style_words(s: &str, style: &Style, options: &DisplayOptions) -> String {
+    s.split(' ')
+        .map(|word| {
+            if tag_of(word).is_some() {
+                options.paint(options.theme.tag_style(word), word)
+            } else if options.hyperlinks && text::is_url(word) {
+                text::hyperlink(word, &options.paint(&options.theme.link, word))
+            } else {
+                options.paint(style, word)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The tag of a `#tag` word, without the `#`
+fn tag_of(word: &str) -> Option<&str> {
+    word.strip_prefix('#').filter(|tag| !tag.is_empty())
+}
+
+/// URLs in `s`, both bare and from markdown links
+fn find_urls(s: &str) -> Vec<&str> {
+    fn bare_urls(s: &str) -> impl Iterator<Item = &str> {
+        s.split_whitespace().filter(|word| text::is_url(word))
+    }
+    let mut urls = vec![];
+    let mut rest = s;
+    while let Some(link) = text::find_markdown_link(rest) {
+        urls.extend(bare_urls(&rest[..link.start]));
+        urls.push(link.url);
+        rest = &rest[link.end..];
+    }
+    urls.extend(bare_urls(rest));
+    urls
+}
+
+/// Titles are not wrapped or truncated when less than this many columns are left for them
+const MIN_TITLE_WIDTH: usize = 10;
+
+/// What to do with titles that don't fit in the terminal
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Overflow {
+    /// Continue the title on the next lines, aligned with its first line
+    #[default]
+    Wrap,
+    /// Cut the title short and end it with `…`
+    Truncate,
+}
+
+/// Options that control how items are rendered for the terminal
+#[derive(Debug, Clone, Default)]
+pub struct DisplayOptions {
+    /// Style the output with ANSI escape codes
+    pub color: bool,
+    pub theme: Theme,
+    pub glyphs: Glyphs,
+    /// Turn URLs into terminal hyperlinks
+    pub hyperlinks: bool,
+    /// Width of the terminal. Long titles are left as is if this is `None`
+    pub width: Option<usize>,
+    pub overflow: Overflow,
+    /// Leave the `#tag` words out of titles
+    pub hide_tags: bool,
+    /// Columns item numbers are right-aligned in. 0 fits them to the highest number shown with
+    /// them where that's known, see [`DisplayOptions::fitting_numbers`]
+    pub number_width: usize,
+    /// Show the IDs of items after their numbers
+    pub show_ids: bool,
+    /// Leave the dates of `due:`, `created:` and `done:` words as they are, or write them with
+    /// `date_format` if it's set, instead of like `due in 3 days`
+    pub absolute_dates: bool,
+    /// See [`date::Date::format`]
+    pub date_format: Option<String>,
+}
+
+impl DisplayOptions {
+    /// These options with a `number_width` fitting the numbers up to `highest`, unless a width
+    /// is set already
+    pub fn fitting_numbers(&self, highest: usize) -> DisplayOptions {
+        let mut options = self.clone();
+        if options.number_width == 0 {
+            options.number_width = text::digits(highest);
+        }
+        options
+    }
+
+    /// How `date` is shown, relative to today unless `absolute_dates` is set
+    pub fn show_date(&self, date: &date::Date) -> String {
+        match (&self.date_format, self.absolute_dates) {
+            (_, false) => date.relative(),
+            (Some(format), true) => date.format(format),
+            (None, true) => date.to_string(),
+        }
+    }
+
+    /// Paints `text` with `style` if colors are enabled
+    pub fn paint(&self, style: &Style, text: &str) -> String {
+        if self.color {
+            style.paint(text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// How items are turned into text, so callers can pick what suits their output
+#[derive(Debug, Clone, Copy)]
+pub enum Renderer<'a> {
+    /// Markers and titles without any escape codes
+    Plain,
+    /// For the terminal, styled according to the options
+    Styled(&'a DisplayOptions),
+    /// The markdown lines the items are written to list files as
+    Markdown,
+}
+
+impl Renderer<'_> {
+    pub fn render_item(&self, item: &TodoItem) -> String {
+        match self {
+            Renderer::Plain => item.render_plain(),
+            Renderer::Styled(options) => item.render(options),
+            Renderer::Markdown => item.as_markdown(),
+        }
+    }
+
+    /// Renders the items of `list`, one after the other
+    pub fn render_list(&self, list: &TodoList) -> String {
+        list.items()
+            .map(|item| self.render_item(item))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Iterator over the items of a list, from [`TodoList::items`]
+pub struct Items<'a>(std::slice::Iter<'a, ListElement>);
+
+impl<'a> Iterator for Items<'a> {
+    type Item = &'a TodoItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|e| match e {
+            ListElement::Item(item) => Some(item),
+            ListElement::Text(_) => None,
+        })
+    }
+}
+
+/// Iterator over the items of a list that can change them, from [`TodoList::items_mut`]
+pub struct ItemsMut<'a>(std::slice::IterMut<'a, ListElement>);
+
+impl<'a> Iterator for ItemsMut<'a> {
+    type Item = &'a mut TodoItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|e| match e {
+            ListElement::Item(item) => Some(item),
+            ListElement::Text(_) => None,
+        })
+    }
+}
+
+/// Iterator taking the items out of a list
+pub struct IntoItems(std::vec::IntoIter<ListElement>);
+
+impl Iterator for IntoItems {
+    type Item = TodoItem;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(|e| match e {
+            ListElement::Item(item) => Some(item),
+            ListElement::Text(_) => None,
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a TodoList {
+    type Item = &'a TodoItem;
+    type IntoIter = Items<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut TodoList {
+    type Item = &'a mut TodoItem;
+    type IntoIter = ItemsMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items_mut()
+    }
+}
+
+impl IntoIterator for TodoList {
+    type Item = TodoItem;
+    type IntoIter = IntoItems;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoItems(self.list.into_iter())
+    }
 }
 
 impl Display for TodoList {
@@ -159,8 +1757,7 @@ impl Display for TodoList {
         writeln!(
             f,
             "{}",
-            self.list
-                .iter()
+            self.items()
                 .map(|i| i.to_string())
                 .collect::<Vec<String>>()
                 .join("\n")
@@ -188,56 +1785,99 @@ impl Display for TodoItemState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TodoItemState::Done => write!(f, "✅"),
-            TodoItemState::Initial => write!(f, "⬜"),
+            TodoItemState::Initial | TodoItemState::Other(_) => write!(f, "⬜"),
+        }
+    }
+}
+
+/// Byte offset of the mark inside the check box if `line` starts with a list bullet (`-`, `*`,
+/// `+` or a number like `1.` or `2)`) followed by `[`. The bullet can be indented, nested under
+/// other bullets and inside block quotes
+fn checkbox_start(line: &str) -> Option<usize> {
+    let mut rest = line.trim_start();
+    while let Some(quoted) = rest.strip_prefix('>') {
+        rest = quoted.trim_start();
+    }
+    let digits = rest.chars().take_while(char::is_ascii_digit).count();
+    let bullet = if digits > 0 {
+        rest[digits..]
+            .starts_with(['.', ')'])
+            .then_some(digits + 1)?
+    } else {
+        rest.starts_with(['-', '*', '+']).then_some(1)?
+    };
+    rest[bullet..]
+        .starts_with(" [")
+        .then_some(line.len() - rest.len() + bullet + 2)
+}
+
+/// Tracks whether the lines of a file, read in order, are in fenced code blocks. Lines in them
+/// are never items, even if they look like ones
+#[derive(Default)]
+struct CodeFences {
+    /// The character of the fence of the open block, `` ` `` or `~`
+    open: Option<char>,
+}
+
+impl CodeFences {
+    /// Whether `line` is a fence or inside a fenced block
+    fn is_code(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        let fence = ['`', '~']
+            .into_iter()
+            .find(|&c| trimmed.chars().take(3).filter(|&t| t == c).count() == 3);
+        match (self.open, fence) {
+            (None, Some(fence)) => {
+                self.open = Some(fence);
+                true
+            }
+            (Some(open), Some(fence)) if open == fence => {
+                self.open = None;
+                true
+            }
+            (open, _) => open.is_some(),
         }
     }
 }
 
+/// Whether `line` has the shape of an item, like `- [?] title`, even if it's not a valid one
+fn looks_like_item(line: &str) -> bool {
+    checkbox_start(line).is_some_and(|i| line[i..].chars().nth(1) == Some(']'))
+}
+
 impl FromStr for TodoItem {
     type Err = TodoError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if !s.starts_with("- [") {
-            return Err(TodoError::ParseError(format!(
-                "Item should start with the check box\nFound: '{s}'"
-            )));
+        let invalid = |message: &str| TodoError::ParseError(format!("{message}\nFound: '{s}'"));
+        let Some(checkbox) = checkbox_start(s) else {
+            return Err(invalid("Item should start with the check box"));
         };
-        let mut chars = s.chars().skip(3); // skip first 3 characters ("- [")
+        let mut chars = s[checkbox..].chars();
         let mark = chars
             .next()
-            .ok_or_else(|| {
-                TodoError::ParseError(format!(
-                    "Item should start with the check box. No mark.\nFound: '{s}'"
-                ))
-            })?
+            .ok_or_else(|| invalid("The check box has no mark"))?
             .to_string();
-        chars
-            .next()
-            .ok_or_else(|| TodoError::ParseError(format!(
-                "Item should start with the check box. No closing bracket.\nFound: '{s}'"
-            )))?
-            .eq(&']')
-            .then(|| chars.next())
-            .ok_or_else(|| TodoError::ParseError(format!(
-                "Item should start with the check box. Expected ']'\nFound: '{s}'"
-            )))?
-            .ok_or_else(|| TodoError::ParseError(format!(
-                "Item should start with the check box. Item ended unexpectedly. Space expected after ']'.\nFound: '{s}'"
-            )))?
-            .eq(&' ')
-            .then_some(())
-            .ok_or_else(|| TodoError::ParseError(format!(
-                "Item should start with the check box. Space expected after ']'.\nFound: '{s}'"
-            )))?;
+        match chars.next() {
+            Some(']') => {}
+            Some(_) => return Err(invalid("The check box should have one mark and a ']'")),
+            None => return Err(invalid("The check box isn't closed with ']'")),
+        }
+        match chars.next() {
+            Some(' ') => {}
+            Some(_) => return Err(invalid("Expected a space after the check box")),
+            None => return Err(invalid("Item name can't be empty")),
+        }
         let name = chars.collect::<String>();
-        (!name.is_empty()).then_some(()).ok_or_else(|| {
-            TodoError::ParseError(format!("Item name can't be empty.\nFound: '{s}'"))
-        })?;
+        if name.is_empty() {
+            return Err(invalid("Item name can't be empty"));
+        }
 
         Ok(Self {
             name,
             state: mark.parse()?,
             description: None,
+            raw: Some(s.to_string()),
         })
     }
 }
@@ -246,9 +1886,13 @@ impl FromStr for TodoItemState {
     type Err = TodoError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "x" => Ok(TodoItemState::Done),
-            " " => Ok(TodoItemState::Initial),
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some('x' | 'X'), None) => Ok(TodoItemState::Done),
+            (Some(' '), None) => Ok(TodoItemState::Initial),
+            (Some(mark), None) if !mark.is_whitespace() && !matches!(mark, '[' | ']') => {
+                Ok(TodoItemState::Other(mark))
+            }
             _ => Err(TodoError::ParseError(format!(
                 "This state of a todo item is not supported.\nFound: '{s}'"
             ))),
@@ -256,6 +1900,43 @@ impl FromStr for TodoItemState {
     }
 }
 
+/// A line of a list file that couldn't be read
+#[derive(Debug, Clone)]
+pub struct LineError {
+    /// Number of the line, starting at 1
+    pub line: usize,
+    /// Byte offset of the start of the line in the file
+    pub offset: usize,
+    pub text: String,
+    pub message: String,
+}
+
+impl LineError {
+    fn new(line: usize, offset: usize, text: &str, error: TodoError) -> Self {
+        let message = match error {
+            // the line is shown separately
+            TodoError::ParseError(message) => message
+                .split("\nFound:")
+                .next()
+                .unwrap_or_default()
+                .to_string(),
+            error => error.to_string(),
+        };
+        Self {
+            line,
+            offset,
+            text: text.to_string(),
+            message,
+        }
+    }
+}
+
+impl Display for LineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}\n    {}", self.line, self.message, self.text)
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum TodoError {
     #[error("Parsing error. {0}")]
@@ -264,4 +1945,366 @@ pub enum TodoError {
     InvalidItemNumber(usize),
     #[error("IO Error. {0}")]
     FileIOError(#[from] io::Error),
+    #[error("The list at '{}' changed on disk since it was read", .0.display())]
+    ChangedOnDisk(PathBuf),
+    #[error("Invalid query. {0}")]
+    InvalidQuery(String),
+    #[error("Invalid regex. {0}")]
+    InvalidRegex(String),
+    #[error("Invalid replacement. {0}")]
+    InvalidReplacement(String),
+    #[error("There's no item with the ID '@{0}'")]
+    UnknownItemId(String),
+    #[error("Several items have the ID '@{0}'. Use their numbers instead")]
+    AmbiguousItemId(String),
+    #[error("The patch doesn't apply. {0}")]
+    PatchConflict(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(markdown: &str) -> TodoList {
+        TodoList::from_reader(markdown.as_bytes()).unwrap()
+    }
+
+    fn titles(list: &TodoList) -> Vec<&str> {
+        list.items().map(|item| item.name.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_quoted_items() {
+        let markdown = "> - [ ] quoted\n> > * [x] nested quote\n> - [ ]\n> not an item";
+        let list = list(markdown);
+        assert_eq!(titles(&list), ["quoted", "nested quote"]);
+        assert!(list.get_item(2).unwrap().is_done());
+        assert_eq!(list.invalid_lines().len(), 1);
+        assert_eq!(list.invalid_lines()[0].line, 3);
+        assert_eq!(list.as_markdown(), markdown);
+    }
+
+    #[test]
+    fn reads_numbered_items() {
+        let markdown = "1. [ ] first\n2) [x] second\n  10. [ ] nested\n3. [ ]\n4. plain";
+        let list = list(markdown);
+        assert_eq!(titles(&list), ["first", "second", "nested"]);
+        assert_eq!(list.done_count(), 1);
+        assert_eq!(list.invalid_lines().len(), 1);
+        assert_eq!(list.as_markdown(), markdown);
+    }
+
+    #[test]
+    fn skips_fenced_code() {
+        let markdown = "- [ ] before\n```md\n- [ ] in code\n- [ ]\n```\n~~~\n- [ ] tildes\n```\n- [ ] still tildes\n~~~\n- [ ] after";
+        let list = list(markdown);
+        assert_eq!(titles(&list), ["before", "after"]);
+        assert!(list.invalid_lines().is_empty());
+        assert_eq!(list.as_markdown(), markdown);
+    }
+
+    fn merged(base: &str, ours: &str, theirs: &str) -> Merged {
+        merge3(&list(base), &list(ours), &list(theirs)).unwrap()
+    }
+
+    #[test]
+    fn merges_changes_to_different_items() {
+        let merged = merged(
+            "# L\n- [ ] one\n- [ ] two\n- [ ] three",
+            "# L\n- [x] one\n- [ ] two\n- [ ] three",
+            "# L\n- [ ] one\n- [ ] two again\n",
+        );
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(titles(&merged.list), ["one", "two again"]);
+        assert!(merged.list.get_item(1).unwrap().is_done());
+    }
+
+    #[test]
+    fn keeps_our_version_of_items_edited_on_both_sides() {
+        let merged = merged(
+            "- [ ] one\n- [ ] two",
+            "- [ ] one here\n- [ ] two",
+            "- [ ] one there\n- [x] two",
+        );
+        assert_eq!(titles(&merged.list), ["one here", "two"]);
+        assert!(merged.list.get_item(2).unwrap().is_done());
+        assert_eq!(merged.conflicts.len(), 1);
+        let conflict = &merged.conflicts[0];
+        assert_eq!(conflict.base.name, "one");
+        assert_eq!(conflict.ours.as_ref().unwrap().name, "one here");
+        assert_eq!(conflict.theirs.as_ref().unwrap().name, "one there");
+    }
+
+    #[test]
+    fn same_edit_on_both_sides_is_no_conflict() {
+        let merged = merged("- [ ] one", "- [x] one", "- [x] one");
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.list.done_count(), 1);
+    }
+
+    #[test]
+    fn edit_and_delete_conflict() {
+        let merged = merged("- [ ] one\n- [ ] two", "- [x] one\n- [ ] two", "- [ ] two");
+        assert_eq!(titles(&merged.list), ["one", "two"]);
+        assert_eq!(merged.conflicts.len(), 1);
+        assert!(merged.conflicts[0].theirs.is_none());
+
+        let merged = self::merged("- [ ] one\n- [ ] two", "- [ ] two", "- [x] one\n- [ ] two");
+        assert_eq!(titles(&merged.list), ["two"]);
+        assert_eq!(merged.conflicts.len(), 1);
+        assert!(merged.conflicts[0].ours.is_none());
+    }
+
+    #[test]
+    fn applies_their_deletes() {
+        let merged = merged("- [ ] one\n- [ ] two", "- [ ] one\n- [ ] two", "- [ ] two");
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(titles(&merged.list), ["two"]);
+    }
+
+    #[test]
+    fn keeps_items_added_on_both_sides() {
+        let merged = merged(
+            "- [ ] one",
+            "- [ ] one\n- [ ] same\n- [ ] ours",
+            "- [ ] one\n- [ ] same\n- [ ] theirs",
+        );
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(titles(&merged.list), ["one", "same", "ours", "theirs"]);
+    }
+
+    #[test]
+    fn keeps_invalid_lines_as_text() {
+        let mut list = list("- [ ] one\n- [ ] \n- [/]two\n- [ ] three");
+        assert_eq!(titles(&list), ["one", "three"]);
+        assert_eq!(list.invalid_lines().len(), 2);
+        list.mark_item_done(2).unwrap();
+        assert!(list
+            .as_markdown()
+            .starts_with("- [ ] one\n- [ ] \n- [/]two\n- [x] three"));
+    }
+
+    #[test]
+    fn ids_stay_when_items_change() {
+        let mut list = list("- [ ] first\n- [ ] second");
+        assert!(matches!(
+            list.find_by_id("@abc"),
+            Err(TodoError::UnknownItemId(_))
+        ));
+        assert_eq!(list.assign_ids(), 2);
+        assert_eq!(list.assign_ids(), 0);
+        let id = list.get_item(2).unwrap().id().unwrap().to_string();
+        let item = list.get_item_mut(2).unwrap();
+        item.name = item.name.replace("second", "renamed #tag");
+        item.set_field("due", "2026-10-20");
+        list.mark_item_done(2).unwrap();
+        list.delete_items(vec![1]).unwrap();
+        assert_eq!(list.find_by_id(&format!("@{id}")).unwrap(), 1);
+        assert!(!list
+            .get_item(1)
+            .unwrap()
+            .render(&DisplayOptions::default())
+            .contains("id:"));
+    }
+
+    #[test]
+    fn duplicate_titles_get_their_own_ids() {
+        let mut list = list("- [ ] same\n- [ ] same id:abc123\n- [ ] same id:abc123");
+        assert!(matches!(
+            list.find_by_id("abc123"),
+            Err(TodoError::AmbiguousItemId(_))
+        ));
+        assert_eq!(list.assign_ids(), 2);
+        let ids = list
+            .items()
+            .map(|item| item.id().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids[1], "abc123");
+        assert!(ids[0] != ids[1] && ids[0] != ids[2] && ids[1] != ids[2]);
+        for (number, id) in ids.iter().enumerate() {
+            assert_eq!(list.find_by_id(id).unwrap(), number + 1);
+        }
+    }
+
+    #[test]
+    fn applying_a_diff_gives_the_other_list() {
+        let old = list(
+            "- [ ] keep\n- [ ] rename me\n- [ ] drop\n- [x] reopen done:2026-10-01\n- [ ] finish",
+        );
+        let new = list(
+            "- [ ] keep\n- [ ] renamed\n- [ ] reopen\n- [x] finish done:2026-10-14\n- [ ] new one\n- [ ] new two",
+        );
+        let changes = old.diff(&new);
+        let kinds = changes
+            .iter()
+            .map(|change| match change {
+                ListChange::Added(item) => format!("+{}", item.name),
+                ListChange::Removed(item) => format!("-{}", item.name),
+                ListChange::Retitled { from, to } => format!("{}->{}", from.name, to.name),
+                ListChange::StateChanged { to, .. } => format!("~{}", to.name),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                "rename me->renamed",
+                "-drop",
+                "~reopen",
+                "~finish done:2026-10-14",
+                "+new one",
+                "+new two"
+            ]
+        );
+        let mut patched = list(&old.as_markdown());
+        patched.apply(&changes).unwrap();
+        assert_eq!(patched.as_markdown(), new.as_markdown());
+        assert!(new.diff(&patched).is_empty());
+    }
+
+    #[test]
+    fn patches_for_items_that_are_gone_dont_apply() {
+        let old = list("- [ ] one\n- [ ] two");
+        let changes = old.diff(&list("- [x] one\n- [ ] three"));
+        let mut other = list("- [ ] one\n- [ ] four");
+        let error = other.apply(&changes).unwrap_err();
+        assert!(matches!(&error, TodoError::PatchConflict(_)), "{error}");
+        assert!(error.to_string().contains("'two'"), "{error}");
+        // the change made before the failing one isn't kept either
+        assert_eq!(other.as_markdown(), "- [ ] one\n- [ ] four");
+
+        let mut removed = list("- [ ] one");
+        let changes = list("- [ ] one\n- [ ] two").diff(&removed);
+        removed.apply(&changes).unwrap_err();
+        assert_eq!(titles(&removed), ["one"]);
+    }
+
+    #[test]
+    fn sorts_within_sections() {
+        let mut list = list(
+            "- [ ] c\n- [ ] a\n\n## Later\n- [ ] b2\n- [ ] a2\n- [ ] b1\nnote\n- [ ] z\n- [ ] y",
+        );
+        list.sort_by(|a, b| a.name[..1].cmp(&b.name[..1]));
+        assert_eq!(
+            list.as_markdown(),
+            "- [ ] a\n- [ ] c\n\n## Later\n- [ ] a2\n- [ ] b2\n- [ ] b1\nnote\n- [ ] y\n- [ ] z"
+        );
+    }
+
+    fn merged_with(strategy: MergeStrategy) -> (TodoList, Vec<TodoItem>) {
+        let mut ours = list(
+            "- [ ] open\n- [x] done here done:2026-10-10\n- [x] newer done:2026-10-01\n- [ ] ours",
+        );
+        let theirs = list("## Theirs\n- [x] open done:2026-10-12\n- [ ] done here\n- [x] newer done:2026-10-05\n- [ ] theirs");
+        let dropped = ours.merge(&theirs, strategy, false);
+        (ours, dropped)
+    }
+
+    #[test]
+    fn merges_keeping_both_copies() {
+        let (merged, dropped) = merged_with(MergeStrategy::KeepBoth);
+        assert!(dropped.is_empty());
+        assert_eq!(
+            titles(&merged),
+            [
+                "open",
+                "done here done:2026-10-10",
+                "newer done:2026-10-01",
+                "ours",
+                "open done:2026-10-12",
+                "done here",
+                "newer done:2026-10-05",
+                "theirs"
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_preferring_done_copies() {
+        let (merged, dropped) = merged_with(MergeStrategy::PreferDone);
+        assert_eq!(
+            titles(&merged),
+            [
+                "open done:2026-10-12",
+                "done here done:2026-10-10",
+                "newer done:2026-10-01",
+                "ours",
+                "theirs"
+            ]
+        );
+        let dropped = dropped
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(dropped, ["open", "done here", "newer done:2026-10-05"]);
+    }
+
+    #[test]
+    fn merges_preferring_newer_copies() {
+        let (merged, dropped) = merged_with(MergeStrategy::PreferNewer);
+        assert_eq!(
+            titles(&merged),
+            [
+                "open done:2026-10-12",
+                "done here done:2026-10-10",
+                "newer done:2026-10-05",
+                "ours",
+                "theirs"
+            ]
+        );
+        let dropped = dropped
+            .iter()
+            .map(|item| item.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(dropped, ["open", "done here", "newer done:2026-10-01"]);
+
+        // the copy merged in wins ties
+        let mut ours = list("- [x] same");
+        ours.merge(&list("- [ ] same"), MergeStrategy::PreferNewer, false);
+        assert_eq!(ours.as_markdown(), "- [ ] same");
+    }
+
+    #[test]
+    fn merges_sections_with_keep_sections() {
+        let mut ours = list("- [ ] one");
+        ours.merge(
+            &list("## Theirs\n- [ ] two"),
+            MergeStrategy::PreferDone,
+            true,
+        );
+        assert_eq!(ours.as_markdown(), "- [ ] one\n## Theirs\n- [ ] two");
+    }
+
+    #[test]
+    fn reports_one_error_for_invalid_items() {
+        let message = |line: &str| match line.parse::<TodoItem>() {
+            Err(TodoError::ParseError(message)) => message,
+            _ => panic!("'{line}' isn't an item"),
+        };
+        assert_eq!(message("- [ ]"), "Item name can't be empty\nFound: '- [ ]'");
+        assert_eq!(
+            message("- [ ] "),
+            "Item name can't be empty\nFound: '- [ ] '"
+        );
+        assert_eq!(
+            message("1. [ab] title"),
+            "The check box should have one mark and a ']'\nFound: '1. [ab] title'"
+        );
+        assert_eq!(
+            message("* [x]title"),
+            "Expected a space after the check box\nFound: '* [x]title'"
+        );
+        assert_eq!(
+            message("> - [x"),
+            "The check box isn't closed with ']'\nFound: '> - [x'"
+        );
+        let list = list("- [ ]\n* [x]title");
+        let messages = list.invalid_lines().iter().map(|e| e.message.as_str());
+        assert_eq!(
+            messages.collect::<Vec<_>>(),
+            [
+                "Item name can't be empty",
+                "Expected a space after the check box"
+            ]
+        );
+    }
 }
@@ -1,11 +1,51 @@
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashSet},
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
-use config::Config;
-use todo::{TodoError, TodoList};
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use config::{Config, Dialect, LineEndings};
+use hooks::ItemEvent;
+use index::ListIndex;
+use sync::BackendKind;
+use todo::{
+    date::{self, parse_since, Date},
+    frontmatter::Frontmatter,
+    indexed::IndexedList,
+    query::Query,
+    regex::Replacer,
+    style::{Glyphs, Style},
+    text::normalize,
+    DisplayOptions, ListChange, MergeStrategy, TodoError, TodoItem, TodoList,
+};
 
+mod backup;
 mod config;
+mod daemon;
+mod digest;
+mod doctor;
+mod done_log;
+mod git;
+mod hooks;
+mod index;
+mod journal;
+mod mcp;
+mod mstodo;
+mod remind;
+mod review;
+mod serve;
+mod standup;
+#[cfg(feature = "sqlite")]
+mod store;
+mod sync;
+mod webhook;
 
 #[derive(Parser, Debug)]
 #[command(author,version, about, long_about = None)]
@@ -13,59 +53,666 @@ struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// Perform actions on this list - general list is used if unspecified
+    /// Perform actions on this list - `TODO_LIST`, the TODO.md in the current directory or the
+    /// general list is used if unspecified
     #[arg(short, long)]
-    list: Option<String>, // TODO: implement some way to store list path in config so lists can be
-    // refered by name here
+    list: Option<String>,
     /// Optionally specify path to a configuration file.
     #[arg(short, long)]
     config: Option<PathBuf>,
+    /// When to use colors in the output
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+    /// Disable colors. Same as `--color=never`
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Use plain ASCII markers for item states
+    #[arg(long, global = true)]
+    ascii: bool,
+    /// Output plain text without colors, emoji or other decorations
+    #[arg(long, global = true)]
+    plain: bool,
+    /// Write lists even if they changed on disk while the command was running
+    #[arg(long, global = true)]
+    force: bool,
+    /// Allow changes to lists marked read-only
+    #[arg(long, global = true)]
+    unlock: bool,
+    /// Show long titles in full instead of fitting them to the terminal width
+    #[arg(long, global = true)]
+    full: bool,
+    /// Leave the `#tag` words out of the titles shown
+    #[arg(long, global = true)]
+    hide_tags: bool,
+    /// Show the IDs of items next to their numbers. Commands taking item numbers take IDs too,
+    /// as `@<id>`, which stay the same when other items are added, removed or changed. Items get
+    /// an `id:` field the first time they're listed with IDs
+    #[arg(long, global = true)]
+    ids: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ColorChoice {
+    /// Use colors only when writing to a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn should_color(&self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+                !no_color && io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// Width of the terminal stdout is connected to, if it is one
+fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        // SAFETY: TIOCGWINSZ only writes into the winsize struct we pass
+        let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if result == 0 && size.ws_col > 0 {
+            return Some(size.ws_col as usize);
+        }
+    }
+    env::var("COLUMNS").ok()?.parse().ok()
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Add an item
     #[command(alias = "a")]
-    Add { title: String },
+    Add {
+        /// Title of the item. Without one, the item is written in the editor
+        title: Option<String>,
+        /// Write the item in `$VISUAL` or `$EDITOR`, starting with TITLE if given. Handy for
+        /// long descriptions
+        #[arg(long)]
+        edit: bool,
+        /// Add the item as already done, to log work that's finished
+        #[arg(long)]
+        done: bool,
+        /// Tag the item, e.g. `--tag work --tag urgent` adds `#work #urgent`
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+    },
+    /// Add the text on the clipboard as an item. The first line is its title, the rest its
+    /// description
+    Capture,
     /// List items
     #[command(alias = "ls")]
     List {
-        #[arg(short, long)]
-        all: bool,
+        #[command(flatten)]
+        filter: ListFilter,
+        /// Keep running and show the list again whenever it changes
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Show all lists
+    Lists {
+        #[command(subcommand)]
+        action: Option<ListsAction>,
+        /// Show how many items of each list are done
+        #[arg(long)]
+        counts: bool,
+        /// Show hidden lists too
+        #[arg(long)]
+        hidden: bool,
+    },
+    /// Show the open items of all lists, numbered as `<list>:<number>`
+    All,
+    /// Show the open items of all lists that are due today or overdue
+    Due {
+        /// Print nothing if no item is due, and exit with 1 after printing the items otherwise,
+        /// e.g. for cron or a shell profile
+        #[arg(long)]
+        check: bool,
     },
     /// Mark items done
     #[command(alias = "d")]
     Done {
-        /// Item numbers to mark
-        #[arg(short, long, num_args(1..))]
-        item_numbers: Vec<usize>,
+        /// Numbers or `@<id>`s of the items to mark. Items of other lists can be given as
+        /// `<list>:<number>`
+        #[arg(short, long, num_args(1..), required_unless_present_any = ["query", "title_match", "last", "all"])]
+        item_numbers: Vec<ItemRef>,
+        /// Mark the items matching this query
+        #[arg(long = "where", value_name = "QUERY", conflicts_with = "item_numbers")]
+        query: Option<String>,
+        /// Mark the open item whose title contains this text, asking which one if several do.
+        /// With --all, every open item containing it is marked
+        #[arg(long = "match", value_name = "TEXT", conflicts_with_all = ["item_numbers", "query"])]
+        title_match: Option<String>,
+        /// Mark the last item of the list, which is the one added last
+        #[arg(long, conflicts_with_all = ["item_numbers", "query", "title_match"])]
+        last: bool,
+        /// Mark every open item
+        #[arg(long, conflicts_with_all = ["item_numbers", "query", "last"])]
+        all: bool,
+        /// With --all, only mark the items with this tag
+        #[arg(long, requires = "all")]
+        tag: Option<String>,
     },
     /// Delete items
     #[command(alias = "rm")]
     Remove {
-        /// Item numbers to delete
-        #[arg(short, long, num_args(1..))]
-        item_numbers: Vec<usize>,
+        /// Numbers or `@<id>`s of the items to delete. Items of other lists can be given as
+        /// `<list>:<number>`
+        #[arg(short, long, num_args(1..), required_unless_present_any = ["query", "title_match", "done", "all"])]
+        item_numbers: Vec<ItemRef>,
+        /// Delete the items matching this query
+        #[arg(long = "where", value_name = "QUERY", conflicts_with = "item_numbers")]
+        query: Option<String>,
+        /// Delete the item whose title contains this text, asking which one if several do
+        #[arg(long = "match", value_name = "TEXT", conflicts_with_all = ["item_numbers", "query"])]
+        title_match: Option<String>,
+        /// Delete all done items
+        #[arg(long, conflicts_with_all = ["item_numbers", "query", "title_match"])]
+        done: bool,
+        /// Delete every item of the list, after asking
+        #[arg(long, conflicts_with_all = ["item_numbers", "query", "title_match", "done"])]
+        all: bool,
     },
     /// move items to another list
     #[command(alias = "mv")]
     Move {
-        /// Item numbers to move
-        #[arg(short, long, num_args(1..))]
-        item_numbers: Vec<usize>,
+        /// Numbers or `@<id>`s of the items to move
+        #[arg(short, long, num_args(1..), required_unless_present = "title_match")]
+        item_numbers: Vec<ItemKey>,
+        /// Move the item whose title contains this text, asking which one if several do
+        #[arg(long = "match", value_name = "TEXT", conflicts_with = "item_numbers")]
+        title_match: Option<String>,
         /// Destination list
         #[arg(short, long)]
         to_list: String,
+        /// Put the items at the end of the section with this heading, which is added if needed,
+        /// instead of at the end of the list
+        #[arg(long)]
+        to_section: Option<String>,
+    },
+    /// Copy items to another list, keeping them in this one
+    #[command(alias = "cp")]
+    Copy {
+        /// Numbers or `@<id>`s of the items to copy
+        #[arg(short, long, num_args(1..), required = true)]
+        item_numbers: Vec<ItemKey>,
+        /// Destination list
+        #[arg(short, long)]
+        to_list: String,
+    },
+    /// Create a new empty list
+    NewList {
+        name: String,
+        /// Create the list file here instead of in the main dir and register it in the config
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Fill the new list with the items of this template
+        #[arg(short, long)]
+        template: Option<String>,
+    },
+    /// Start a new run of a checklist: a list named after the template and today's date, with
+    /// the items of the template
+    Checklist {
+        template: String,
+        /// Name the list `<NAME>-<date>` instead of after the template
+        #[arg(long)]
+        name: Option<String>,
+        /// Archive the list once all its items are done
+        #[arg(long)]
+        archive_when_done: bool,
+    },
+    /// Create a TODO.md in the current directory and register it as a list
+    Init {
+        /// Fill the new list with the items of this template
+        #[arg(short, long)]
+        template: Option<String>,
+    },
+    /// Check the config and the lists for problems
+    Doctor,
+    /// Serve the lists to assistants with the Model Context Protocol over stdio
+    Mcp,
+    /// Serve the lists over HTTP, for browser extensions, phone shortcuts and the like
+    Serve {
+        /// Address to listen on. Use 0.0.0.0 to serve other devices on the network
+        #[arg(long, default_value = "127.0.0.1")]
+        host: String,
+        #[arg(long, default_value_t = 7878)]
+        port: u16,
+        /// Only accept requests with the header `Authorization: Bearer <TOKEN>`
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Show today's journal list, creating it with the open items of the last one if needed
+    Journal,
+    /// Move all items of a list into another one and drop the emptied list
+    Merge {
+        src: String,
+        /// List the items are moved into
+        #[arg(long)]
+        into: String,
+        /// Also move the headings and notes, so the items stay in their sections
+        #[arg(long)]
+        keep_sections: bool,
+        /// What to do with items in both lists: keep-both, prefer-done or prefer-newer, which
+        /// keeps the copy done last
+        #[arg(long, value_name = "STRATEGY", default_value = "prefer-done")]
+        duplicates: MergeStrategy,
+        /// Archive the source list instead of deleting it
+        #[arg(long)]
+        archive: bool,
+    },
+    /// Move the items of a list into new lists, one for each tag or section
+    Split {
+        list: String,
+        #[arg(long)]
+        by: SplitBy,
+    },
+    /// Add the lists of a Microsoft To Do export, a JSON file of lists and their tasks or the
+    /// CSV of tasks Outlook exports. Steps become items indented under their task, and items
+    /// are added to the lists of the same names if there are any
+    Import { file: PathBuf },
+    /// Write the list as a markdown file into a directory
+    Export {
+        /// Write every `## ` section of the list to a file of its own instead, named after its
+        /// heading, e.g. to turn a project's list into documents for a wiki
+        #[arg(long)]
+        split_sections: bool,
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Remove duplicate items, keeping the first of each. It's marked done if any copy is
+    Dedupe {
+        /// Also count titles differing in case, punctuation or a typo as duplicates
+        #[arg(long)]
+        fuzzy: bool,
+        /// Show the duplicates without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Show what changed in the list since another version of it, by default the one in the
+    /// last git commit
+    Diff {
+        /// The other version, e.g. a copy synced from elsewhere
+        file: Option<PathBuf>,
+        /// Print the changes as a JSON patch for `todo apply`
+        #[arg(long)]
+        json: bool,
+    },
+    /// Make the changes in a JSON patch from `todo diff --json` to the list
+    Apply {
+        /// The patch file, or `-` to read it from stdin
+        patch: PathBuf,
+    },
+    /// Replace text in the titles of the items
+    Replace {
+        old: String,
+        new: String,
+        /// Treat OLD as a regex. NEW can refer to its groups as `$1` and so on
+        #[arg(long)]
+        regex: bool,
+        /// Show the changes without making them
+        #[arg(long)]
+        dry_run: bool,
+        /// Replace in all lists that aren't hidden
+        #[arg(long)]
+        all_lists: bool,
+    },
+    /// Go through the open items one by one, deciding to keep, finish, remove, defer or move
+    /// each
+    Review,
+    /// Summarize what was done recently, what's overdue and the open priorities, e.g. for a
+    /// weekly report
+    Digest {
+        /// Count items done since this date, or span like `3d` or `1w`
+        #[arg(long, default_value = "1w", value_parser = parse_since)]
+        since: Date,
+        /// Output HTML instead of plain text
+        #[arg(long)]
+        html: bool,
+    },
+    /// The items done since a date as markdown, e.g. for release notes. Items of all lists are
+    /// included unless `--list` is passed
+    DoneLog {
+        /// A date, or a span like `3d` or `1w`
+        #[arg(long, default_value = "1w", value_parser = parse_since)]
+        since: Date,
+        /// Group the items by their first tag instead of the heading they are under
+        #[arg(long)]
+        by_tag: bool,
+    },
+    /// What was done since yesterday and what's to do today, for pasting into a standup chat
+    Standup {
+        /// Format the report as markdown
+        #[arg(long, conflicts_with = "slack")]
+        markdown: bool,
+        /// Format the report for Slack
+        #[arg(long)]
+        slack: bool,
+    },
+    /// Keep running and show desktop notifications when items are due, or at their `snooze:`
+    /// time
+    Daemon {
+        /// Set up the daemon to start on login instead
+        #[arg(long)]
+        install: bool,
+    },
+    /// Show a desktop notification for each open item that is due today or overdue, e.g. from
+    /// the entries `todo remind` schedules
+    Notify,
+    /// Print crontab lines or systemd timer units that run `todo notify` at the
+    /// `reminder_times` of the config, 09:00 by default
+    Remind {
+        #[arg(long, value_enum, default_value_t = remind::Scheduler::Cron)]
+        scheduler: remind::Scheduler,
+        /// Add them to the crontab, or write the units to the systemd user dir, instead
+        #[arg(long)]
+        install: bool,
+    },
+    /// See and restore backups of lists
+    Backups {
+        #[command(subcommand)]
+        action: BackupsAction,
+    },
+    /// Copy lists between the markdown files and a SQLite DB
+    #[cfg(feature = "sqlite")]
+    Db {
+        #[command(subcommand)]
+        action: DbAction,
+        /// The DB file, `todo.sqlite` in the main dir by default
+        #[arg(long)]
+        db: Option<PathBuf>,
+    },
+    /// Read or change the config
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Sync the lists with the configured backend. By default the changes in the main dir are
+    /// committed, then pulled from and pushed to its git remote
+    Sync {
+        /// Use this backend instead of the configured one
+        #[arg(long, value_enum)]
+        backend: Option<BackendKind>,
+    },
+    /// Open the URL in an item in the browser
+    OpenUrl {
+        /// Number or `@<id>` of the item with the URL
+        item_number: ItemKey,
     },
 }
 
+/// Options of `list` that select the items shown. Items must match all of them
+#[derive(Args, Debug, Default)]
+struct ListFilter {
+    /// Show done items too
+    #[arg(short, long)]
+    all: bool,
+    /// Hide done items, even if the config shows them by default
+    #[arg(short, long, alias = "open", conflicts_with = "all")]
+    open_only: bool,
+    /// Only show done items
+    #[arg(long, conflicts_with_all = ["all", "open_only"])]
+    done: bool,
+    /// Only show items matching this query, e.g. `state:open AND tag:work AND due<2025-07-01`.
+    /// Done items are shown if they match
+    #[arg(long = "where", value_name = "QUERY")]
+    query: Option<String>,
+    /// Only show items matching the query saved as `filters.<FILTER>` in the config
+    #[arg(long)]
+    filter: Option<String>,
+    /// Only show items with this tag
+    #[arg(long)]
+    tag: Vec<String>,
+    /// Only show items whose title contains this text
+    #[arg(long)]
+    search: Option<String>,
+    /// Only show items due before this date, e.g. `2025-07-01` or `tomorrow`
+    #[arg(long, value_parser = parse_date)]
+    due_before: Option<Date>,
+    /// Only show items with `priority:<PRIORITY>` in their title
+    #[arg(long)]
+    priority: Option<String>,
+}
+
+/// An item of a list given on the command line, either as its number or as `@<id>`
+#[derive(Debug, Clone)]
+enum ItemKey {
+    Number(usize),
+    Id(String),
+}
+
+impl FromStr for ItemKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(id) = s.strip_prefix('@') {
+            return Ok(ItemKey::Id(id.to_lowercase()));
+        }
+        s.parse()
+            .map(ItemKey::Number)
+            .map_err(|_| format!("Expected an item number or @<id>. Found '{s}'"))
+    }
+}
+
+impl ItemKey {
+    /// The number of the item in `list`
+    fn number_in(&self, list: &TodoList) -> Result<usize, TodoError> {
+        match self {
+            ItemKey::Number(number) => Ok(*number),
+            ItemKey::Id(id) => list.find_by_id(id),
+        }
+    }
+}
+
+/// The numbers of the items `keys` in `list`
+fn numbers_in(list: &TodoList, keys: &[ItemKey]) -> Result<Vec<usize>, TodoError> {
+    keys.iter().map(|key| key.number_in(list)).collect()
+}
+
+/// An item given on the command line, either as its number or ID in the current list or as
+/// `<list>:<number>`
+#[derive(Debug, Clone)]
+struct ItemRef {
+    list: Option<String>,
+    key: ItemKey,
+}
+
+impl FromStr for ItemRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (list, key) = match s.rsplit_once(':') {
+            Some((list, key)) => (Some(list.to_string()), key),
+            None => (None, s),
+        };
+        let key = key.parse().map_err(|_| {
+            format!("Expected an item number, @<id> or <list>:<number>. Found '{s}'")
+        })?;
+        Ok(Self { list, key })
+    }
+}
+
+/// Splits item references into the items of the current list and items of other lists
+fn split_item_refs(
+    config: &Config,
+    refs: Vec<ItemRef>,
+) -> Result<(Vec<ItemKey>, Vec<SourcedItem>)> {
+    let mut keys = vec![];
+    let mut elsewhere = vec![];
+    for item_ref in refs {
+        let Some(list_name) = item_ref.list else {
+            keys.push(item_ref.key);
+            continue;
+        };
+        let list_name = resolve_list_name(config, &list_name)?;
+        let list_path = config.list_path(&list_name);
+        let list = TodoList::from_file(&list_path)
+            .with_context(|| format!("Couldn't read the list '{list_name}'"))?;
+        let item_number = item_ref.key.number_in(&list)?;
+        let item = list.get_item(item_number)?.clone();
+        elsewhere.push(SourcedItem {
+            list_name,
+            list_path,
+            item_number,
+            item,
+        });
+    }
+    Ok((keys, elsewhere))
+}
+
+fn parse_date(s: &str) -> Result<Date, TodoError> {
+    s.parse()
+}
+
+impl ListFilter {
+    /// The query combining all the options. `show_done` tells if done items are shown when
+    /// no option says otherwise
+    fn query(&self, config: &Config, show_done: bool) -> Result<Query> {
+        let mut parts = vec![];
+        if let Some(query) = &self.query {
+            parts.push(parse_query(query)?);
+        }
+        if let Some(name) = &self.filter {
+            let query = config.filters().get(name).with_context(|| {
+                let names = config.filters().keys().cloned().collect::<Vec<_>>();
+                if names.is_empty() {
+                    format!("There's no filter '{name}'. Add filters to `filters` in the config")
+                } else {
+                    format!(
+                        "There's no filter '{name}'. The filters are: {}",
+                        names.join(", ")
+                    )
+                }
+            })?;
+            parts.push(
+                parse_query(query).with_context(|| format!("The filter '{name}' is invalid"))?,
+            );
+        }
+        // queries decide by themselves whether done items are shown
+        let queried = !parts.is_empty();
+        parts.extend(self.tag.iter().map(|tag| Query::tag(tag)));
+        parts.extend(self.search.as_deref().map(Query::text));
+        parts.extend(
+            self.due_before
+                .map(|date| Query::before("due", &date.to_string())),
+        );
+        parts.extend(
+            self.priority
+                .as_deref()
+                .map(|p| Query::field("priority", p)),
+        );
+        if self.done {
+            parts.push(Query::state(true));
+        } else if self.open_only || !self.all && !queried && !show_done {
+            parts.push(Query::state(false));
+        }
+        Ok(Query::all(parts))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ListsAction {
+    /// Remove a list. Registered lists are unregistered, their file is kept unless
+    /// `--delete-file` or `--archive` is passed
+    Remove {
+        name: String,
+        /// Delete the list file
+        #[arg(long)]
+        delete_file: bool,
+        /// Move the list file into the archive directory instead of deleting it
+        #[arg(long, conflicts_with = "delete_file")]
+        archive: bool,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Stop tracking a registered list. Its file is left untouched
+    Untrack { name: String },
+    /// Unregister lists whose files don't exist anymore
+    Prune {
+        /// Only show the lists that would be unregistered
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[cfg(feature = "sqlite")]
+#[derive(Subcommand, Debug)]
+enum DbAction {
+    /// Copy lists into the DB, replacing the versions in it. All lists if none are given
+    Import { lists: Vec<String> },
+    /// Write lists in the DB to their markdown files. All lists if none are given
+    Export { lists: Vec<String> },
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupsAction {
+    /// Show the backups of a list, or of all lists
+    List { list: Option<String> },
+    /// Replace a list with one of its backups
+    Restore {
+        /// File name of the backup. The latest backup of the current list is used if unspecified
+        backup: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Print a config value, or the whole config if no key is given
+    Get {
+        /// Key of the value. Nested keys are separated by dots, e.g. `theme.done`. Keys with
+        /// dots go in brackets and quotes, e.g. `lists["example.com"].readonly`
+        key: Option<String>,
+    },
+    /// Change a config value
+    Set {
+        /// Key of the value, written as for `get`
+        key: String,
+        /// New value. Parsed as JSON if possible, used as a string otherwise
+        value: String,
+    },
+    /// Remove a value from the config file so its default is used
+    Unset { key: String },
+    /// Register a list file that lives outside the main dir
+    AddList { name: String, path: PathBuf },
+    /// Unregister a list added with `add-list`. The list file is left as is
+    RemoveList { name: String },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SplitBy {
+    /// The first tag of each item. Items without tags stay
+    Tag,
+    /// The heading each item is under. Items under the heading with the list's name stay
+    Section,
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    let config = if let Some(config_path) = cli.config {
+    let config_path = cli
+        .config
+        .or_else(|| env::var_os(config::CONFIG_PATH_VAR).map(PathBuf::from));
+    if let Some(Commands::Doctor) = cli.command {
+        return doctor::run(config_path.as_deref());
+    }
+
+    let config = if let Some(config_path) = config_path {
         Config::read_from(&config_path)?
     } else if let Ok(config) = Config::read_from_default() {
         config
+    } else if let Some(config) = Config::from_env() {
+        config
     } else {
         println!(
             "Looked for the config file at '{}'",
@@ -74,42 +721,371 @@ fn main() -> Result<()> {
         println!("It either does not exist or is invalid.");
         println!("You can stop the application now or you can respond to the following questions to create a new config file.");
         Config::read_interactive()?
+    }
+    .with_env_overrides();
+    if let Some(zone) = config.time_zone() {
+        date::set_time_zone(
+            zone.parse()
+                .context("The time_zone in the config is invalid")?,
+        );
+    }
+
+    let plain = cli.plain || *config.plain();
+    let color = if cli.no_color || plain {
+        ColorChoice::Never
+    } else {
+        cli.color
+    };
+    let color = color.should_color();
+    let display_options = DisplayOptions {
+        color,
+        hyperlinks: color && *config.hyperlinks(),
+        theme: config.theme().clone(),
+        glyphs: if plain {
+            Glyphs::plain()
+        } else if cli.ascii {
+            Glyphs::ascii()
+        } else {
+            config.glyphs().clone()
+        },
+        width: (!cli.full).then(terminal_width).flatten(),
+        overflow: *config.overflow(),
+        hide_tags: cli.hide_tags || *config.hide_tags(),
+        number_width: *config.number_width(),
+        show_ids: cli.ids || *config.show_ids(),
+        absolute_dates: *config.absolute_dates(),
+        date_format: config.date_format().clone(),
     };
 
     // list is the default command
-    let command = cli.command.unwrap_or(Commands::List { all: false });
+    let command = cli.command.unwrap_or(Commands::List {
+        filter: ListFilter::default(),
+        watch: false,
+    });
+
+    let writer = ListWriter {
+        config: &config,
+        force: cli.force,
+        unlock: cli.unlock,
+    };
+    let requested_list = cli
+        .list
+        .clone()
+        .or_else(|| env::var(LIST_VAR).ok().filter(|l| !l.is_empty()));
+    if let Some(name) = requested_list.filter(|name| config.smart_lists().contains_key(name)) {
+        return run_smart_list(&config, &name, command, &writer, &display_options);
+    }
 
     // perform operation on this list
-    let list_name = cli.list.unwrap_or(config.general_list().clone());
-    let list_path = config.list_path(&list_name);
+    let (list_name, list_path) = current_list(&config, cli.list.as_deref())?;
 
     match command {
-        Commands::Add { title } => {
-            let mut list = match TodoList::from_file(&list_path) {
-                Ok(list) => list,
-                Err(TodoError::FileIOError(_)) => TodoList::new(&list_name),
-                Err(e) => return Err(e.into()),
-            };
-            list.add_item(&title);
-            list.write(&list_path)
-                .with_context(|| "Couldn't write the list")?;
+        Commands::Add {
+            title,
+            edit,
+            done,
+            tags,
+        } => {
+            let (title, description) = match title {
+                Some(title) if !edit => (title, None),
+                title => compose_item(title.as_deref().unwrap_or_default())?,
+            };
+            let added = add_item(&writer, &list_name, &list_path, &title, |item| {
+                item.description = description;
+                for tag in &tags {
+                    item.add_tag(tag);
+                }
+                if done {
+                    item.mark_done();
+                }
+            })?;
+            if done {
+                hooks::item_event(&config, ItemEvent::Done, &list_name, &[added]);
+            }
         }
-        Commands::List { all } => {
-            let list = TodoList::from_file(&list_path)?;
-            println!(
-                "{}",
-                list.display_with_numbers(|&(_, i)| { all || !i.is_done() })
-            );
+        Commands::Capture => {
+            let text = read_clipboard()?;
+            let Some((title, description)) = item_from_text(&text) else {
+                bail!("The clipboard is empty");
+            };
+            let added = add_item(&writer, &list_name, &list_path, &title, |item| {
+                item.description = description;
+            })?;
+            println!("Added {}", added.render(&display_options));
         }
-        Commands::Done { item_numbers } => {
-            let done_items = {
+        Commands::List { filter, watch } => {
+            let show_done = config
+                .list_settings(&list_name)
+                .show_done
+                .unwrap_or(*config.show_done_by_default());
+            let query = filter.query(&config, show_done)?;
+            // items get their IDs the first time they're shown, unless the list is read-only
+            if display_options.show_ids
+                && list_path.exists()
+                && writer.ensure_writable(&list_path).is_ok()
+            {
                 let mut list = TodoList::from_file(&list_path)?;
-                let done_items = item_numbers
+                if list.assign_ids() > 0 {
+                    writer.write(&mut list, &list_path, "give items IDs")?;
+                }
+            }
+            let render = || render_list(&config, &list_path, &query, &display_options);
+            if watch {
+                watch_output(render);
+            }
+            println!("{}", render()?);
+        }
+        Commands::Lists {
+            action: Some(action),
+            ..
+        } => match action {
+            ListsAction::Remove {
+                name,
+                delete_file,
+                archive,
+                yes,
+            } => {
+                let registered = config.external_list_path(&name).is_some();
+                let path = config.list_path(&name);
+                if !registered && !path.exists() {
+                    bail!("The list '{name}' doesn't exist");
+                }
+                if !registered && !delete_file && !archive {
+                    bail!("'{name}' is a list in the main dir. Pass --delete-file to delete it or --archive to archive it");
+                }
+                let action = if delete_file {
+                    format!(
+                        "Delete the list '{name}' and its file '{}'?",
+                        path.display()
+                    )
+                } else if archive {
+                    format!("Archive the list '{name}'?")
+                } else {
+                    format!("Unregister the list '{name}'? '{}' is kept", path.display())
+                };
+                if delete_file || archive {
+                    writer.ensure_writable(&path)?;
+                }
+                if !yes && !confirm(&action)? {
+                    return Ok(());
+                }
+                if delete_file && path.exists() {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("Couldn't delete '{}'", path.display()))?;
+                } else if archive && path.exists() {
+                    let archived = archive_list(&config, &name, &path)?;
+                    println!("Archived to '{}'", archived.display());
+                }
+                if registered {
+                    config.edit(|c| config::unset_key(c, &["lists", &name]))?;
+                }
+                println!("Removed the list '{name}'");
+            }
+            ListsAction::Untrack { name } => {
+                let Some(path) = config.external_list_path(&name) else {
+                    bail!("'{name}' is not a registered list");
+                };
+                config.edit(|c| config::unset_key(c, &["lists", &name]))?;
+                println!(
+                    "Stopped tracking '{name}'. '{}' is left as is",
+                    path.display()
+                );
+            }
+            ListsAction::Prune { dry_run } => {
+                let dead = config
+                    .lists()
                     .iter()
-                    .map(|item_number| list.mark_item_done(*item_number).map(|i| i.clone()))
+                    .filter(|(_, list)| list.path().is_some_and(|p| !p.exists()))
+                    .collect::<Vec<_>>();
+                if dead.is_empty() {
+                    println!("All registered lists exist");
+                    return Ok(());
+                }
+                for (name, list) in &dead {
+                    let path = list.path().expect("only lists with a path are dead");
+                    println!("{name}: '{}' doesn't exist", path.display());
+                }
+                if dry_run {
+                    println!("{} list(s) would be unregistered", dead.len());
+                } else {
+                    config.edit(|c| {
+                        dead.iter()
+                            .try_for_each(|(name, _)| config::unset_key(c, &["lists", name]))
+                    })?;
+                    println!("Unregistered {} list(s)", dead.len());
+                }
+            }
+        },
+        Commands::Lists {
+            action: None,
+            counts,
+            hidden,
+        } => {
+            let names = config
+                .list_names()?
+                .into_iter()
+                .filter(|name| hidden || !config.is_hidden(name))
+                .collect::<Vec<_>>();
+            let width = names.iter().map(|n| n.len()).max().unwrap_or(0);
+            let mut index = ListIndex::load(&config);
+            for name in names {
+                let path = config.list_path(&name);
+                let style =
+                    list_style(&Frontmatter::read(&path).ok().flatten().unwrap_or_default());
+                if counts {
+                    let mut list = TodoList::new(&name);
+                    list.add_items(index.items(&path)?);
+                    let shown = display_options.paint(&style, &format!("{name: <width$}"));
+                    println!("{shown} {}", list.progress_bar(&display_options));
+                } else {
+                    println!("{}", display_options.paint(&style, &name));
+                }
+            }
+            index.save()?;
+        }
+        Commands::All => print_labeled(&open_items_of_all_lists(&config)?, &display_options),
+        Commands::Due { check } => {
+            let today = Date::today();
+            let due = open_items_of_all_lists(&config)?
+                .into_iter()
+                .filter(|(_, item)| item.due().is_some_and(|(date, _)| date <= today))
+                .collect::<Vec<_>>();
+            if due.is_empty() {
+                if !check {
+                    println!("Nothing is due");
+                }
+                return Ok(());
+            }
+            print_labeled(&due, &display_options);
+            if check {
+                std::process::exit(1);
+            }
+        }
+        Commands::Done {
+            item_numbers,
+            query,
+            title_match,
+            last,
+            all,
+            tag,
+        } => {
+            let (item_keys, elsewhere) = split_item_refs(&config, item_numbers)?;
+            // finding items by ID needs their titles, so only numbers are marked in place
+            let plain_numbers = item_keys
+                .iter()
+                .map(|key| match key {
+                    ItemKey::Number(number) => Some(*number),
+                    ItemKey::Id(_) => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            let in_place = match plain_numbers {
+                Some(numbers) if query.is_none() && title_match.is_none() && !last && !all => {
+                    writer.mark_done_in_place(&list_name, &list_path, &numbers)?
+                }
+                _ => None,
+            };
+            let done_items = if let Some(mut done_items) = in_place {
+                done_items.extend(mark_done_at_source(
+                    &writer,
+                    &elsewhere.iter().collect::<Vec<_>>(),
+                )?);
+                done_items
+            } else {
+                let mut list = TodoList::from_file(&list_path)?;
+                let included = included_items(&list_path, &list)?;
+                let item_numbers = match query {
+                    Some(query) => {
+                        let query = parse_query(&query)?;
+                        let own = list.len();
+                        let mut numbers = list.filter(&query);
+                        numbers.extend(
+                            (1..=included.len())
+                                .filter(|&n| query.matches(&included[n - 1].item))
+                                .map(|n| own + n),
+                        );
+                        if numbers.is_empty() {
+                            bail!("No items match the query");
+                        }
+                        numbers
+                    }
+                    None if all => {
+                        let tag = tag.as_deref().map(Query::tag);
+                        let needle = title_match.as_deref().map(normalize);
+                        let numbers = list
+                            .numbered_items()
+                            .filter(|(_, item)| {
+                                !item.is_done()
+                                    && tag.as_ref().is_none_or(|tag| tag.matches(item))
+                                    && needle
+                                        .as_ref()
+                                        .is_none_or(|needle| normalize(&item.name).contains(needle))
+                            })
+                            .map(|(number, _)| number)
+                            .collect::<Vec<_>>();
+                        if numbers.is_empty() {
+                            bail!("There are no open items to mark done");
+                        }
+                        numbers
+                    }
+                    None => match &title_match {
+                        Some(text) => vec![match_item(&list, text, true, &display_options)?],
+                        None if last => {
+                            let Some((number, item)) = list.numbered_items().last() else {
+                                bail!("The list is empty");
+                            };
+                            if item.is_done() {
+                                bail!(
+                                    "The last item '{}' is already done",
+                                    item.name_without_field("done")
+                                );
+                            }
+                            vec![number]
+                        }
+                        None => numbers_in(&list, &item_keys)?,
+                    },
+                };
+                // items after the list's own ones come from the lists it includes
+                let (own, from_included): (Vec<_>, Vec<_>) =
+                    item_numbers.into_iter().partition(|&n| n <= list.len());
+                let from_included = from_included
+                    .into_iter()
+                    .map(|n| {
+                        included
+                            .get(n - list.len() - 1)
+                            .ok_or(TodoError::InvalidItemNumber(n))
+                    })
                     .collect::<Result<Vec<_>, _>>()?;
-                list.write(&config.list_path(&list_name))
-                    .with_context(|| "Something went wrong. Couldn't write to the list.")?;
+                let mut done_items = own
+                    .iter()
+                    .map(|item_number| list.mark_item_done(*item_number).cloned())
+                    .collect::<Result<Vec<_>, _>>()?;
+                if !done_items.is_empty() {
+                    writer
+                        .write(
+                            &mut list,
+                            &list_path,
+                            &format!("done {}", quoted_titles(&done_items)),
+                        )
+                        .with_context(|| "Something went wrong. Couldn't write to the list.")?;
+                    hooks::item_event(&config, ItemEvent::Done, &list_name, &done_items);
+                }
+                let archive = config
+                    .lists()
+                    .get(&list_name)
+                    .is_some_and(|l| *l.archive_when_done());
+                if archive && !list.is_empty() && list.done_count() == list.len() {
+                    let archived = archive_list(&config, &list_name, &list_path)?;
+                    config.edit(|c| config::unset_key(c, &["lists", &list_name]))?;
+                    println!(
+                        "All items of '{list_name}' are done. Archived it to '{}'",
+                        archived.display()
+                    );
+                }
+                done_items.extend(mark_done_at_source(&writer, &from_included)?);
+                done_items.extend(mark_done_at_source(
+                    &writer,
+                    &elsewhere.iter().collect::<Vec<_>>(),
+                )?);
                 done_items
             };
 
@@ -117,42 +1093,1580 @@ fn main() -> Result<()> {
                 "Marked item(s) done.\n{}",
                 done_items
                     .iter()
-                    .map(|i| i.to_string())
+                    .map(|i| i.render(&display_options))
                     .collect::<Vec<String>>()
                     .join("\n")
             );
         }
-        Commands::Remove { item_numbers } => {
+        Commands::Remove {
+            item_numbers,
+            query,
+            title_match,
+            done,
+            all,
+        } => {
+            let (item_keys, elsewhere) = split_item_refs(&config, item_numbers)?;
             let mut list = TodoList::from_file(&list_path)?;
-            let removed_items = list.delete_items(item_numbers)?;
+            let item_numbers = match (query, title_match) {
+                (Some(query), _) => matching_items(&list, &query)?,
+                (None, Some(text)) => vec![match_item(&list, &text, false, &display_options)?],
+                (None, None) if done => {
+                    let numbers = list
+                        .numbered_items()
+                        .filter(|(_, item)| item.is_done())
+                        .map(|(number, _)| number)
+                        .collect::<Vec<_>>();
+                    if numbers.is_empty() {
+                        println!("There are no done items in '{list_name}'");
+                        return Ok(());
+                    }
+                    numbers
+                }
+                (None, None) if all => {
+                    let question = format!("Delete all {} items of '{list_name}'?", list.len());
+                    if list.is_empty() || !confirm(&question)? {
+                        return Ok(());
+                    }
+                    (1..=list.len()).collect()
+                }
+                (None, None) => numbers_in(&list, &item_keys)?,
+            };
+            let mut removed_items = list.delete_items(item_numbers)?;
 
-            list.write(&list_path)
-                .with_context(|| "Couldn't write to the list")?;
+            if !removed_items.is_empty() {
+                backup::backup_list(&config, &list_name, &list_path)?;
+                writer
+                    .write(
+                        &mut list,
+                        &list_path,
+                        &format!("remove {}", quoted_titles(&removed_items)),
+                    )
+                    .with_context(|| "Couldn't write to the list")?;
+                hooks::item_event(&config, ItemEvent::Removed, &list_name, &removed_items);
+            }
+            removed_items.extend(remove_at_source(&config, &writer, &elsewhere)?);
 
             println!(
                 "Deleted todo item(s)\n{}",
                 removed_items
                     .iter()
-                    .map(|i| i.to_string())
+                    .map(|i| i.render(&display_options))
                     .collect::<Vec<_>>()
                     .join("\n")
             );
         }
         Commands::Move {
             item_numbers,
+            title_match,
             to_list,
+            to_section,
         } => {
             let mut from_list = TodoList::from_file(&list_path)?;
-            let to_list_path = config.list_path(&to_list);
+            let item_numbers = match title_match {
+                Some(text) => vec![match_item(&from_list, &text, false, &display_options)?],
+                None => numbers_in(&from_list, &item_numbers)?,
+            };
+            let to_list_name = resolve_list_name(&config, &to_list)?;
+            let to_list_path = config.list_path(&to_list_name);
             let mut to_list = TodoList::from_file(&to_list_path)?;
+            writer.ensure_writable(&list_path)?;
+            writer.ensure_writable(&to_list_path)?;
             let removed_items = from_list.delete_items(item_numbers)?;
-            to_list.add_items(removed_items);
+            let message = format!("move {} to {to_list_name}", quoted_titles(&removed_items));
+            match &to_section {
+                Some(section) => to_list.add_items_to_section(removed_items, section),
+                None => to_list.add_items(removed_items),
+            }
+
+            backup::backup_list(&config, &list_name, &list_path)?;
+            writer
+                .write(&mut to_list, &to_list_path, &message)
+                .with_context(|| {
+                    "Couldn't write to destination list. Items not added or removed"
+                })?;
+            writer.write(&mut from_list, &list_path, &message).with_context(|| "Couldn't write to source list. Items not removed from source list but written to destination list.")?;
+        }
+        Commands::Copy {
+            item_numbers,
+            to_list,
+        } => {
+            let from_list = TodoList::from_file(&list_path)?;
+            let to_list_name = resolve_list_name(&config, &to_list)?;
+            if to_list_name == list_name {
+                bail!("Can't copy items to the list they are in");
+            }
+            let to_list_path = config.list_path(&to_list_name);
+            let mut to_list = TodoList::from_file(&to_list_path)?;
+            let items = numbers_in(&from_list, &item_numbers)?
+                .into_iter()
+                .map(|number| from_list.get_item(number).cloned())
+                .collect::<Result<Vec<_>, _>>()?;
+            let message = format!("copy {} to {to_list_name}", quoted_titles(&items));
+            to_list.add_items(items.clone());
+            writer
+                .write(&mut to_list, &to_list_path, &message)
+                .with_context(|| "Couldn't write to destination list. Items not copied")?;
+            hooks::item_event(&config, ItemEvent::Added, &to_list_name, &items);
+            println!("Copied {} item(s) to '{to_list_name}'", items.len());
+        }
+        Commands::NewList {
+            name,
+            path,
+            template,
+        } => {
+            if config.lists().contains_key(&name) || config.list_path(&name).exists() {
+                bail!("The list '{name}' already exists");
+            }
+            let list_path = match &path {
+                Some(path) => std::path::absolute(path)
+                    .with_context(|| format!("Invalid path '{}'", path.display()))?,
+                None => config.list_path(&name),
+            };
+            if list_path.exists() {
+                bail!("'{}' already exists", list_path.display());
+            }
+            create_list(&writer, &list_path, &name, template.as_deref())?;
+            if path.is_some() {
+                config.register_list(&name, &list_path)?;
+            }
+            println!("Created the list '{name}' at '{}'", list_path.display());
+        }
+        Commands::Checklist {
+            template,
+            name,
+            archive_when_done,
+        } => {
+            let base = name.as_deref().unwrap_or(&template);
+            let name = config.unique_list_name(&format!("{base}-{}", Date::today()));
+            let list_path = config.list_path(&name);
+            create_list(&writer, &list_path, &name, Some(&template))?;
+            if archive_when_done {
+                config.edit(|c| {
+                    config::set_key(
+                        c,
+                        &["lists", &name, "archive_when_done"],
+                        serde_json::json!(true),
+                    )
+                })?;
+            }
+            println!("Created the list '{name}'. Use it with `todo --list {name}`");
+        }
+        Commands::Init { template } => {
+            let cwd = env::current_dir().context("Couldn't find the current directory")?;
+            let path = cwd.join("TODO.md");
+            if let Some(name) = config
+                .lists()
+                .iter()
+                .find_map(|(name, list)| (list.path() == Some(&path)).then_some(name))
+            {
+                println!("'{}' is already registered as '{name}'", path.display());
+                return Ok(());
+            }
+            if path.exists() {
+                println!("Using the existing '{}'", path.display());
+            } else {
+                create_list(&writer, &path, "TODO", template.as_deref())?;
+                println!("Created '{}'", path.display());
+            }
+            let dir_name = cwd
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| "root".to_string());
+            let name = config.unique_list_name(&dir_name);
+            config.register_list(&name, &path)?;
+            println!("Registered it as the list '{name}'. Use it with `todo --list {name}`");
+        }
+        Commands::Backups { action } => match action {
+            BackupsAction::List { list } => {
+                for backup in backup::find_backups(&config, list.as_deref())? {
+                    println!("{}", backup.file_name());
+                }
+            }
+            BackupsAction::Restore { backup } => {
+                let backups = backup::find_backups(&config, None)?;
+                let backup = match &backup {
+                    Some(file_name) => backups
+                        .iter()
+                        .find(|b| &b.file_name() == file_name)
+                        .with_context(|| format!("There's no backup '{file_name}'"))?,
+                    None => backups
+                        .iter()
+                        .rfind(|b| b.list == list_name)
+                        .with_context(|| format!("There are no backups of '{list_name}'"))?,
+                };
+                let target = if backup.list == list_name {
+                    list_path.clone()
+                } else {
+                    config.list_path(&backup.list)
+                };
+                writer.ensure_writable(&target)?;
+                // the current state can be restored again if this was a mistake
+                backup::backup_list(&config, &backup.list, &target)?;
+                fs::copy(&backup.path, &target)
+                    .with_context(|| format!("Couldn't restore '{}'", target.display()))?;
+                println!("Restored '{}' from {}", backup.list, backup.file_name());
+            }
+        },
+        #[cfg(feature = "sqlite")]
+        Commands::Db { action, db } => {
+            let db = store::SqliteStore {
+                path: db.unwrap_or_else(|| config.main_dir().join("todo.sqlite")),
+            };
+            let files = store::MarkdownStore { writer: &writer };
+            let aliased = |lists: &[String]| {
+                lists
+                    .iter()
+                    .map(|list| config.resolve_alias(list).to_string())
+                    .collect::<Vec<_>>()
+            };
+            match action {
+                DbAction::Import { lists } => {
+                    let count = store::copy(&files, &db, &aliased(&lists))?;
+                    println!("Imported {count} list(s) into '{}'", db.path.display());
+                }
+                DbAction::Export { lists } => {
+                    let count = store::copy(&db, &files, &aliased(&lists))?;
+                    println!("Exported {count} list(s) from '{}'", db.path.display());
+                }
+            }
+        }
+        Commands::Sync { backend } => sync::run(&config, backend)?,
+        Commands::Mcp => mcp::run(&writer, &list_name, &list_path)?,
+        Commands::Serve { host, port, token } => {
+            serve::run(&writer, &host, port, token.as_deref())?
+        }
+        Commands::Merge {
+            src,
+            into,
+            keep_sections,
+            duplicates,
+            archive,
+        } => {
+            let src = resolve_list_name(&config, &src)?;
+            let dst = resolve_list_name(&config, &into)?;
+            if src == dst {
+                bail!("Can't merge '{src}' into itself");
+            }
+            let src_path = config.list_path(&src);
+            let dst_path = config.list_path(&dst);
+            for (name, path) in [(&src, &src_path), (&dst, &dst_path)] {
+                if !path.exists() {
+                    bail!("The list '{name}' doesn't exist");
+                }
+                writer.ensure_writable(path)?;
+            }
+            let src_list = TodoList::from_file(&src_path)?;
+            let mut dst_list = TodoList::from_file(&dst_path)?;
+            backup::backup_list(&config, &src, &src_path)?;
+            backup::backup_list(&config, &dst, &dst_path)?;
+
+            let dropped = dst_list.merge(&src_list, duplicates, keep_sections);
+            writer
+                .write(&mut dst_list, &dst_path, &format!("merge {src} into {dst}"))
+                .with_context(|| format!("Couldn't write to the list '{dst}'. '{src}' is kept"))?;
+
+            if archive {
+                let archived = archive_list(&config, &src, &src_path)?;
+                println!("Archived '{src}' to '{}'", archived.display());
+            } else {
+                fs::remove_file(&src_path)
+                    .with_context(|| format!("Couldn't delete '{}'", src_path.display()))?;
+            }
+            if config.external_list_path(&src).is_some() {
+                config.edit(|c| config::unset_key(c, &["lists", &src]))?;
+            }
+            let aliases = config
+                .aliases()
+                .iter()
+                .filter(|(_, target)| **target == src)
+                .map(|(alias, _)| alias.clone())
+                .collect::<Vec<_>>();
+            if !aliases.is_empty() {
+                config.edit(|c| {
+                    aliases.iter().try_for_each(|alias| {
+                        config::set_key(c, &["aliases", alias], serde_json::json!(dst))
+                    })
+                })?;
+            }
+            println!(
+                "Merged {} item(s) of '{src}' into '{dst}', dropping {} duplicate(s)",
+                src_list.len(),
+                dropped.len()
+            );
+        }
+        Commands::Import { file } => {
+            let imported = mstodo::read(&file)?;
+            if imported.is_empty() {
+                println!("There are no lists in '{}'", file.display());
+            }
+            for imported in imported {
+                let path = config.list_path(&imported.name);
+                let mut list = if path.exists() {
+                    TodoList::from_file(&path)?
+                } else {
+                    if let Some(dir) = path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    let mut list = TodoList::new(&imported.name);
+                    list.add_line(&format!("# {}", imported.title));
+                    list
+                };
+                let count = imported.items.len();
+                list.add_items(imported.items);
+                writer
+                    .write(
+                        &mut list,
+                        &path,
+                        &format!("import {count} item(s) from Microsoft To Do"),
+                    )
+                    .with_context(|| format!("Couldn't write the list '{}'", imported.name))?;
+                println!("Imported {count} item(s) into '{}'", imported.name);
+            }
+        }
+        Commands::Export {
+            split_sections,
+            dir,
+        } => {
+            let list = TodoList::from_file(&list_path)?;
+            let parts = if split_sections {
+                list.split_sections()
+            } else {
+                vec![(None, list)]
+            };
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Couldn't create the directory '{}'", dir.display()))?;
+            let mut written = HashSet::new();
+            for (heading, part) in parts {
+                let base = heading
+                    .map(|heading| normalize(&heading).replace(' ', "-"))
+                    .filter(|name| !name.is_empty())
+                    .unwrap_or_else(|| list_name.clone());
+                let name = (1..)
+                    .map(|i| match i {
+                        1 => base.clone(),
+                        i => format!("{base}-{i}"),
+                    })
+                    .find(|name| !written.contains(name))
+                    .expect("some number is free");
+                let path = dir.join(format!("{name}.md"));
+                part.overwrite(&path)
+                    .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+                println!("Wrote '{}'", path.display());
+                written.insert(name);
+            }
+        }
+        Commands::Split { list, by } => {
+            let name = resolve_list_name(&config, &list)?;
+            let path = config.list_path(&name);
+            if !path.exists() {
+                bail!("The list '{name}' doesn't exist");
+            }
+            writer.ensure_writable(&path)?;
+            let mut list = TodoList::from_file(&path)?;
+            // new lists are named after what the items are grouped by, e.g. `work-urgent`
+            let suffix = |key: &str| normalize(key).replace(' ', "-");
+            let mut groups = BTreeMap::<String, Vec<usize>>::new();
+            for (number, (section, item)) in (1..).zip(list.sectioned_items()) {
+                let key = match by {
+                    SplitBy::Tag => item.tags().first().copied(),
+                    SplitBy::Section => section.filter(|section| *section != name),
+                };
+                let Some(suffix) = key.map(suffix).filter(|s| !s.is_empty()) else {
+                    continue;
+                };
+                groups.entry(suffix).or_default().push(number);
+            }
+            if groups.is_empty() {
+                println!("There's nothing to split '{name}' by");
+                return Ok(());
+            }
+
+            backup::backup_list(&config, &name, &path)?;
+            let mut taken = list.delete_items(groups.values().flatten().copied().collect())?;
+            if let SplitBy::Section = by {
+                list.remove_lines(|line| {
+                    line.starts_with('#') && groups.contains_key(&suffix(line.trim_matches('#')))
+                });
+            }
+            // lists outside the main dir are split into lists next to them
+            let external_dir = config
+                .external_list_path(&name)
+                .map(|path| path.parent().unwrap_or(Path::new("")).to_path_buf());
+            for (suffix, numbers) in &groups {
+                let items = taken.drain(..numbers.len()).collect::<Vec<_>>();
+                let new_name = config.unique_list_name(&format!("{name}-{suffix}"));
+                let new_path = match &external_dir {
+                    Some(dir) => dir.join(format!("{new_name}.md")),
+                    None => config.list_path(&new_name),
+                };
+                if new_path.exists() {
+                    bail!("'{}' already exists", new_path.display());
+                }
+                create_list(&writer, &new_path, &new_name, None)?;
+                let mut new_list = TodoList::from_file(&new_path)?;
+                let message = format!("move {} to {new_name}", quoted_titles(&items));
+                new_list.add_items(items);
+                writer
+                    .write(&mut new_list, &new_path, &message)
+                    .with_context(|| format!("Couldn't write to the list '{new_name}'"))?;
+                if external_dir.is_some() {
+                    config.register_list(&new_name, &new_path)?;
+                }
+                println!("Moved {} item(s) to '{new_name}'", numbers.len());
+            }
+            writer
+                .write(&mut list, &path, &format!("split {name}"))
+                .with_context(|| format!("Couldn't write the list '{name}'"))?;
+        }
+        Commands::Diff { file, json } => {
+            let list = TodoList::from_file(&list_path)?;
+            let other = match file {
+                Some(file) => TodoList::from_file(&file)?,
+                None => {
+                    let contents = git::head_version(&list_path)?;
+                    TodoList::from_reader(contents.as_bytes())
+                        .context("The list in the last commit is invalid")?
+                }
+            };
+            let changes = other.diff(&list);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&changes)?);
+                return Ok(());
+            }
+            if changes.is_empty() {
+                println!("There are no changes");
+            }
+            for change in changes {
+                match change {
+                    ListChange::Added(item) => {
+                        println!("{}", item.render_labeled("added", &display_options))
+                    }
+                    ListChange::Removed(item) => {
+                        println!("{}", item.render_labeled("removed", &display_options))
+                    }
+                    ListChange::Retitled { from, to } => println!(
+                        "{} (was '{}')",
+                        to.render_labeled("retitled", &display_options),
+                        from.name
+                    ),
+                    ListChange::StateChanged { to, .. } => {
+                        let label = if to.is_done() { "done" } else { "reopened" };
+                        println!("{}", to.render_labeled(label, &display_options));
+                    }
+                }
+            }
+        }
+        Commands::Apply { patch } => {
+            let contents = if patch.as_os_str() == "-" {
+                io::read_to_string(io::stdin()).context("Couldn't read the patch from stdin")?
+            } else {
+                fs::read_to_string(&patch)
+                    .with_context(|| format!("Couldn't read '{}'", patch.display()))?
+            };
+            let changes = serde_json::from_str::<Vec<ListChange>>(&contents)
+                .context("The patch is invalid")?;
+            let mut list = TodoList::from_file(&list_path)?;
+            list.apply(&changes)?;
+            backup::backup_list(&config, &list_name, &list_path)?;
+            writer.write(&mut list, &list_path, &format!("apply {}", patch.display()))?;
+            let (mut added, mut done, mut removed) = (vec![], vec![], vec![]);
+            for change in changes {
+                match change {
+                    ListChange::Added(item) => added.push(item),
+                    ListChange::Removed(item) => removed.push(item),
+                    ListChange::StateChanged { to, .. } if to.is_done() => done.push(to),
+                    _ => {}
+                }
+            }
+            hooks::item_event(&config, ItemEvent::Added, &list_name, &added);
+            hooks::item_event(&config, ItemEvent::Done, &list_name, &done);
+            hooks::item_event(&config, ItemEvent::Removed, &list_name, &removed);
+            println!("Applied the patch to '{list_name}'");
+        }
+        Commands::Dedupe { fuzzy, dry_run } => {
+            let mut list = TodoList::from_file(&list_path)?;
+            let groups = list.duplicates(fuzzy);
+            if groups.is_empty() {
+                println!("There are no duplicates");
+                return Ok(());
+            }
+            let highest = groups.iter().flatten().max().copied().unwrap_or(0);
+            let options = display_options.fitting_numbers(highest);
+            for group in &groups {
+                for (i, &number) in group.iter().enumerate() {
+                    let item = list.get_item(number)?;
+                    let label = if i == 0 { "keep" } else { "drop" };
+                    println!("{label} {}", item.render_numbered(number, &options));
+                }
+                println!();
+            }
+            let duplicates = groups.iter().map(|g| g.len() - 1).sum::<usize>();
+            if dry_run {
+                println!("{duplicates} duplicate(s) would be removed");
+                return Ok(());
+            }
+            let removed = remove_duplicates(&mut list, &groups)?;
+            backup::backup_list(&config, &list_name, &list_path)?;
+            writer
+                .write(
+                    &mut list,
+                    &list_path,
+                    &format!("remove duplicates {}", quoted_titles(&removed)),
+                )
+                .with_context(|| "Couldn't write the list")?;
+            hooks::item_event(&config, ItemEvent::Removed, &list_name, &removed);
+            println!("Removed {duplicates} duplicate(s)");
+        }
+        Commands::Replace {
+            old,
+            new,
+            regex,
+            dry_run,
+            all_lists,
+        } => {
+            let replacer = Replacer::new(&old, &new, regex)?;
+            let lists = if all_lists {
+                visible_lists(&config)?
+            } else {
+                vec![(list_name, list_path)]
+            };
+            let mut changes = 0;
+            let mut changed_lists = vec![];
+            for (name, path) in lists {
+                let mut list = TodoList::from_file(&path)?;
+                let mut changed = false;
+                for number in 1..=list.len() {
+                    let item = list.get_item_mut(number)?;
+                    let replaced = replacer
+                        .replace(&item.name)
+                        .with_context(|| format!("Nothing was replaced in '{name}'"))?;
+                    if replaced != item.name {
+                        println!("{name}:{number}  {} -> {replaced}", item.name);
+                        item.name = replaced;
+                        changes += 1;
+                        changed = true;
+                    }
+                }
+                if changed {
+                    changed_lists.push((name, path, list));
+                }
+            }
+            if changes == 0 {
+                println!("No items contain '{old}'");
+            } else if dry_run {
+                println!("{changes} item(s) would change");
+            } else {
+                for (_, path, _) in &changed_lists {
+                    writer.ensure_writable(path)?;
+                }
+                for (name, path, mut list) in changed_lists {
+                    backup::backup_list(&config, &name, &path)?;
+                    writer
+                        .write(
+                            &mut list,
+                            &path,
+                            &format!("replace \"{old}\" with \"{new}\""),
+                        )
+                        .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+                }
+                println!("Changed {changes} item(s)");
+            }
+        }
+        Commands::Journal => {
+            let (_, path) = journal::open_today(&writer)?;
+            let query = ListFilter::default().query(&config, *config.show_done_by_default())?;
+            println!("{}", render_list(&config, &path, &query, &display_options)?);
+        }
+        Commands::Review => review::run(&writer, &list_name, &list_path, &display_options)?,
+        Commands::Digest { since, html } => {
+            let digest = digest::Digest::collect(&config, since)?;
+            if html {
+                print!("{}", digest.to_html());
+            } else {
+                println!("{}", digest.to_text());
+            }
+        }
+        Commands::DoneLog { since, by_tag } => {
+            let lists = if cli.list.is_some() {
+                vec![(list_name, list_path)]
+            } else {
+                visible_lists(&config)?
+            };
+            println!("{}", done_log::render(&lists, since, by_tag)?);
+        }
+        Commands::Standup { markdown, slack } => {
+            let flavor = if markdown {
+                standup::Flavor::Markdown
+            } else if slack {
+                standup::Flavor::Slack
+            } else {
+                standup::Flavor::Plain
+            };
+            print!("{}", standup::report(&config, flavor)?);
+        }
+        Commands::Daemon { install: true } => daemon::install(&config)?,
+        Commands::Daemon { install: false } => daemon::run(&config)?,
+        Commands::Notify => remind::notify_due(&config)?,
+        Commands::Remind { scheduler, install } => remind::run(&config, scheduler, install)?,
+        Commands::Doctor => unreachable!("handled before reading the config"),
+        Commands::Config { action } => match action {
+            ConfigAction::Get { key } => {
+                let value = match key {
+                    Some(key) => config.get(&key)?,
+                    None => serde_json::to_value(&config)?,
+                };
+                match value {
+                    serde_json::Value::String(s) => println!("{s}"),
+                    value => println!("{}", serde_json::to_string_pretty(&value)?),
+                }
+            }
+            ConfigAction::Set { key, value } => {
+                let value =
+                    serde_json::from_str(&value).unwrap_or(serde_json::Value::String(value));
+                config.edit(|c| config::set_key(c, &config::key_path(c, &key)?, value))?;
+            }
+            ConfigAction::Unset { key } => {
+                config.edit(|c| config::unset_key(c, &config::key_path(c, &key)?))?;
+            }
+            ConfigAction::AddList { name, path } => {
+                let path = std::path::absolute(&path)
+                    .with_context(|| format!("Invalid path '{}'", path.display()))?;
+                config.register_list(&name, &path)?;
+                println!("Added list '{name}' at '{}'", path.display());
+            }
+            ConfigAction::RemoveList { name } => {
+                config.edit(|c| config::unset_key(c, &["lists", &name]))?;
+                println!("Removed list '{name}'");
+            }
+        },
+        Commands::OpenUrl { item_number } => {
+            let list = TodoList::from_file(&list_path)?;
+            let item_number = item_number.number_in(&list)?;
+            let item = list.get_item(item_number)?;
+            let url = item
+                .urls()
+                .first()
+                .copied()
+                .with_context(|| format!("Item {item_number} doesn't have a URL"))?;
+            open_in_browser(url)?;
+        }
+    }
+    Ok(())
+}
+
+/// Picks the list when `--list` is not passed
+const LIST_VAR: &str = "TODO_LIST";
+
+/// The list commands work on: the one passed with `--list` or set in `TODO_LIST`, else the
+/// TODO.md in the current directory (unless `detect_cwd_list` is off), else today's journal
+/// (if `journal_by_default` is on and it exists), else the general list
+fn current_list(config: &Config, list: Option<&str>) -> Result<(String, PathBuf)> {
+    let list = list
+        .map(str::to_string)
+        .or_else(|| env::var(LIST_VAR).ok().filter(|l| !l.is_empty()));
+    if let Some(name) = list {
+        let name = resolve_list_name(config, &name)?;
+        let path = config.list_path(&name);
+        return Ok((name, path));
+    }
+    if *config.detect_cwd_list() {
+        if let Some(cwd_list) = env::current_dir()
+            .ok()
+            .map(|cwd| cwd.join("TODO.md"))
+            .filter(|path| path.is_file())
+        {
+            let name = config
+                .lists()
+                .iter()
+                .find_map(|(name, list)| (list.path() == Some(&cwd_list)).then(|| name.clone()))
+                .unwrap_or_else(|| "TODO".to_string());
+            return Ok((name, cwd_list));
+        }
+    }
+    if *config.journal_by_default() {
+        let name = journal::list_name(Date::today());
+        let path = config.list_path(&name);
+        if path.exists() {
+            return Ok((name, path));
+        }
+    }
+    let name = config.general_list().clone();
+    let path = config.list_path(&name);
+    Ok((name, path))
+}
+
+/// Removes all but the first item of each group of duplicates, returning the removed items. The
+/// first one is marked done if any of its copies is
+fn remove_duplicates(list: &mut TodoList, groups: &[Vec<usize>]) -> Result<Vec<TodoItem>> {
+    for group in groups {
+        let done_copy = group
+            .iter()
+            .filter_map(|&number| list.get_item(number).ok())
+            .find(|item| item.is_done());
+        // keep the day the copy was done
+        let done_date = done_copy.map(|item| item.done_date());
+        let kept = list.get_item_mut(group[0])?;
+        if let (Some(done_date), false) = (done_date, kept.is_done()) {
+            kept.mark_done();
+            if let Some(date) = done_date {
+                kept.set_field("done", &date.to_string());
+            }
+        }
+    }
+    Ok(list.delete_items(groups.iter().flat_map(|g| g[1..].to_vec()).collect())?)
+}
+
+/// Names and paths of the lists that aren't hidden
+fn visible_lists(config: &Config) -> Result<Vec<(String, PathBuf)>> {
+    Ok(config
+        .list_names()?
+        .into_iter()
+        .filter(|name| !config.is_hidden(name))
+        .map(|name| {
+            let path = config.list_path(&name);
+            (name, path)
+        })
+        .collect())
+}
 
-            to_list.write(&to_list_path).with_context(|| {
-                "Couldn't write to destination list. Items not added or removed"
+/// Numbers of the items of `list` matching `query`. Fails if there are none
+fn matching_items(list: &TodoList, query: &str) -> Result<Vec<usize>> {
+    let numbers = list.filter(&parse_query(query)?);
+    if numbers.is_empty() {
+        bail!("No items match '{query}'");
+    }
+    Ok(numbers)
+}
+
+/// Number of the item of `list` whose title contains `text`, ignoring case and punctuation. If
+/// none does, titles with the characters of `text` in order match too. The user picks one if
+/// several match. With `open_only`, done items are left out
+fn match_item(
+    list: &TodoList,
+    text: &str,
+    open_only: bool,
+    options: &DisplayOptions,
+) -> Result<usize> {
+    let matches = list
+        .find_by_title(text)
+        .into_iter()
+        .filter_map(|number| Some((number, list.get_item(number).ok()?)))
+        .filter(|(_, item)| !open_only || !item.is_done())
+        .collect::<Vec<_>>();
+    match matches.as_slice() {
+        [] => bail!("No items match '{text}'"),
+        [(number, _)] => Ok(*number),
+        matches => {
+            let highest = matches.iter().map(|(number, _)| *number).max();
+            let options = options.fitting_numbers(highest.unwrap_or(0));
+            for (number, item) in matches {
+                println!("{}", item.render_numbered(*number, &options));
+            }
+            print!("{} items match '{text}'. Which one? ", matches.len());
+            io::stdout().flush()?;
+            let mut answer = String::new();
+            io::stdin()
+                .read_line(&mut answer)
+                .context("Failed to read user input")?;
+            let number = answer
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|n| matches.iter().any(|(number, _)| number == n));
+            number.with_context(|| format!("'{}' is not one of the matching items", answer.trim()))
+        }
+    }
+}
+
+fn parse_query(query: &str) -> Result<Query> {
+    query
+        .parse()
+        .with_context(|| format!("Couldn't understand the query '{query}'"))
+}
+
+/// An item shown away from its own list, like in a smart list or the list including its list
+struct SourcedItem {
+    list_name: String,
+    list_path: PathBuf,
+    /// Number of the item in its own list
+    item_number: usize,
+    item: TodoItem,
+}
+
+/// The open items of the lists that aren't hidden, labeled `<list>:<number>`
+fn open_items_of_all_lists(config: &Config) -> Result<Vec<(String, TodoItem)>> {
+    let mut rows = vec![];
+    let mut index = ListIndex::load(config);
+    for name in config.list_names()? {
+        if config.is_hidden(&name) {
+            continue;
+        }
+        rows.extend(
+            index
+                .items(&config.list_path(&name))?
+                .into_iter()
+                .enumerate()
+                .filter(|(_, item)| !item.is_done())
+                .map(|(i, item)| (format!("{name}:{}", i + 1), item)),
+        );
+    }
+    index.save()?;
+    Ok(rows)
+}
+
+/// Prints `rows` with their labels right-aligned
+fn print_labeled(rows: &[(String, TodoItem)], display_options: &DisplayOptions) {
+    let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    for (label, item) in rows {
+        println!(
+            "{}",
+            item.render_labeled(&format!("{label: >width$}"), display_options)
+        );
+    }
+}
+
+/// Items of all visible lists matching the query of the smart list `name`
+fn smart_list_items(config: &Config, name: &str) -> Result<Vec<SourcedItem>> {
+    let query = config.smart_lists()[name]
+        .parse::<Query>()
+        .with_context(|| format!("The query of the smart list '{name}' is invalid"))?;
+    let mut items = vec![];
+    let mut index = ListIndex::load(config);
+    for list_name in config.list_names()? {
+        if config.is_hidden(&list_name) {
+            continue;
+        }
+        let list_path = config.list_path(&list_name);
+        items.extend(
+            index
+                .items(&list_path)?
+                .into_iter()
+                .enumerate()
+                .filter(|(_, item)| query.matches(item))
+                .map(|(i, item)| SourcedItem {
+                    list_name: list_name.clone(),
+                    list_path: list_path.clone(),
+                    item_number: i + 1,
+                    item,
+                }),
+        );
+    }
+    index.save()?;
+    Ok(items)
+}
+
+/// Runs `command` on the smart list `name`. Smart lists can only be shown, and their items
+/// marked done in the lists they come from
+fn run_smart_list(
+    config: &Config,
+    name: &str,
+    command: Commands,
+    writer: &ListWriter,
+    display_options: &DisplayOptions,
+) -> Result<()> {
+    let items = smart_list_items(config, name)?;
+    match command {
+        Commands::List { filter, watch } => {
+            if watch {
+                bail!("--watch doesn't work with smart lists");
+            }
+            let query = filter.query(config, *config.show_done_by_default())?;
+            println!(
+                "{}",
+                display_sourced_items(&items, 1, &query, display_options)
+            );
+        }
+        Commands::Done {
+            item_numbers,
+            query,
+            title_match,
+            last,
+            all,
+            ..
+        } => {
+            if title_match.is_some() || last || all {
+                bail!("--match, --last and --all don't work with smart lists");
+            }
+            let (item_keys, elsewhere) = split_item_refs(config, item_numbers)?;
+            let item_numbers = match query {
+                Some(query) => {
+                    let query = parse_query(&query)?;
+                    (1..=items.len())
+                        .filter(|&n| query.matches(&items[n - 1].item))
+                        .collect()
+                }
+                None => {
+                    let mut list = TodoList::new(name);
+                    list.add_items(items.iter().map(|sourced| sourced.item.clone()).collect());
+                    numbers_in(&list, &item_keys)?
+                }
+            };
+            let selected = item_numbers
+                .into_iter()
+                .map(|number| {
+                    number
+                        .checked_sub(1)
+                        .and_then(|i| items.get(i))
+                        .ok_or(TodoError::InvalidItemNumber(number))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut done_items = mark_done_at_source(writer, &selected)?;
+            done_items.extend(mark_done_at_source(
+                writer,
+                &elsewhere.iter().collect::<Vec<_>>(),
+            )?);
+            println!(
+                "Marked item(s) done.\n{}",
+                done_items
+                    .iter()
+                    .map(|i| i.render(display_options))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            );
+        }
+        _ => bail!("'{name}' is a smart list. Only `list` and `done` work on it"),
+    }
+    Ok(())
+}
+
+/// Shows `items` numbered from `first_number` under the names of their lists, leaving out
+/// the ones not matching `query`
+fn display_sourced_items(
+    items: &[SourcedItem],
+    first_number: usize,
+    query: &Query,
+    display_options: &DisplayOptions,
+) -> String {
+    let highest = (0..items.len())
+        .rfind(|&i| query.matches(&items[i].item))
+        .map_or(0, |i| first_number + i);
+    let display_options = &display_options.fitting_numbers(highest);
+    let mut lines = vec![];
+    let mut shown_list = None;
+    for (i, sourced) in items.iter().enumerate() {
+        if !query.matches(&sourced.item) {
+            continue;
+        }
+        if shown_list != Some(&sourced.list_name) {
+            lines.push(sourced.list_name.clone());
+            shown_list = Some(&sourced.list_name);
+        }
+        lines.push(
+            sourced
+                .item
+                .render_numbered(first_number + i, display_options),
+        );
+    }
+    lines.join("\n")
+}
+
+/// The list at `path` as shown by `list`, with the items of the lists it includes
+fn render_list(
+    config: &Config,
+    path: &Path,
+    query: &Query,
+    display_options: &DisplayOptions,
+) -> Result<String> {
+    let list = TodoList::from_file(path)?;
+    let mut output = vec![];
+    if let Some(frontmatter) = list.frontmatter() {
+        if let Some(title) = &frontmatter.title {
+            let style = Style {
+                bold: true,
+                ..list_style(&frontmatter)
+            };
+            output.push(display_options.paint(&style, title));
+        }
+    }
+    if *config.show_progress() && !list.is_empty() {
+        output.push(list.progress_bar(display_options));
+    }
+    let included = included_items(path, &list)?;
+    // the items of included lists are numbered after the list's own, so they share the width
+    let highest = included
+        .iter()
+        .rposition(|sourced| query.matches(&sourced.item))
+        .map(|i| list.len() + 1 + i)
+        .or_else(|| {
+            list.numbered_items()
+                .filter(|(_, item)| query.matches(item))
+                .map(|(number, _)| number)
+                .last()
+        });
+    let display_options = &display_options.fitting_numbers(highest.unwrap_or(0));
+    output.push(list.display_with_numbers(|&(_, i)| query.matches(i), display_options));
+    if !included.is_empty() {
+        output.push(display_sourced_items(
+            &included,
+            list.len() + 1,
+            query,
+            display_options,
+        ));
+    }
+    Ok(output.join("\n"))
+}
+
+/// Style the name of a list is shown in, with the color of its frontmatter
+fn list_style(frontmatter: &Frontmatter) -> Style {
+    frontmatter.color.map(Style::fg).unwrap_or_default()
+}
+
+/// How often `list --watch` checks for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Clears the terminal and shows what `render` returns every time it changes, until the
+/// process is stopped
+fn watch_output(render: impl Fn() -> Result<String>) -> ! {
+    let mut shown = None;
+    loop {
+        // the file may be half written by an editor, the next check will show it
+        let output = render().unwrap_or_else(|e| format!("{e:#}"));
+        if shown.as_ref() != Some(&output) {
+            println!("\x1b[2J\x1b[H{output}");
+            let _ = io::stdout().flush();
+            shown = Some(output);
+        }
+        thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Marks `items` done in the lists they come from
+fn mark_done_at_source(writer: &ListWriter, items: &[&SourcedItem]) -> Result<Vec<TodoItem>> {
+    let mut by_list = BTreeMap::<&Path, Vec<&SourcedItem>>::new();
+    for sourced in items {
+        by_list.entry(&sourced.list_path).or_default().push(sourced);
+    }
+    let mut done_items = vec![];
+    for (path, sourced_items) in by_list {
+        let mut list = TodoList::from_file(path)?;
+        let marked = sourced_items
+            .iter()
+            .map(|i| list.mark_item_done(i.item_number).cloned())
+            .collect::<Result<Vec<_>, _>>()?;
+        writer
+            .write(&mut list, path, &format!("done {}", quoted_titles(&marked)))
+            .with_context(|| {
+                format!(
+                    "Couldn't write to the list '{}'",
+                    sourced_items[0].list_name
+                )
             })?;
-            from_list.write(&list_path).with_context(|| "Couldn't write to source list. Items not removed from source list but written to destination list.")?;
+        hooks::item_event(
+            writer.config,
+            ItemEvent::Done,
+            &sourced_items[0].list_name,
+            &marked,
+        );
+        done_items.extend(marked);
+    }
+    Ok(done_items)
+}
+
+/// Deletes `items` from the lists they come from, backing the lists up first
+fn remove_at_source(
+    config: &Config,
+    writer: &ListWriter,
+    items: &[SourcedItem],
+) -> Result<Vec<TodoItem>> {
+    let mut by_list = BTreeMap::<&Path, Vec<&SourcedItem>>::new();
+    for sourced in items {
+        by_list.entry(&sourced.list_path).or_default().push(sourced);
+    }
+    let mut removed_items = vec![];
+    for (path, sourced_items) in by_list {
+        let list_name = &sourced_items[0].list_name;
+        let mut list = TodoList::from_file(path)?;
+        let removed = list.delete_items(sourced_items.iter().map(|i| i.item_number).collect())?;
+        backup::backup_list(config, list_name, path)?;
+        writer
+            .write(
+                &mut list,
+                path,
+                &format!("remove {}", quoted_titles(&removed)),
+            )
+            .with_context(|| format!("Couldn't write to the list '{list_name}'"))?;
+        hooks::item_event(config, ItemEvent::Removed, list_name, &removed);
+        removed_items.extend(removed);
+    }
+    Ok(removed_items)
+}
+
+/// Items of the lists `list` includes with `@include <path>` lines, and of the lists those
+/// include, in the order they are included
+fn included_items(path: &Path, list: &TodoList) -> Result<Vec<SourcedItem>> {
+    let mut items = vec![];
+    let mut seen = vec![fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())];
+    let mut pending = list
+        .includes()
+        .map(|include| resolve_include(path, include))
+        .collect::<Vec<_>>();
+    pending.reverse();
+    while let Some(include_path) = pending.pop() {
+        let canonical = fs::canonicalize(&include_path).unwrap_or_else(|_| include_path.clone());
+        if seen.contains(&canonical) {
+            continue;
+        }
+        seen.push(canonical);
+        let included = TodoList::from_file(&include_path).with_context(|| {
+            format!(
+                "Couldn't read the included list '{}'",
+                include_path.display()
+            )
+        })?;
+        let list_name = include_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        items.extend(
+            included
+                .numbered_items()
+                .map(|(item_number, item)| SourcedItem {
+                    list_name: list_name.clone(),
+                    list_path: include_path.clone(),
+                    item_number,
+                    item: item.clone(),
+                }),
+        );
+        let mut nested = included
+            .includes()
+            .map(|include| resolve_include(&include_path, include))
+            .collect::<Vec<_>>();
+        nested.reverse();
+        pending.extend(nested);
+    }
+    Ok(items)
+}
+
+/// Path of a list included by the list at `path`. Relative paths start from its directory
+fn resolve_include(path: &Path, include: &str) -> PathBuf {
+    path.parent().unwrap_or(Path::new("")).join(include)
+}
+
+/// Resolves aliases in a list name given by the user. If no list has that name, a similar
+/// list name is offered instead
+fn resolve_list_name(config: &Config, name: &str) -> Result<String> {
+    let name = match config.suggest_list_name(name) {
+        Some(suggestion) if confirm(&format!("There's no list '{name}'. Use '{suggestion}'?"))? => {
+            suggestion
+        }
+        _ => name.to_string(),
+    };
+    Ok(config.resolve_alias(&name).to_string())
+}
+
+/// Lists at least this big have single items marked done by rewriting only their lines
+const LARGE_LIST_BYTES: u64 = 1 << 20;
+
+/// Heading of the section done items are moved to with `move_done_items`
+const DONE_SECTION: &str = "Done";
+
+/// Writes lists following the config and the flags passed on the command line
+struct ListWriter<'a> {
+    config: &'a Config,
+    /// overwrite lists that changed on disk after they were read
+    force: bool,
+    /// change read-only lists
+    unlock: bool,
+}
+
+impl ListWriter<'_> {
+    /// Writes `list` to `path`. `change` describes what changed, for the commit message when
+    /// lists are committed to git
+    fn write(&self, list: &mut TodoList, path: &Path, change: &str) -> Result<()> {
+        self.ensure_writable(path)?;
+        if self.config.moves_done_items_at(path) {
+            list.move_done_items_to_section(DONE_SECTION);
+        }
+        self.config.dialect_at(path).apply(list);
+        self.config.line_endings().apply(list);
+        let write_hooks = self.config.write_hooks_at(path);
+        if let Some(command) = &write_hooks.pre_write {
+            if let Some(contents) = hooks::pre_write(command, path, &list.as_markdown())? {
+                list.set_markdown(&contents)
+                    .context("The pre-write hook printed an invalid list")?;
+            }
+        }
+        let result = if self.force {
+            list.overwrite(path)
+        } else {
+            list.write(path)
+        };
+        match result {
+            Err(e @ TodoError::ChangedOnDisk(_)) => {
+                return Err(e)
+                    .context("Run the command again, or pass --force to overwrite the changes")
+            }
+            result => result?,
+        }
+        self.written(path, change);
+        Ok(())
+    }
+
+    /// Commits the list written to `path` and runs the post-write hook, as configured
+    fn written(&self, path: &Path, change: &str) {
+        if *self.config.git_commit() {
+            if let Err(e) = git::commit_file(path, &format!("todo: {change}")) {
+                eprintln!("Couldn't commit the list: {e:#}");
+            }
+        }
+        if let Some(command) = &self.config.write_hooks_at(path).post_write {
+            if let Err(e) = hooks::post_write(command, path) {
+                eprintln!("{e:#}");
+            }
+        }
+    }
+
+    /// Marks the items `item_numbers` of the list at `path` done by rewriting only their lines,
+    /// if the list is large enough for that to matter. `None` if the list has to be written
+    /// whole instead, e.g. since a pre-write hook wants to see all of it
+    fn mark_done_in_place(
+        &self,
+        list_name: &str,
+        path: &Path,
+        item_numbers: &[usize],
+    ) -> Result<Option<Vec<TodoItem>>> {
+        let config = self.config;
+        let large = fs::metadata(path).is_ok_and(|m| m.len() >= LARGE_LIST_BYTES);
+        let archive = config
+            .lists()
+            .get(list_name)
+            .is_some_and(|l| *l.archive_when_done());
+        if !large
+            || archive
+            || config.moves_done_items_at(path)
+            || config.dialect_at(path) != Dialect::Plain
+            || item_numbers.is_empty()
+            || config.write_hooks_at(path).pre_write.is_some()
+            || !matches!(config.line_endings(), LineEndings::Preserve)
+        {
+            return Ok(None);
+        }
+        let mut list = IndexedList::open(path)?;
+        // the items after the list's own ones come from the lists it includes
+        if item_numbers.iter().any(|&number| number > list.len()) {
+            return Ok(None);
+        }
+        self.ensure_writable(path)?;
+        let done = item_numbers
+            .iter()
+            .map(|&number| list.update(number, TodoItem::mark_done))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.written(path, &format!("done {}", quoted_titles(&done)));
+        hooks::item_event(config, ItemEvent::Done, list_name, &done);
+        Ok(Some(done))
+    }
+
+    /// Fails if the list at `path` is read-only and `--unlock` wasn't passed
+    fn ensure_writable(&self, path: &Path) -> Result<()> {
+        if self.unlock {
+            return Ok(());
+        }
+        if let Some(name) = self.config.readonly_list_at(path) {
+            bail!("The list '{name}' is read-only. Pass --unlock to change it anyway")
+        }
+        if Frontmatter::read(path)
+            .ok()
+            .flatten()
+            .is_some_and(|f| f.readonly)
+        {
+            bail!(
+                "'{}' is read-only in its frontmatter. Pass --unlock to change it anyway",
+                path.display()
+            )
+        }
+        Ok(())
+    }
+}
+
+/// Titles of `items` in quotes, separated by commas
+fn quoted_titles(items: &[TodoItem]) -> String {
+    items
+        .iter()
+        .map(|i| format!("\"{}\"", i.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Asks the user a yes/no question, defaulting to no
+fn confirm(question: &str) -> Result<bool> {
+    print!("{question} [y/N] ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read user input")?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Lines of the editor buffer starting with this are left out of the item
+const EDITOR_COMMENT: &str = "# ";
+
+/// Lets the user write an item in their editor, starting with `title`. Returns the first line as
+/// the title and the lines after it as the description
+fn compose_item(title: &str) -> Result<(String, Option<String>)> {
+    let path = env::temp_dir().join(format!("todo-item.{}.md", std::process::id()));
+    fs::write(
+        &path,
+        format!(
+            "{title}\n\n{EDITOR_COMMENT}The first line is the title, the lines below it the description.\n\
+             {EDITOR_COMMENT}Lines starting with '{EDITOR_COMMENT}' are left out. An empty title cancels.\n"
+        ),
+    )
+    .context("Couldn't create a file to edit the item in")?;
+    let edited = run_editor(&path)
+        .and_then(|_| fs::read_to_string(&path).context("Couldn't read the edited item"));
+    let _ = fs::remove_file(&path);
+    let edited = edited?;
+
+    let text = edited
+        .lines()
+        .filter(|line| !line.starts_with(EDITOR_COMMENT))
+        .collect::<Vec<_>>()
+        .join("\n");
+    item_from_text(&text).context("The item has no title. Nothing added")
+}
+
+/// The first line of `text` that isn't empty as a title, and the lines after it as a
+/// description. `None` if `text` is blank
+fn item_from_text(text: &str) -> Option<(String, Option<String>)> {
+    let mut lines = text.lines().map(str::trim);
+    let title = lines.by_ref().find(|line| !line.is_empty())?;
+    // descriptions are the indented lines under an item, and end at an empty line
+    let description = lines
+        .filter(|line| !line.is_empty())
+        .map(|line| format!("  {line}"))
+        .collect::<Vec<_>>();
+    Some((
+        title.to_string(),
+        (!description.is_empty()).then(|| description.join("\n")),
+    ))
+}
+
+/// Text on the system clipboard, read with the clipboard tool of the platform
+fn read_clipboard() -> Result<String> {
+    let tools: &[&[&str]] = if cfg!(target_os = "windows") {
+        &[&["powershell", "-NoProfile", "-Command", "Get-Clipboard"]]
+    } else if cfg!(target_os = "macos") {
+        &[&["pbpaste"]]
+    } else {
+        &[
+            &["wl-paste", "--no-newline"],
+            &["xclip", "-selection", "clipboard", "-out"],
+            &["xsel", "--clipboard", "--output"],
+        ]
+    };
+    for tool in tools {
+        let Ok(output) = Command::new(tool[0]).args(&tool[1..]).output() else {
+            continue;
+        };
+        if output.status.success() {
+            return String::from_utf8(output.stdout).context("The clipboard doesn't hold text");
+        }
+    }
+    let names = tools.iter().map(|tool| tool[0]).collect::<Vec<_>>();
+    bail!(
+        "Couldn't read the clipboard. Is {} installed?",
+        names.join(" or ")
+    )
+}
+
+/// Adds an item titled `title` to the list, creating the list if needed. `edit` can change the
+/// item before it's written. The hooks are told about it
+fn add_item<F>(
+    writer: &ListWriter,
+    list_name: &str,
+    list_path: &Path,
+    title: &str,
+    edit: F,
+) -> Result<TodoItem>
+where
+    F: FnOnce(&mut TodoItem),
+{
+    let config = writer.config;
+    let mut list = match TodoList::from_file(list_path) {
+        Ok(list) => list,
+        Err(TodoError::FileIOError(_)) => {
+            let mut list = TodoList::new(list_name);
+            add_seed_items(config, &mut list);
+            list
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let tags = list.frontmatter().map(|f| f.tags).unwrap_or_default();
+    list.add_item(title);
+    let added = list.get_item_mut(list.len())?;
+    for tag in &tags {
+        added.add_tag(tag);
+    }
+    edit(added);
+    let added = added.clone();
+    writer
+        .write(&mut list, list_path, &format!("add \"{title}\""))
+        .with_context(|| "Couldn't write the list")?;
+    hooks::item_event(
+        config,
+        ItemEvent::Added,
+        list_name,
+        std::slice::from_ref(&added),
+    );
+    Ok(added)
+}
+
+/// Opens `path` in `$VISUAL` or `$EDITOR`, waiting for the editor to close
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| {
+            if cfg!(target_os = "windows") {
+                "notepad"
+            } else {
+                "vi"
+            }
+            .to_string()
+        });
+    // editors are often given with arguments, like `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .with_context(|| format!("Couldn't run the editor '{editor}'"))?;
+    if !status.success() {
+        bail!("The editor '{editor}' exited with {status}");
+    }
+    Ok(())
+}
+
+/// Writes a new list at `path` with the items of `template`, or just a heading if there's none
+fn create_list(writer: &ListWriter, path: &Path, name: &str, template: Option<&str>) -> Result<()> {
+    let config = writer.config;
+    let mut list = match template {
+        Some(template) => {
+            let mut list = TodoList::from_file(&config.template_path(template))
+                .with_context(|| format!("Couldn't read the template '{template}'"))?;
+            list.reset();
+            list
+        }
+        None => {
+            let mut list = TodoList::new(name);
+            list.add_line(&format!("# {name}"));
+            list
+        }
+    };
+    list.name = name.to_string();
+    add_seed_items(config, &mut list);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Couldn't create the directory '{}'", dir.display()))?;
+    }
+    writer
+        .write(&mut list, path, &format!("create list {name}"))
+        .with_context(|| format!("Couldn't create '{}'", path.display()))
+}
+
+/// Adds the `seed_items` of the config to a new list, unless it has them already
+fn add_seed_items(config: &Config, list: &mut TodoList) {
+    for title in config.seed_items() {
+        if !list.numbered_items().any(|(_, item)| &item.name == title) {
+            list.add_item(title);
+        }
+    }
+}
+
+/// Moves the list `name` at `path` to the archive directory, or to the `archive` of its
+/// frontmatter, returning its new path
+fn archive_list(config: &Config, name: &str, path: &Path) -> Result<PathBuf> {
+    let target = Frontmatter::read(path)?.and_then(|f| f.archive);
+    let archived = match target {
+        Some(target) => {
+            let target = path.parent().unwrap_or(Path::new(".")).join(target);
+            if target.is_dir() || target.as_os_str().to_string_lossy().ends_with('/') {
+                config::free_path(&target, name)
+            } else if target.exists() {
+                bail!(
+                    "Couldn't archive to '{}', it exists already",
+                    target.display()
+                );
+            } else {
+                target
+            }
         }
+        None => config.archive_path(name),
+    };
+    if let Some(dir) = archived.parent() {
+        fs::create_dir_all(dir).context("Couldn't create the archive directory")?;
+    }
+    fs::rename(path, &archived)
+        .with_context(|| format!("Couldn't archive '{}'", path.display()))?;
+    Ok(archived)
+}
+
+fn open_in_browser(url: &str) -> Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    let status = command
+        .arg(url)
+        .status()
+        .with_context(|| format!("Couldn't open '{url}'"))?;
+    if !status.success() {
+        bail!("Couldn't open '{url}'");
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_filters(filters: serde_json::Value) -> Config {
+        serde_json::from_value(serde_json::json!({ "main_dir": "/tmp", "filters": filters }))
+            .unwrap()
+    }
+
+    fn item(line: &str) -> TodoItem {
+        line.parse().unwrap()
+    }
+
+    #[test]
+    fn saved_filters() {
+        let config = config_with_filters(serde_json::json!({
+            "today": "due<=today OR #pinned",
+            "broken": "due<",
+        }));
+        let filter = |name: &str| ListFilter {
+            filter: Some(name.to_string()),
+            ..Default::default()
+        };
+        let today = filter("today").query(&config, false).unwrap();
+        assert!(today.matches(&item(&format!("- [ ] a due:{}", Date::today()))));
+        assert!(today.matches(&item("- [ ] b #pinned")));
+        assert!(!today.matches(&item("- [ ] c due:2999-01-01")));
+        // the filter decides whether done items are shown
+        assert!(today.matches(&item("- [x] d #pinned")));
+
+        let narrowed = ListFilter {
+            tag: vec!["work".to_string()],
+            ..filter("today")
+        };
+        let narrowed = narrowed.query(&config, false).unwrap();
+        assert!(narrowed.matches(&item("- [ ] e #pinned #work")));
+        assert!(!narrowed.matches(&item("- [ ] f #pinned")));
+
+        assert!(filter("broken").query(&config, false).is_err());
+        let error = filter("missing").query(&config, false).unwrap_err();
+        assert!(
+            error.to_string().contains("The filters are: broken, today"),
+            "{error}"
+        );
+    }
+}
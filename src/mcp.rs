@@ -0,0 +1,212 @@
+//! A Model Context Protocol server over stdio, so assistants can read and update lists through
+//! tool calls. Messages are JSON-RPC, one per line
+
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use todo::{text::single_line, TodoList};
+
+use crate::{
+    add_seed_items,
+    hooks::{self, ItemEvent},
+    serve::{item_json, read_list},
+    ListWriter,
+};
+
+const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// Answers requests on stdin until it's closed. Tools use the list `default_list` at
+/// `default_path` when no list is given
+pub fn run(writer: &ListWriter, default_list: &str, default_path: &Path) -> Result<()> {
+    let server = Server {
+        writer,
+        default_list,
+        default_path,
+    };
+    let mut stdout = io::stdout().lock();
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(&message),
+            Err(e) => Some(error_response(Value::Null, -32700, &e.to_string())),
+        };
+        if let Some(response) = response {
+            writeln!(stdout, "{response}")?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+struct Server<'a> {
+    writer: &'a ListWriter<'a>,
+    default_list: &'a str,
+    default_path: &'a Path,
+}
+
+impl Server<'_> {
+    /// The response to `message`, if it's a request rather than a notification
+    fn handle(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id")?.clone();
+        let params = &message["params"];
+        let result = match message["method"].as_str().unwrap_or_default() {
+            "initialize" => json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "todo", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tools() }),
+            "tools/call" => {
+                let arguments = &params["arguments"];
+                let name = params["name"].as_str().unwrap_or_default();
+                match self.call(name, arguments) {
+                    Ok(text) => json!({ "content": [{ "type": "text", "text": text }] }),
+                    // tool errors are reported to the assistant rather than as protocol errors
+                    Err(e) => json!({
+                        "content": [{ "type": "text", "text": format!("{e:#}") }],
+                        "isError": true,
+                    }),
+                }
+            }
+            method => {
+                return Some(error_response(
+                    id,
+                    -32601,
+                    &format!("Unknown method '{method}'"),
+                ))
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    fn call(&self, tool: &str, arguments: &Value) -> Result<String> {
+        let config = self.writer.config;
+        if tool == "list_lists" {
+            let names = config
+                .list_names()?
+                .into_iter()
+                .filter(|name| !config.is_hidden(name))
+                .collect::<Vec<_>>();
+            return Ok(json!(names).to_string());
+        }
+
+        let (name, path, mut list) = match arguments["list"].as_str() {
+            Some(name) => {
+                let (path, list) = read_list(config, name)?
+                    .with_context(|| format!("There's no list '{name}'"))?;
+                (name, path, list)
+            }
+            None if self.default_path.exists() => (
+                self.default_list,
+                self.default_path.to_path_buf(),
+                TodoList::from_file(self.default_path)?,
+            ),
+            None => {
+                let mut list = TodoList::new(self.default_list);
+                add_seed_items(config, &mut list);
+                (self.default_list, self.default_path.to_path_buf(), list)
+            }
+        };
+        match tool {
+            "list_items" => {
+                let include_done = arguments["include_done"].as_bool().unwrap_or(false);
+                let items = list
+                    .numbered_items()
+                    .filter(|(_, item)| include_done || !item.is_done())
+                    .map(|(number, item)| item_json(number, item))
+                    .collect::<Vec<_>>();
+                Ok(json!({ "list": name, "items": items }).to_string())
+            }
+            "add_item" => {
+                let Some(title) = arguments["title"]
+                    .as_str()
+                    .map(single_line)
+                    .filter(|t| !t.is_empty())
+                else {
+                    bail!("A title is needed");
+                };
+                list.add_item(&title);
+                let number = list.len();
+                let added = list.get_item(number)?.clone();
+                self.writer
+                    .write(&mut list, &path, &format!("add \"{title}\""))?;
+                hooks::item_event(config, ItemEvent::Added, name, &[added]);
+                Ok(format!("Added item {number} to '{name}'"))
+            }
+            "complete_item" => {
+                let number = arguments["number"]
+                    .as_u64()
+                    .context("An item number is needed")? as usize;
+                let item = list.mark_item_done(number)?.clone();
+                let title = &item.name;
+                self.writer
+                    .write(&mut list, &path, &format!("done \"{title}\""))?;
+                hooks::item_event(config, ItemEvent::Done, name, std::slice::from_ref(&item));
+                Ok(format!("Marked item {number} \"{title}\" as done"))
+            }
+            _ => bail!("Unknown tool '{tool}'"),
+        }
+    }
+}
+
+fn error_response(id: Value, code: i32, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn tools() -> Value {
+    let list = json!({
+        "type": "string",
+        "description": "Name of the list. The project's list is used if not given",
+    });
+    json!([
+        {
+            "name": "list_lists",
+            "description": "Names of the todo lists",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+        {
+            "name": "list_items",
+            "description": "The items of a todo list, with the numbers to refer to them by",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "list": list,
+                    "include_done": { "type": "boolean", "description": "Include done items" },
+                },
+            },
+        },
+        {
+            "name": "add_item",
+            "description": "Add an item to a todo list",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "list": list, "title": { "type": "string" } },
+                "required": ["title"],
+            },
+        },
+        {
+            "name": "complete_item",
+            "description": "Mark an item of a todo list as done",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "list": list,
+                    "number": { "type": "integer", "description": "The item's number" },
+                },
+                "required": ["number"],
+            },
+        },
+    ])
+}
@@ -0,0 +1,282 @@
+//! Reading the exports of Microsoft To Do: the JSON of its lists and tasks, as the Graph API
+//! returns them, and the CSV of tasks Outlook exports
+
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use todo::{date::Date, text, TodoItem};
+
+/// A list of the export, with the name its file gets and the items it has
+pub struct ImportedList {
+    pub name: String,
+    pub title: String,
+    pub items: Vec<TodoItem>,
+}
+
+/// A task of either kind of export
+struct Task {
+    list: String,
+    title: String,
+    done: bool,
+    done_on: Option<Date>,
+    due: Option<Date>,
+    notes: String,
+    categories: Vec<String>,
+    importance: String,
+    /// Titles of the steps and whether they are checked
+    steps: Vec<(String, bool)>,
+}
+
+/// List tasks are put in when the export doesn't say
+const DEFAULT_LIST: &str = "Tasks";
+
+/// The lists of the export at `path`, which is read as CSV if its name ends in `.csv` and as
+/// JSON otherwise
+pub fn read(path: &Path) -> Result<Vec<ImportedList>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Couldn't read '{}'", path.display()))?;
+    let contents = contents.trim_start_matches('\u{feff}');
+    let is_csv = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+    let tasks = if is_csv {
+        csv_tasks(contents)?
+    } else {
+        let json = serde_json::from_str(contents)
+            .context("The export isn't valid JSON. Pass a file ending in .csv for CSV exports")?;
+        json_tasks(&json)?
+    };
+    let mut lists: Vec<ImportedList> = vec![];
+    for task in tasks {
+        let items = task_items(&task)?;
+        match lists.iter_mut().find(|list| list.title == task.list) {
+            Some(list) => list.items.extend(items),
+            None => lists.push(ImportedList {
+                name: list_name(&task.list),
+                title: task.list,
+                items,
+            }),
+        }
+    }
+    Ok(lists)
+}
+
+/// The name of the file of the list `title`, e.g. `work-projects` for "Work Projects"
+fn list_name(title: &str) -> String {
+    let name = text::normalize(title).replace(' ', "-");
+    if name.is_empty() {
+        DEFAULT_LIST.to_lowercase()
+    } else {
+        name
+    }
+}
+
+/// The item of `task` followed by its steps, indented under it
+fn task_items(task: &Task) -> Result<Vec<TodoItem>> {
+    let mut builder = TodoItem::builder()
+        .name(&task.title)
+        .description(&task.notes)
+        .done(task.done);
+    for category in &task.categories {
+        builder = builder.tag(category);
+    }
+    let mut item = builder
+        .build()
+        .with_context(|| format!("A task of the list '{}' has no title", task.list))?;
+    if let Some(due) = task.due {
+        item.set_field("due", &due.to_string());
+    }
+    if let Some(done_on) = task.done_on.filter(|_| task.done) {
+        item.set_field("done", &done_on.to_string());
+    }
+    if matches!(task.importance.as_str(), "high" | "low") {
+        item.set_field("priority", &task.importance);
+    }
+    let mut items = vec![item];
+    for (step, checked) in &task.steps {
+        if step.trim().is_empty() {
+            continue;
+        }
+        let mark = if *checked { 'x' } else { ' ' };
+        items.push(format!("  - [{mark}] {}", step.trim()).parse()?);
+    }
+    Ok(items)
+}
+
+/// A date like `2025-07-01T00:00:00.0000000`, `2025-07-01` or the `7/1/2025` of Outlook
+fn parse_date(s: &str) -> Option<Date> {
+    let s = s.trim();
+    if let Some(date) = s.get(..10).and_then(|date| date.parse().ok()) {
+        return Some(date);
+    }
+    let date = s.split_whitespace().next()?;
+    let [month, day, year] = date.split('/').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let number = |s: &str| s.parse::<u32>().ok();
+    format!(
+        "{:04}-{:02}-{:02}",
+        number(year)?,
+        number(month)?,
+        number(day)?
+    )
+    .parse()
+    .ok()
+}
+
+/// The tasks of a JSON export: an array of lists, or an object with them under `lists` or
+/// `value`. Lists have their tasks under `tasks`
+fn json_tasks(json: &Value) -> Result<Vec<Task>> {
+    let lists = match json {
+        Value::Array(lists) => lists,
+        Value::Object(object) => match object.get("lists").or_else(|| object.get("value")) {
+            Some(Value::Array(lists)) => lists,
+            _ => bail!("Expected the lists of the export under 'lists' or 'value'"),
+        },
+        _ => bail!("Expected the lists of the export as an array"),
+    };
+    let mut tasks = vec![];
+    for list in lists {
+        let list_title = list["displayName"]
+            .as_str()
+            .or_else(|| list["name"].as_str())
+            .unwrap_or(DEFAULT_LIST);
+        let list_tasks = match &list["tasks"] {
+            Value::Object(page) => &page["value"],
+            list_tasks => list_tasks,
+        };
+        for task in list_tasks.as_array().into_iter().flatten() {
+            tasks.push(Task {
+                list: list_title.to_string(),
+                title: task["title"].as_str().unwrap_or_default().to_string(),
+                done: task["status"] == "completed",
+                done_on: inner_text(&task["completedDateTime"], "dateTime").and_then(parse_date),
+                due: inner_text(&task["dueDateTime"], "dateTime").and_then(parse_date),
+                notes: inner_text(&task["body"], "content")
+                    .unwrap_or_default()
+                    .to_string(),
+                categories: task["categories"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|category| category.as_str().map(str::to_string))
+                    .collect(),
+                importance: task["importance"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_lowercase(),
+                steps: task["checklistItems"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|step| {
+                        let title = step["displayName"].as_str()?;
+                        Some((title.to_string(), step["isChecked"] == true))
+                    })
+                    .collect(),
+            });
+        }
+    }
+    Ok(tasks)
+}
+
+/// `value` if it's a string, or its string under `key` like the `dateTime` of
+/// `{"dateTime": "…", "timeZone": "UTC"}`
+fn inner_text<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.as_str().or_else(|| value[key].as_str())
+}
+
+/// The tasks of a CSV export. Its columns are found by their headers, which are the ones
+/// Outlook writes, like `Subject`, `Due Date`, `Date Completed`, `Categories` and `Notes`. A
+/// `List` or `Folder` column says which list a task is in
+fn csv_tasks(csv: &str) -> Result<Vec<Task>> {
+    let mut rows = csv_rows(csv).into_iter();
+    let Some(headers) = rows.next() else {
+        bail!("The export is empty");
+    };
+    let column = |names: &[&str]| {
+        headers.iter().position(|header| {
+            names
+                .iter()
+                .any(|name| header.trim().eq_ignore_ascii_case(name))
+        })
+    };
+    let Some(title) = column(&["Subject", "Title", "Task", "Name"]) else {
+        bail!("The export has no Subject or Title column");
+    };
+    let list = column(&["List", "Folder", "List Name"]);
+    let status = column(&["Status", "Complete", "Completed"]);
+    let done_on = column(&["Date Completed", "Completed Date", "Completed Date Time"]);
+    let due = column(&["Due Date", "Due", "Due Date Time"]);
+    let notes = column(&["Notes", "Body", "Description"]);
+    let categories = column(&["Categories"]);
+    let importance = column(&["Priority", "Importance"]);
+
+    Ok(rows
+        .filter(|row| row.iter().any(|field| !field.trim().is_empty()))
+        .map(|row| {
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|column| row.get(column))
+                    .map_or("", |field| field.trim())
+            };
+            let done_on = parse_date(field(done_on));
+            let status = field(status).to_lowercase();
+            Task {
+                list: Some(field(list))
+                    .filter(|list| !list.is_empty())
+                    .unwrap_or(DEFAULT_LIST)
+                    .to_string(),
+                title: field(Some(title)).to_string(),
+                done: matches!(
+                    status.as_str(),
+                    "completed" | "complete" | "true" | "yes" | "1"
+                ) || done_on.is_some(),
+                done_on,
+                due: parse_date(field(due)),
+                notes: field(notes).to_string(),
+                categories: field(categories)
+                    .split([';', ','])
+                    .map(str::trim)
+                    .filter(|category| !category.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                importance: field(importance).to_lowercase(),
+                steps: vec![],
+            }
+        })
+        .collect())
+}
+
+/// The rows of `csv`. Quoted fields can have commas and line breaks, and `""` for a quote
+fn csv_rows(csv: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut field)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
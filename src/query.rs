@@ -0,0 +1,564 @@
+//! Queries like `state:open AND (tag:urgent OR due<=today) AND NOT report` that select items
+
+use std::{cmp::Ordering, str::FromStr};
+
+use crate::{
+    date::{parse_date_time, Date},
+    TodoError, TodoItem,
+};
+
+/// A parsed query. Adjacent terms must all match, as if joined by `AND`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query(Expr);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    /// `tag:work` matches items tagged `#work`
+    Tag(String),
+    /// `state:open` or `state:done`
+    State(bool),
+    /// `key:value` matches items with the word `key:value` in their title
+    Field(String, String),
+    /// `key<value` and the like compare the value of `key:…` in the title with `value`
+    Compare(String, Ordering, bool, String),
+    /// Anything else matches items whose title contains it
+    Text(String),
+}
+
+impl Query {
+    /// A query matching the items both `self` and `other` match
+    pub fn and(self, other: Query) -> Query {
+        Query(Expr::And(vec![self.0, other.0]))
+    }
+
+    /// A query matching the items all `queries` match. Matches every item if there are none
+    pub fn all(queries: impl IntoIterator<Item = Query>) -> Query {
+        Query(Expr::And(queries.into_iter().map(|q| q.0).collect()))
+    }
+
+    /// Items tagged `#tag`
+    pub fn tag(tag: &str) -> Query {
+        Query(Expr::Term(Term::Tag(
+            tag.trim_start_matches('#').to_string(),
+        )))
+    }
+
+    /// Items whose title contains `text`, ignoring case
+    pub fn text(text: &str) -> Query {
+        Query(Expr::Term(Term::Text(text.to_string())))
+    }
+
+    /// Done items if `done`, open ones otherwise
+    pub fn state(done: bool) -> Query {
+        Query(Expr::Term(Term::State(done)))
+    }
+
+    /// Items with the word `key:value` in their title
+    pub fn field(key: &str, value: &str) -> Query {
+        Query(Expr::Term(Term::Field(
+            key.to_string(),
+            resolve_value(value),
+        )))
+    }
+
+    /// Items whose `key:…` value comes before `value`
+    pub fn before(key: &str, value: &str) -> Query {
+        Query(Expr::Term(Term::Compare(
+            key.to_string(),
+            Ordering::Less,
+            false,
+            resolve_value(value),
+        )))
+    }
+
+    pub fn matches(&self, item: &TodoItem) -> bool {
+        self.0.matches(item)
+    }
+}
+
+impl Expr {
+    fn matches(&self, item: &TodoItem) -> bool {
+        match self {
+            Expr::Or(exprs) => exprs.iter().any(|e| e.matches(item)),
+            Expr::And(exprs) => exprs.iter().all(|e| e.matches(item)),
+            Expr::Not(expr) => !expr.matches(item),
+            Expr::Term(term) => term.matches(item),
+        }
+    }
+}
+
+impl Term {
+    fn matches(&self, item: &TodoItem) -> bool {
+        match self {
+            Term::Tag(tag) => item.has_tag(tag),
+            Term::State(done) => item.is_done() == *done,
+            Term::Field(key, value) => item
+                .field(key)
+                .is_some_and(|v| v.eq_ignore_ascii_case(value)),
+            Term::Compare(key, ordering, or_equal, value) => item.field(key).is_some_and(|v| {
+                let found = compare(v, value);
+                found == *ordering || *or_equal && found == Ordering::Equal
+            }),
+            Term::Text(text) => item.name.to_lowercase().contains(&text.to_lowercase()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word(String),
+    /// A quoted string, always matched as text
+    Quoted(String),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::Word(word) => write!(f, "'{word}'"),
+            Token::Quoted(text) => write!(f, "\"{text}\""),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, TodoError> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let quoted = chars.by_ref().take_while(|&c| c != '"').collect();
+                tokens.push(Token::Quoted(quoted));
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    if !s.matches('"').count().is_multiple_of(2) {
+        return Err(TodoError::InvalidQuery("Unclosed quote".to_string()));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser. `OR` binds loosest, then `AND` (or no operator), then `NOT`
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, TodoError> {
+        let mut exprs = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, TodoError> {
+        let mut exprs = vec![self.not()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Or | Token::Close) | None => break,
+                Some(_) => {}
+            }
+            exprs.push(self.not()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn not(&mut self) -> Result<Expr, TodoError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next();
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, TodoError> {
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(TodoError::InvalidQuery("Expected ')'".to_string())),
+                }
+            }
+            Some(Token::Word(word)) => Ok(Expr::Term(parse_term(&word)?)),
+            Some(Token::Quoted(text)) => Ok(Expr::Term(Term::Text(text))),
+            Some(token) => Err(TodoError::InvalidQuery(format!(
+                "Expected a term, found {token}"
+            ))),
+            None => Err(TodoError::InvalidQuery(
+                "Query ended unexpectedly".to_string(),
+            )),
+        }
+    }
+}
+
+/// Compares numbers as numbers, dates like `2025-07-01` and `2025-07-01T09:30+02:00` as dates
+/// in the time zone of the lists and anything else as text. Only the days are compared if either
+/// date has no time
+fn compare(a: &str, b: &str) -> Ordering {
+    if let (Ok(a), Ok(b)) = (parse_date_time(a), parse_date_time(b)) {
+        return match (a, b) {
+            ((a, Some(a_time)), (b, Some(b_time))) => (a, a_time).cmp(&(b, b_time)),
+            ((a, _), (b, _)) => a.cmp(&b),
+        };
+    }
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        _ => a.cmp(b),
+    }
+}
+
+/// Splits `due<=2025-07-01` into its key, comparison and value. `None` if it isn't a comparison
+fn parse_comparison(word: &str) -> Result<Option<Term>, TodoError> {
+    let Some(start) = word.find(['<', '>', '=']) else {
+        return Ok(None);
+    };
+    let (key, rest) = word.split_at(start);
+    let (ordering, or_equal, value) = if let Some(value) = rest.strip_prefix("<=") {
+        (Ordering::Less, true, value)
+    } else if let Some(value) = rest.strip_prefix(">=") {
+        (Ordering::Greater, true, value)
+    } else if let Some(value) = rest.strip_prefix('<') {
+        (Ordering::Less, false, value)
+    } else if let Some(value) = rest.strip_prefix('>') {
+        (Ordering::Greater, false, value)
+    } else if let Some(value) = rest.strip_prefix('=') {
+        (Ordering::Equal, false, value)
+    } else {
+        return Ok(None);
+    };
+    let valid_key = !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if !valid_key {
+        return Ok(None);
+    }
+    if value.is_empty() {
+        return Err(TodoError::InvalidQuery(format!(
+            "'{word}' has no value to compare with"
+        )));
+    }
+    check_date(value)?;
+    Ok(Some(Term::Compare(
+        key.to_string(),
+        ordering,
+        or_equal,
+        resolve_value(value),
+    )))
+}
+
+/// Fails if `value` starts like a date, `YYYY-MM-DD`, but isn't one, like `2026-02-30`, so it
+/// isn't compared as text
+fn check_date(value: &str) -> Result<(), TodoError> {
+    let bytes = value.as_bytes();
+    let date_like = bytes.len() >= 10
+        && bytes[..10].iter().enumerate().all(|(i, b)| {
+            if i == 4 || i == 7 {
+                *b == b'-'
+            } else {
+                b.is_ascii_digit()
+            }
+        });
+    match parse_date_time(value) {
+        Err(e) if date_like => Err(TodoError::InvalidQuery(format!(
+            "'{value}' isn't a valid date. {e}"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Turns relative dates like `today` into `YYYY-MM-DD` dates
+fn resolve_value(value: &str) -> String {
+    match value {
+        "today" | "tomorrow" | "yesterday" => value
+            .parse::<Date>()
+            .map_or(value.to_string(), |date| date.to_string()),
+        _ => value.to_string(),
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term, TodoError> {
+    if let Some(comparison) = parse_comparison(word)? {
+        return Ok(comparison);
+    }
+    Ok(match word.split_once(':') {
+        Some(("tag", tag)) => Term::Tag(tag.trim_start_matches('#').to_string()),
+        Some(("state", state)) => match state {
+            "open" => Term::State(false),
+            "done" => Term::State(true),
+            _ => {
+                return Err(TodoError::InvalidQuery(format!(
+                    "The state can be 'open' or 'done'. Found '{state}'"
+                )))
+            }
+        },
+        Some((key, value)) if !key.is_empty() && !value.is_empty() => {
+            check_date(value)?;
+            Term::Field(key.to_string(), resolve_value(value))
+        }
+        _ => match word.strip_prefix('#') {
+            Some(tag) if !tag.is_empty() => Term::Tag(tag.to_string()),
+            _ => Term::Text(word.to_string()),
+        },
+    })
+}
+
+impl FromStr for Query {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(token) = parser.peek() {
+            return Err(TodoError::InvalidQuery(format!("Unexpected {token}")));
+        }
+        Ok(Query(expr))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(s: &str) -> Query {
+        s.parse().unwrap()
+    }
+
+    fn matching<'a>(s: &str, titles: &[&'a str]) -> Vec<&'a str> {
+        let query = query(s);
+        titles
+            .iter()
+            .copied()
+            .filter(|title| query.matches(&format!("- [ ] {title}").parse().unwrap()))
+            .collect()
+    }
+
+    fn word(s: &str) -> Token {
+        Token::Word(s.to_string())
+    }
+
+    fn text(s: &str) -> Expr {
+        Expr::Term(Term::Text(s.to_string()))
+    }
+
+    #[test]
+    fn tokenizes() {
+        assert_eq!(
+            tokenize(r#"(a OR "b c")AND NOT due<=today or"#).unwrap(),
+            [
+                Token::Open,
+                word("a"),
+                Token::Or,
+                Token::Quoted("b c".to_string()),
+                Token::Close,
+                Token::And,
+                Token::Not,
+                word("due<=today"),
+                word("or"),
+            ]
+        );
+        assert!(tokenize(r#"a "b"#).is_err());
+    }
+
+    #[test]
+    fn parses_with_precedence() {
+        // OR binds loosest, then AND and adjacent terms, then NOT
+        assert_eq!(
+            query("a OR b c AND NOT d"),
+            Query(Expr::Or(vec![
+                text("a"),
+                Expr::And(vec![text("b"), text("c"), Expr::Not(Box::new(text("d")))]),
+            ]))
+        );
+        assert_eq!(
+            query("(a OR b) c"),
+            Query(Expr::And(vec![
+                Expr::Or(vec![text("a"), text("b")]),
+                text("c")
+            ]))
+        );
+        assert_eq!(
+            query("NOT NOT a"),
+            Query(Expr::Not(Box::new(Expr::Not(Box::new(text("a"))))))
+        );
+        for invalid in ["", "(a", "a)", "a OR", "AND a", "NOT", "()", "state:later"] {
+            assert!(invalid.parse::<Query>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn matches_with_and_or_not() {
+        let titles = ["report #work", "groceries #home", "plan #work #home"];
+        assert_eq!(
+            matching("tag:work", &titles),
+            ["report #work", "plan #work #home"]
+        );
+        assert_eq!(matching("#work AND #home", &titles), ["plan #work #home"]);
+        assert_eq!(matching("#work #home", &titles), ["plan #work #home"]);
+        assert_eq!(
+            matching("report OR groceries", &titles),
+            ["report #work", "groceries #home"]
+        );
+        assert_eq!(matching("NOT tag:work", &titles), ["groceries #home"]);
+        assert_eq!(
+            matching("NOT (REPORT OR groceries)", &titles),
+            ["plan #work #home"]
+        );
+        assert_eq!(matching(r#""plan #work""#, &titles), ["plan #work #home"]);
+        let item = "- [x] done".parse::<TodoItem>().unwrap();
+        assert!(query("state:done").matches(&item));
+        assert!(!query("state:open").matches(&item));
+    }
+
+    #[test]
+    fn compares_fields() {
+        let titles = [
+            "a due:2026-10-01",
+            "b due:2026-10-14",
+            "c due:2026-11-01",
+            "d size:9",
+            "e size:10",
+            "f",
+        ];
+        assert_eq!(matching("due<2026-10-14", &titles), ["a due:2026-10-01"]);
+        assert_eq!(
+            matching("due<=2026-10-14", &titles),
+            ["a due:2026-10-01", "b due:2026-10-14"]
+        );
+        assert_eq!(matching("due>2026-10-14", &titles), ["c due:2026-11-01"]);
+        assert_eq!(
+            matching("due>=2026-10-14", &titles),
+            ["b due:2026-10-14", "c due:2026-11-01"]
+        );
+        assert_eq!(matching("due=2026-10-14", &titles), ["b due:2026-10-14"]);
+        assert_eq!(matching("due:2026-10-14", &titles), ["b due:2026-10-14"]);
+        // numbers as numbers, not as text
+        assert_eq!(matching("size>9", &titles), ["e size:10"]);
+        assert_eq!(matching("size<10", &titles), ["d size:9"]);
+    }
+
+    #[test]
+    fn compares_values() {
+        assert_eq!(compare("2026-10-14", "2026-10-14T09:30"), Ordering::Equal);
+        assert_eq!(
+            compare("2026-10-14T09:30", "2026-10-14T10:00"),
+            Ordering::Less
+        );
+        assert_eq!(compare("2026-02-28", "2026-10-01"), Ordering::Less);
+        assert_eq!(compare("9", "10"), Ordering::Less);
+        assert_eq!(compare("1.5", "1.25"), Ordering::Greater);
+        assert_eq!(compare("abc", "abd"), Ordering::Less);
+        assert_eq!(compare("10", "abc"), Ordering::Less);
+    }
+
+    #[test]
+    fn resolves_relative_dates() {
+        let today = Date::today();
+        assert_eq!(resolve_value("today"), today.to_string());
+        assert_eq!(resolve_value("tomorrow"), today.add_days(1).to_string());
+        assert_eq!(resolve_value("yesterday"), today.add_days(-1).to_string());
+        assert_eq!(resolve_value("2026-10-14"), "2026-10-14");
+        assert_eq!(resolve_value("later"), "later");
+
+        let titles = [
+            format!("old due:{}", today.add_days(-1)),
+            format!("now due:{today}"),
+            format!("soon due:{}", today.add_days(1)),
+        ];
+        let titles = titles.iter().map(String::as_str).collect::<Vec<_>>();
+        assert_eq!(matching("due<today", &titles), &titles[..1]);
+        assert_eq!(matching("due<=today", &titles), &titles[..2]);
+        assert_eq!(matching("due>=tomorrow", &titles), &titles[2..]);
+        assert_eq!(matching("due:yesterday", &titles), &titles[..1]);
+    }
+
+    #[test]
+    fn rejects_empty_values_and_invalid_dates() {
+        for invalid in [
+            "due<",
+            "due<=",
+            "due>",
+            "due=",
+            "due<2026-02-30",
+            "due>=2026-13-01",
+            "due:2025-02-29",
+            "due<2026-10-14T25:00",
+        ] {
+            assert!(invalid.parse::<Query>().is_err(), "{invalid}");
+        }
+        assert!("due<2024-02-29".parse::<Query>().is_ok());
+        // not comparisons, so matched as text
+        assert_eq!(query("<3"), Query(text("<3")));
+        assert_eq!(query("a-b=c"), Query(text("a-b=c")));
+    }
+}
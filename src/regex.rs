@@ -0,0 +1,589 @@
+//! A small backtracking regex engine for find-and-replace. It supports literals, `.`, classes
+//! like `[a-z]` and `[^0-9]`, `\d`, `\w`, `\s` and their negations, `^`, `$`, groups with `|`,
+//! `(?:…)` and the greedy `*`, `+`, `?` and `{n,m}` repetitions
+
+use std::str::FromStr;
+
+use crate::TodoError;
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    /// Alternatives of the whole pattern, which is group 0
+    alternatives: Vec<Vec<Node>>,
+    groups: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Group {
+        alternatives: Vec<Vec<Node>>,
+        index: Option<usize>,
+    },
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+/// Start and end of the whole match and of each group, as char indices
+type Captures = Vec<Option<(usize, usize)>>;
+
+impl Regex {
+    /// `text` with every match replaced by `replacement`, in which `$0` to `$9` stand for the
+    /// match and its groups and `$$` for a `$`
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut result = String::new();
+        let mut position = 0;
+        for captures in self.matches(&chars) {
+            let (start, end) = captures[0].expect("group 0 is the whole match");
+            result.extend(&chars[position..start]);
+            expand(replacement, &chars, &captures, &mut result);
+            if end == start {
+                // step over empty matches so they aren't found again
+                result.extend(chars.get(end));
+                position = end + 1;
+            } else {
+                position = end;
+            }
+        }
+        if position < chars.len() {
+            result.extend(&chars[position..]);
+        }
+        result
+    }
+
+    /// Whether the regex matches an empty part of `text`, like `a*` does before every `b`
+    pub fn matches_empty(&self, text: &str) -> bool {
+        self.matches(&text.chars().collect::<Vec<_>>())
+            .iter()
+            .any(|captures| captures[0].is_some_and(|(start, end)| start == end))
+    }
+
+    /// The matches in `chars` that don't overlap, from the first one on
+    fn matches(&self, chars: &[char]) -> Vec<Captures> {
+        let mut matches = vec![];
+        let mut position = 0;
+        while position <= chars.len() {
+            let Some(captures) = self.find_at(chars, position) else {
+                break;
+            };
+            let (start, end) = captures[0].expect("group 0 is the whole match");
+            position = if end == start { end + 1 } else { end };
+            matches.push(captures);
+        }
+        matches
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find_at(&text.chars().collect::<Vec<_>>(), 0).is_some()
+    }
+
+    fn find_at(&self, chars: &[char], from: usize) -> Option<Captures> {
+        let whole = Node::Group {
+            alternatives: self.alternatives.clone(),
+            index: Some(0),
+        };
+        (from..=chars.len()).find_map(|start| {
+            let mut captures = vec![None; self.groups + 1];
+            let mut found = None;
+            let matched = Matcher { chars }.nodes(
+                std::slice::from_ref(&whole),
+                start,
+                &mut captures,
+                &mut |_, captures| {
+                    found = Some(captures.clone());
+                    true
+                },
+            );
+            matched.then_some(found).flatten()
+        })
+    }
+}
+
+struct Matcher<'a> {
+    chars: &'a [char],
+}
+
+impl Matcher<'_> {
+    /// Matches `nodes` at `position`, then calls `next` with where the match ended. Gives up
+    /// on a way of matching when `next` returns false, trying the others
+    fn nodes(
+        &self,
+        nodes: &[Node],
+        position: usize,
+        captures: &mut Captures,
+        next: &mut dyn FnMut(usize, &mut Captures) -> bool,
+    ) -> bool {
+        let Some((node, rest)) = nodes.split_first() else {
+            return next(position, captures);
+        };
+        match node {
+            Node::Group {
+                alternatives,
+                index,
+            } => alternatives.iter().any(|alternative| {
+                self.nodes(alternative, position, captures, &mut |end, captures| {
+                    let Some(index) = *index else {
+                        return self.nodes(rest, end, captures, next);
+                    };
+                    let saved = captures[index];
+                    captures[index] = Some((position, end));
+                    if self.nodes(rest, end, captures, next) {
+                        return true;
+                    }
+                    captures[index] = saved;
+                    false
+                })
+            }),
+            Node::Repeat { node, min, max } => {
+                self.repeat(node, *min, *max, 0, position, rest, captures, next)
+            }
+            node => match self.step(node, position) {
+                Some(end) => self.nodes(rest, end, captures, next),
+                None => false,
+            },
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn repeat(
+        &self,
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        count: usize,
+        position: usize,
+        rest: &[Node],
+        captures: &mut Captures,
+        next: &mut dyn FnMut(usize, &mut Captures) -> bool,
+    ) -> bool {
+        if max.is_none_or(|max| count < max) {
+            let more = self.nodes(
+                std::slice::from_ref(node),
+                position,
+                captures,
+                &mut |end, captures| {
+                    // repeating an empty match can't get anywhere
+                    end != position
+                        && self.repeat(node, min, max, count + 1, end, rest, captures, next)
+                },
+            );
+            if more {
+                return true;
+            }
+        }
+        count >= min && self.nodes(rest, position, captures, next)
+    }
+
+    /// Where a match of the single character or anchor `node` at `position` ends
+    fn step(&self, node: &Node, position: usize) -> Option<usize> {
+        let c = self.chars.get(position).copied();
+        let matched = match node {
+            Node::Start => return (position == 0).then_some(position),
+            Node::End => return (position == self.chars.len()).then_some(position),
+            Node::Char(expected) => c == Some(*expected),
+            Node::Any => c.is_some(),
+            Node::Class { ranges, negated } => c.is_some_and(|c| {
+                ranges.iter().any(|(low, high)| (*low..=*high).contains(&c)) != *negated
+            }),
+            Node::Group { .. } | Node::Repeat { .. } => unreachable!("handled by Matcher::nodes"),
+        };
+        matched.then_some(position + 1)
+    }
+}
+
+/// Appends `replacement` with its `$n` references filled in to `result`
+fn expand(replacement: &str, chars: &[char], captures: &Captures, result: &mut String) {
+    let mut replacement = replacement.chars().peekable();
+    while let Some(c) = replacement.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        match replacement.peek().copied() {
+            Some('$') => {
+                replacement.next();
+                result.push('$');
+            }
+            Some(d @ '0'..='9') => {
+                replacement.next();
+                let group = d.to_digit(10).unwrap_or_default() as usize;
+                if let Some(Some((start, end))) = captures.get(group) {
+                    result.extend(&chars[*start..*end]);
+                }
+            }
+            _ => result.push('$'),
+        }
+    }
+}
+
+/// Replaces a text, or the matches of a regex, in titles with another text, as `todo replace`
+/// does. Titles stay on one line and the replacement never goes in between characters
+pub struct Replacer {
+    pattern: Pattern,
+    replacement: String,
+}
+
+enum Pattern {
+    Text(String),
+    Regex(Regex),
+}
+
+impl Replacer {
+    /// Replaces `old`, which is a regex with `regex`, with `new`. Fails if `old` is empty or
+    /// `new` has a line break, which would split task lines in list files
+    pub fn new(old: &str, new: &str, regex: bool) -> Result<Self, TodoError> {
+        if old.is_empty() {
+            return Err(TodoError::InvalidReplacement(
+                "The text to replace can't be empty".to_string(),
+            ));
+        }
+        if new.contains(['\n', '\r']) {
+            return Err(TodoError::InvalidReplacement(
+                "The replacement can't have line breaks".to_string(),
+            ));
+        }
+        Ok(Self {
+            pattern: if regex {
+                Pattern::Regex(old.parse()?)
+            } else {
+                Pattern::Text(old.to_string())
+            },
+            replacement: new.to_string(),
+        })
+    }
+
+    /// `title` with the replacements made. Fails if the regex matches an empty part of it
+    pub fn replace(&self, title: &str) -> Result<String, TodoError> {
+        match &self.pattern {
+            Pattern::Text(old) => Ok(title.replace(old, &self.replacement)),
+            Pattern::Regex(regex) if regex.matches_empty(title) => {
+                Err(TodoError::InvalidReplacement(format!(
+                    "The regex matches an empty part of '{title}'. Change it so it needs at \
+                    least one character"
+                )))
+            }
+            Pattern::Regex(regex) => Ok(regex.replace_all(title, &self.replacement)),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> TodoError {
+        TodoError::InvalidRegex(format!("{message} at position {}", self.position))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, TodoError> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.next();
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, TodoError> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let node = self.atom()?;
+            nodes.push(self.repetition(node)?);
+        }
+        Ok(nodes)
+    }
+
+    fn repetition(&mut self, node: Node) -> Result<Node, TodoError> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.counted(node),
+            _ => return Ok(node),
+        };
+        self.next();
+        self.repeat(node, min, max)
+    }
+
+    /// `{n}`, `{n,}` and `{n,m}`
+    fn counted(&mut self, node: Node) -> Result<Node, TodoError> {
+        self.next();
+        let mut spec = String::new();
+        loop {
+            match self.next() {
+                Some('}') => break,
+                Some(c) => spec.push(c),
+                None => return Err(self.error("Unclosed '{'")),
+            }
+        }
+        let number = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| self.error("Invalid count"))
+        };
+        let (min, max) = match spec.split_once(',') {
+            Some((min, "")) => (number(min)?, None),
+            Some((min, max)) => (number(min)?, Some(number(max)?)),
+            None => (number(&spec)?, Some(number(&spec)?)),
+        };
+        self.repeat(node, min, max)
+    }
+
+    fn repeat(&self, node: Node, min: usize, max: Option<usize>) -> Result<Node, TodoError> {
+        if matches!(node, Node::Start | Node::End) {
+            return Err(self.error("Anchors can't be repeated"));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("Invalid count"));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, TodoError> {
+        let c = self.next().ok_or_else(|| self.error("Unexpected end"))?;
+        Ok(match c {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                let index = if self.chars[self.position..].starts_with(&['?', ':']) {
+                    self.position += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let alternatives = self.alternatives()?;
+                if self.next() != Some(')') {
+                    return Err(self.error("Unclosed '('"));
+                }
+                Node::Group {
+                    alternatives,
+                    index,
+                }
+            }
+            '[' => self.class()?,
+            '\\' => self.escape()?,
+            '*' | '+' | '?' | '{' => {
+                return Err(self.error(&format!("Nothing to repeat with '{c}'")))
+            }
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Node, TodoError> {
+        let c = self.next().ok_or_else(|| self.error("Unfinished escape"))?;
+        Ok(match shorthand_class(c) {
+            Some((ranges, negated)) => Node::Class { ranges, negated },
+            None => Node::Char(match c {
+                'n' => '\n',
+                't' => '\t',
+                c => c,
+            }),
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, TodoError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.next();
+        }
+        let mut ranges = vec![];
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or_else(|| self.error("Unclosed '['"))?;
+            let low = match c {
+                // a `]` right at the start is a literal
+                ']' if !first => break,
+                '\\' => {
+                    let escaped = self.next().ok_or_else(|| self.error("Unfinished escape"))?;
+                    if let Some((shorthand, negated)) = shorthand_class(escaped) {
+                        ranges.extend(if negated {
+                            complement(&shorthand)
+                        } else {
+                            shorthand
+                        });
+                        first = false;
+                        continue;
+                    }
+                    escaped
+                }
+                c => c,
+            };
+            first = false;
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.position + 1).is_some_and(|&c| c != ']');
+            if is_range {
+                self.next();
+                let high = match self.next() {
+                    Some('\\') => self.next().ok_or_else(|| self.error("Unfinished escape"))?,
+                    Some(high) => high,
+                    None => return Err(self.error("Unclosed '['")),
+                };
+                if high < low {
+                    return Err(self.error("Invalid range"));
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+}
+
+/// The ranges of `\d`, `\w` and `\s`, and whether the class is negated for `\D` and the like
+fn shorthand_class(c: char) -> Option<(Vec<(char, char)>, bool)> {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')],
+        _ => return None,
+    };
+    Some((ranges, c.is_ascii_uppercase()))
+}
+
+/// Every character outside of `ranges`, which are ASCII and sorted, for `[\D]` and the like
+fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort();
+    let mut result = vec![];
+    let mut from = 0;
+    for (low, high) in sorted {
+        if (low as u8) > from {
+            result.push((from as char, (low as u8 - 1) as char));
+        }
+        from = from.max(high as u8 + 1);
+    }
+    result.push((from as char, char::MAX));
+    result
+}
+
+impl FromStr for Regex {
+    type Err = TodoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            position: 0,
+            groups: 0,
+        };
+        let alternatives = parser.alternatives()?;
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("Unmatched ')'"));
+        }
+        Ok(Regex {
+            alternatives,
+            groups: parser.groups,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(pattern: &str, text: &str, replacement: &str) -> String {
+        pattern
+            .parse::<Regex>()
+            .unwrap()
+            .replace_all(text, replacement)
+    }
+
+    #[test]
+    fn alternation() {
+        assert_eq!(replace("cat|dog", "cat and dog", "pet"), "pet and pet");
+        assert_eq!(replace("^a|b$", "aab", "_"), "_a_");
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(replace("(ab)+", "ababab c", "x"), "x c");
+        assert_eq!(replace("a(?:b|c)d", "abd acd aed", "_"), "_ _ aed");
+        assert!("(a".parse::<Regex>().is_err());
+        assert!("a)".parse::<Regex>().is_err());
+    }
+
+    #[test]
+    fn counted_repetitions() {
+        assert_eq!(replace(r"\d{2}", "1 12 123", "#"), "1 # #3");
+        assert_eq!(replace(r"\d{2,}", "1 12 123", "#"), "1 # #");
+        assert_eq!(replace("a{1,2}", "aaa", "x"), "xx");
+        assert!("a{2,1}".parse::<Regex>().is_err());
+        assert!("a{".parse::<Regex>().is_err());
+    }
+
+    #[test]
+    fn empty_matches() {
+        assert_eq!(replace("x*", "abc", "-"), "-a-b-c-");
+        assert_eq!(replace("^", "abc", ">"), ">abc");
+        assert_eq!(replace("$", "abc", "<"), "abc<");
+        assert_eq!(replace("b*", "abba", "-"), "-a--a-");
+    }
+
+    #[test]
+    fn expands_groups() {
+        assert_eq!(
+            replace(r"(\w+)@(\w+)", "me@home", "$2 at $1 ($0) $$5"),
+            "home at me (me@home) $5"
+        );
+        // groups that didn't take part in the match are empty
+        assert_eq!(replace("(a)|(b)", "b", "[$1$2]"), "[b]");
+        assert_eq!(replace("a", "a", "$"), "$");
+    }
+
+    #[test]
+    fn replacer_refuses_empty_matches_and_line_breaks() {
+        assert!(Replacer::new("", "X", false).is_err());
+        assert!(Replacer::new("", "X", true).is_err());
+        assert!(Replacer::new("a", "x\ny", false).is_err());
+        assert!(Replacer::new("a", "x\r\n", true).is_err());
+
+        let replacer = Replacer::new("a*", "X", true).unwrap();
+        assert!(replacer.replace("abc").is_err());
+        assert!(replacer.replace("").is_err());
+        let replacer = Replacer::new("a+", "X", true).unwrap();
+        assert_eq!(replacer.replace("aabca").unwrap(), "XbcX");
+        let replacer = Replacer::new("a.", "X", false).unwrap();
+        assert_eq!(replacer.replace("a.b ab").unwrap(), "Xb ab");
+    }
+
+    #[test]
+    fn negated_shorthands_in_classes() {
+        assert_eq!(replace(r"[\D]+", "ab12cd", "_"), "_12_");
+        assert_eq!(replace(r"[\W]", "a-b c", "_"), "a_b_c");
+        assert_eq!(replace(r"[\S]+", "ab  é", "x"), "x  x");
+        assert_eq!(replace(r"[^\D]", "a1", "_"), "a_");
+    }
+}
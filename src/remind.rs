@@ -0,0 +1,177 @@
+//! Reminders at set times of day through cron or systemd timers, which run `todo notify`,
+//! instead of keeping `todo daemon` running
+
+use std::{
+    env, fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use todo::{
+    date::{Date, Time},
+    TodoList,
+};
+
+use crate::{config::Config, daemon};
+
+/// Marks the crontab lines written by `todo remind --install`, so they're replaced next time
+const CRONTAB_MARKER: &str = "# todo remind";
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Scheduler {
+    Cron,
+    Systemd,
+}
+
+/// Shows a notification for each open item that is due today or overdue
+pub fn notify_due(config: &Config) -> Result<()> {
+    let today = Date::today();
+    for name in config.list_names()? {
+        let Ok(list) = TodoList::from_file(&config.list_path(&name)) else {
+            continue;
+        };
+        for item in list.items() {
+            if !item.is_done() && item.due().is_some_and(|(due, _)| due <= today) {
+                daemon::notify(&name, &item.name)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The times of day reminders are scheduled at
+fn times(config: &Config) -> Result<Vec<Time>> {
+    if config.reminder_times().is_empty() {
+        return Ok(vec![daemon::DEFAULT_REMINDER_TIME]);
+    }
+    config
+        .reminder_times()
+        .iter()
+        .map(|time| {
+            time.parse()
+                .with_context(|| format!("The reminder time '{time}' is invalid"))
+        })
+        .collect()
+}
+
+/// Crontab lines running `todo notify` at the reminder times
+fn crontab_lines(config: &Config) -> Result<Vec<String>> {
+    let command = daemon::todo_command(config, "notify")?
+        .iter()
+        .map(|arg| format!("'{}'", arg.replace('\'', r"'\''")))
+        .collect::<Vec<_>>()
+        .join(" ");
+    // notifications from cron jobs only show up if they can find the desktop session
+    let session = env::var("DBUS_SESSION_BUS_ADDRESS")
+        .map(|address| format!("DBUS_SESSION_BUS_ADDRESS='{address}' "))
+        .unwrap_or_default();
+    // `%` ends the command in crontab lines
+    let command = format!("{session}{command}").replace('%', r"\%");
+    Ok(times(config)?
+        .iter()
+        .map(|time| {
+            format!(
+                "{} {} * * * {command} {CRONTAB_MARKER}",
+                time.minute, time.hour
+            )
+        })
+        .collect())
+}
+
+/// The service and timer units running `todo notify` at the reminder times, with their paths
+fn systemd_units(config: &Config) -> Result<[(PathBuf, String); 2]> {
+    let exec = daemon::systemd_exec(&daemon::todo_command(config, "notify")?);
+    let calendar = times(config)?
+        .iter()
+        .map(|time| format!("OnCalendar=*-*-* {time}:00\n"))
+        .collect::<String>();
+    let dir = daemon::systemd_user_dir()?;
+    Ok([
+        (
+            dir.join("todo-remind.service"),
+            format!(
+                "[Unit]\nDescription=Reminders of due todo items\n\n[Service]\nType=oneshot\nExecStart={exec}\n"
+            ),
+        ),
+        (
+            dir.join("todo-remind.timer"),
+            format!(
+                "[Unit]\nDescription=Reminders of due todo items\n\n[Timer]\n{calendar}Persistent=true\n\n[Install]\nWantedBy=timers.target\n"
+            ),
+        ),
+    ])
+}
+
+/// Prints the scheduler entries for the reminders, or installs them with `install`
+pub fn run(config: &Config, scheduler: Scheduler, install: bool) -> Result<()> {
+    match (scheduler, install) {
+        (Scheduler::Cron, false) => {
+            for line in crontab_lines(config)? {
+                println!("{line}");
+            }
+        }
+        (Scheduler::Cron, true) => {
+            install_crontab(&crontab_lines(config)?)?;
+            println!("Added the reminders to the crontab");
+        }
+        (Scheduler::Systemd, false) => {
+            for (path, contents) in systemd_units(config)? {
+                println!("# {}\n{contents}", path.display());
+            }
+        }
+        (Scheduler::Systemd, true) => {
+            for (path, contents) in systemd_units(config)? {
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)
+                        .with_context(|| format!("Couldn't create '{}'", dir.display()))?;
+                }
+                fs::write(&path, contents)
+                    .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+                println!("Wrote '{}'", path.display());
+            }
+            println!(
+                "Start the reminders with `systemctl --user daemon-reload && systemctl --user enable --now todo-remind.timer`"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the lines added by an earlier install in the user's crontab with `lines`
+fn install_crontab(lines: &[String]) -> Result<()> {
+    let current = Command::new("crontab")
+        .arg("-l")
+        .output()
+        .context("Couldn't run crontab. Is it installed?")?;
+    // `crontab -l` fails when there's no crontab yet
+    let current = if current.status.success() {
+        String::from_utf8_lossy(&current.stdout).into_owned()
+    } else {
+        String::new()
+    };
+    let mut crontab = current
+        .lines()
+        .filter(|line| !line.ends_with(CRONTAB_MARKER))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    for line in lines {
+        crontab.push_str(line);
+        crontab.push('\n');
+    }
+    let mut child = Command::new("crontab")
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Couldn't run crontab")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(crontab.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("crontab exited with {status}");
+    }
+    Ok(())
+}
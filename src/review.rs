@@ -0,0 +1,158 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use todo::{date::Date, DisplayOptions, TodoList};
+
+use crate::{
+    backup,
+    hooks::{self, ItemEvent},
+    quoted_titles, ListWriter,
+};
+
+enum Decision {
+    Keep,
+    Done,
+    Remove,
+    Defer(Date),
+    Move(String),
+}
+
+/// Walks through the open items of the list one at a time, asking what to do with each. The
+/// decisions are applied when all items are reviewed or the review is quit
+pub fn run(
+    writer: &ListWriter,
+    list_name: &str,
+    list_path: &Path,
+    options: &DisplayOptions,
+) -> Result<()> {
+    let config = writer.config;
+    let mut list = TodoList::from_file(list_path)?;
+    let open = list
+        .numbered_items()
+        .filter(|(_, item)| !item.is_done())
+        .map(|(number, _)| number)
+        .collect::<Vec<_>>();
+    if open.is_empty() {
+        println!("There are no open items in '{list_name}'");
+        return Ok(());
+    }
+    writer.ensure_writable(list_path)?;
+    println!(
+        "Reviewing {} open item(s) of '{list_name}'. [k]eep, [d]one, [r]emove, de[f]er, [m]ove or [q]uit",
+        open.len()
+    );
+
+    let mut decisions = vec![];
+    'items: for (i, &number) in open.iter().enumerate() {
+        let item = list.get_item(number)?;
+        println!(
+            "\n{}",
+            item.render_labeled(&format!("({}/{})", i + 1, open.len()), options)
+        );
+        let decision = loop {
+            let Some(answer) = prompt("> ")? else {
+                break 'items;
+            };
+            match answer.as_str() {
+                "k" | "" => break Decision::Keep,
+                "d" => break Decision::Done,
+                "r" => break Decision::Remove,
+                "f" => {
+                    let Some(answer) = prompt("Defer until (e.g. tomorrow or 2025-07-01): ")?
+                    else {
+                        break 'items;
+                    };
+                    match answer.parse() {
+                        Ok(date) => break Decision::Defer(date),
+                        Err(e) => println!("{e}"),
+                    }
+                }
+                "m" => {
+                    let Some(answer) = prompt("Move to list: ")? else {
+                        break 'items;
+                    };
+                    let name = config.resolve_alias(&answer).to_string();
+                    if name == list_name {
+                        println!("The item is already in '{name}'");
+                    } else if config.list_path(&name).exists() {
+                        break Decision::Move(name);
+                    } else {
+                        println!("There's no list '{name}'");
+                    }
+                }
+                "q" => break 'items,
+                _ => println!("Answer k, d, r, f, m or q"),
+            }
+        };
+        decisions.push((number, decision));
+    }
+
+    let mut kept = 0;
+    let mut done = vec![];
+    let mut deferred = 0;
+    let mut removed = vec![];
+    let mut moves = BTreeMap::<String, Vec<usize>>::new();
+    for (number, decision) in decisions {
+        match decision {
+            Decision::Keep => kept += 1,
+            Decision::Done => done.push(list.mark_item_done(number)?.clone()),
+            Decision::Defer(date) => {
+                list.get_item_mut(number)?
+                    .set_field("due", &date.to_string());
+                deferred += 1;
+            }
+            Decision::Remove => removed.push(number),
+            Decision::Move(to) => moves.entry(to).or_default().push(number),
+        }
+    }
+
+    let moved = moves.values().map(Vec::len).sum::<usize>();
+    if !removed.is_empty() || moved > 0 {
+        backup::backup_list(config, list_name, list_path)?;
+    }
+    // items are taken out of the list all at once so the numbers stay valid
+    let mut taken =
+        list.delete_items(moves.values().flatten().chain(&removed).copied().collect())?;
+    let mut by_target = vec![];
+    for (to, numbers) in &moves {
+        by_target.push((to, taken.drain(..numbers.len()).collect::<Vec<_>>()));
+    }
+    for (to, items) in by_target {
+        let path = config.list_path(to);
+        let mut to_list = TodoList::from_file(&path)?;
+        let message = format!("move {} to {to}", quoted_titles(&items));
+        to_list.add_items(items);
+        writer
+            .write(&mut to_list, &path, &message)
+            .with_context(|| format!("Couldn't write to the list '{to}'"))?;
+    }
+    if !done.is_empty() || deferred > 0 || !taken.is_empty() || moved > 0 {
+        writer
+            .write(&mut list, list_path, &format!("review {list_name}"))
+            .with_context(|| "Couldn't write the list")?;
+    }
+    hooks::item_event(config, ItemEvent::Done, list_name, &done);
+    hooks::item_event(config, ItemEvent::Removed, list_name, &taken);
+
+    println!(
+        "\nKept {kept}, done {}, removed {}, deferred {deferred}, moved {moved}",
+        done.len(),
+        taken.len()
+    );
+    Ok(())
+}
+
+/// Asks for a line of input. `None` if stdin is closed
+fn prompt(question: &str) -> Result<Option<String>> {
+    print!("{question}");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    let read = io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read user input")?;
+    Ok((read > 0).then(|| answer.trim().to_string()))
+}
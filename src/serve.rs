@@ -0,0 +1,335 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use todo::{
+    text::{percent_decode, single_line},
+    TodoError, TodoItem, TodoList,
+};
+
+use crate::{
+    config::Config,
+    hooks::{self, ItemEvent},
+    ListWriter,
+};
+
+/// Requests with bigger bodies are refused
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+/// Requests whose request line and headers are bigger than this are refused
+const MAX_HEAD_SIZE: u64 = 16 * 1024;
+/// How long a client can take to send its request. Clients are served one at a time, so an
+/// idle one would hold up the others
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves the lists over HTTP on `host:port` until the process is stopped. Requests must
+/// carry `Authorization: Bearer <token>` if a token is given. Web pages can only make requests
+/// with a token, so other sites the user visits can't reach the lists
+pub fn run(writer: &ListWriter, host: &str, port: u16, token: Option<&str>) -> Result<()> {
+    let listener = TcpListener::bind((host, port))
+        .with_context(|| format!("Couldn't listen on {host}:{port}"))?;
+    if token.is_none() && !matches!(host, "127.0.0.1" | "localhost" | "::1") {
+        eprintln!("Anyone who can reach {host}:{port} can change the lists. Pass --token to require a token");
+    }
+    println!("Serving the lists on http://{host}:{port}");
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+            eprintln!("Couldn't set a timeout for a request: {e}");
+            continue;
+        }
+        let response = match read_request(&mut stream) {
+            Ok(request) if !authorized(&request, token) => {
+                Response::error(401, "Missing or wrong token")
+            }
+            Ok(request) => route(writer, &request).unwrap_or_else(|e| match e.downcast_ref() {
+                Some(TodoError::InvalidItemNumber(_)) => Response::error(404, &format!("{e:#}")),
+                _ => Response::error(400, &format!("{e:#}")),
+            }),
+            Err(e) => Response::error(400, &format!("{e:#}")),
+        };
+        if let Err(e) = response.send(&mut stream, token.is_some()) {
+            eprintln!("Couldn't send a response: {e}");
+        }
+    }
+    Ok(())
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body as JSON. It must be sent as `application/json`, which browsers only allow pages
+    /// after asking with a preflight request
+    fn json(&self) -> Result<Value> {
+        let content_type = self.header("Content-Type").unwrap_or_default();
+        if !content_type
+            .split(';')
+            .next()
+            .is_some_and(|media| media.trim().eq_ignore_ascii_case("application/json"))
+        {
+            bail!("The body should be sent as application/json");
+        }
+        serde_json::from_slice(&self.body).context("The body should be JSON")
+    }
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    let mut reader = BufReader::new(stream);
+    let mut head_size = 0;
+    let mut read_head_line = |line: &mut String| -> Result<()> {
+        let read = (&mut reader)
+            .take(MAX_HEAD_SIZE - head_size)
+            .read_line(line)?;
+        head_size += read as u64;
+        if head_size >= MAX_HEAD_SIZE {
+            bail!("The headers are too big");
+        }
+        Ok(())
+    };
+    let mut line = String::new();
+    read_head_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        bail!("Invalid request line");
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut headers = vec![];
+    loop {
+        line.clear();
+        read_head_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request {
+        method,
+        path,
+        headers,
+        body: vec![],
+    };
+    let length = request
+        .header("Content-Length")
+        .map(str::parse::<usize>)
+        .transpose()
+        .context("Invalid Content-Length")?
+        .unwrap_or(0);
+    if length > MAX_BODY_SIZE {
+        bail!("The body is too big");
+    }
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body)?;
+    Ok(request)
+}
+
+fn authorized(request: &Request, token: Option<&str>) -> bool {
+    // browsers don't send credentials with CORS preflight requests
+    request.method == "OPTIONS"
+        || token
+            .is_none_or(|token| request.header("Authorization") == Some(&format!("Bearer {token}")))
+}
+
+struct Response {
+    status: u16,
+    body: Option<Value>,
+}
+
+impl Response {
+    fn json(status: u16, body: Value) -> Self {
+        Self {
+            status,
+            body: Some(body),
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, json!({ "error": message }))
+    }
+
+    /// Sends the response, allowing web pages to read it with `cors`
+    fn send(&self, stream: &mut TcpStream, cors: bool) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "Internal Server Error",
+        };
+        let body = self
+            .body
+            .as_ref()
+            .map(|body| body.to_string())
+            .unwrap_or_default();
+        let cors = if cors {
+            "Access-Control-Allow-Origin: *\r\n\
+            Access-Control-Allow-Methods: GET, POST, PATCH, OPTIONS\r\n\
+            Access-Control-Allow-Headers: Authorization, Content-Type\r\n"
+        } else {
+            ""
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {reason}\r\n\
+            Content-Type: application/json\r\n\
+            Content-Length: {}\r\n\
+            {cors}\
+            Connection: close\r\n\r\n{body}",
+            self.status,
+            body.len()
+        )
+    }
+}
+
+/// `GET /lists`, `GET /lists/{name}/items`, `POST /lists/{name}/items` with `{"title": …}` and
+/// `PATCH /lists/{name}/items/{number}` with `{"done": …}` and/or `{"title": …}`
+fn route(writer: &ListWriter, request: &Request) -> Result<Response> {
+    let config = writer.config;
+    let path = request.path.split('?').next().unwrap_or_default();
+    let segments = path
+        .trim_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect::<Vec<_>>();
+    let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+
+    if request.method == "OPTIONS" {
+        return Ok(Response {
+            status: 204,
+            body: None,
+        });
+    }
+    let response = match (request.method.as_str(), segments.as_slice()) {
+        ("GET", ["lists"]) => {
+            let mut lists = vec![];
+            for name in config.list_names()? {
+                if config.is_hidden(&name) {
+                    continue;
+                }
+                let list = TodoList::from_file(&config.list_path(&name))?;
+                lists.push(json!({
+                    "name": name,
+                    "items": list.len(),
+                    "done": list.done_count(),
+                }));
+            }
+            Response::json(200, json!(lists))
+        }
+        ("GET", ["lists", name, "items"]) => {
+            let Some((_, list)) = read_list(config, name)? else {
+                return Ok(no_list(name));
+            };
+            let items = list
+                .numbered_items()
+                .map(|(number, item)| item_json(number, item))
+                .collect::<Vec<_>>();
+            Response::json(200, json!(items))
+        }
+        ("POST", ["lists", name, "items"]) => {
+            let Some((path, mut list)) = read_list(config, name)? else {
+                return Ok(no_list(name));
+            };
+            let body = request.json()?;
+            let Some(title) = body["title"]
+                .as_str()
+                .map(single_line)
+                .filter(|t| !t.is_empty())
+            else {
+                bail!("The body should have a \"title\"");
+            };
+            list.add_item(&title);
+            let number = list.len();
+            let item = list.get_item(number)?.clone();
+            writer.write(&mut list, &path, &format!("add \"{title}\""))?;
+            hooks::item_event(config, ItemEvent::Added, name, std::slice::from_ref(&item));
+            Response::json(201, item_json(number, &item))
+        }
+        ("PATCH", ["lists", name, "items", number]) => {
+            let Some((path, mut list)) = read_list(config, name)? else {
+                return Ok(no_list(name));
+            };
+            let number = number
+                .parse()
+                .map_err(|_| TodoError::InvalidItemNumber(0))?;
+            let body = request.json()?;
+            let item = list.get_item_mut(number)?;
+            let was_done = item.is_done();
+            if let Some(title) = body["title"].as_str().map(single_line) {
+                if title.is_empty() {
+                    bail!("The \"title\" can't be empty");
+                }
+                item.name = title;
+            }
+            match body["done"].as_bool() {
+                Some(true) => item.mark_done(),
+                Some(false) => item.reopen(),
+                None => {}
+            }
+            let item = item.clone();
+            writer.write(&mut list, &path, &format!("update \"{}\"", item.name))?;
+            if item.is_done() && !was_done {
+                hooks::item_event(config, ItemEvent::Done, name, std::slice::from_ref(&item));
+            }
+            Response::json(200, item_json(number, &item))
+        }
+        (_, ["lists"] | ["lists", _, "items"] | ["lists", _, "items", _]) => {
+            Response::error(405, "Method not allowed")
+        }
+        _ => Response::error(404, "Not found"),
+    };
+    Ok(response)
+}
+
+/// The list `name` and its path, if it's one of the lists of the config. Names that would
+/// point outside of them, like `../notes`, are never lists
+pub fn read_list(config: &Config, name: &str) -> Result<Option<(PathBuf, TodoList)>> {
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Ok(None);
+    }
+    let name = config.resolve_alias(name);
+    if !config.list_names()?.iter().any(|known| known == name) {
+        return Ok(None);
+    }
+    let path = config.list_path(name);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let list = TodoList::from_file(&path)?;
+    Ok(Some((path, list)))
+}
+
+fn no_list(name: &str) -> Response {
+    Response::error(404, &format!("There's no list '{name}'"))
+}
+
+pub fn item_json(number: usize, item: &TodoItem) -> Value {
+    let mut value = json!(item);
+    value["number"] = json!(number);
+    value["done"] = json!(item.is_done());
+    value["tags"] = json!(item.tags());
+    value
+}
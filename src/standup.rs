@@ -0,0 +1,55 @@
+use anyhow::Result;
+use todo::date::Date;
+
+use crate::{config::Config, digest::visible_items};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flavor {
+    Plain,
+    Markdown,
+    Slack,
+}
+
+/// "Done since yesterday" with the items done yesterday or today, and "Doing today" with the
+/// open items that are due by today or tagged `#pinned`
+pub fn report(config: &Config, flavor: Flavor) -> Result<String> {
+    let today = Date::today();
+    let yesterday = today.add_days(-1);
+    let (mut done, mut doing) = (vec![], vec![]);
+    for (_, item) in visible_items(config)? {
+        if item.is_done() {
+            if item.done_date().is_some_and(|date| date >= yesterday) {
+                done.push(item);
+            }
+        } else if item.tags().contains(&"pinned") || item.due().is_some_and(|(due, _)| due <= today)
+        {
+            doing.push(item);
+        }
+    }
+    let mut report = String::new();
+    for (heading, items) in [("Done since yesterday", done), ("Doing today", doing)] {
+        if !report.is_empty() {
+            report.push('\n');
+        }
+        report.push_str(&match flavor {
+            Flavor::Plain => format!("{heading}:\n"),
+            Flavor::Markdown => format!("**{heading}**\n"),
+            Flavor::Slack => format!("*{heading}*\n"),
+        });
+        if items.is_empty() {
+            report.push_str(&bullet(flavor, "Nothing"));
+        }
+        for item in items {
+            // the done date says nothing new here
+            report.push_str(&bullet(flavor, &item.name_without_field("done")));
+        }
+    }
+    Ok(report)
+}
+
+fn bullet(flavor: Flavor, text: &str) -> String {
+    match flavor {
+        Flavor::Plain | Flavor::Markdown => format!("- {text}\n"),
+        Flavor::Slack => format!("• {text}\n"),
+    }
+}
@@ -0,0 +1,204 @@
+//! Where lists are kept, so they can be copied between the markdown files and a SQLite DB. The
+//! DB is read and written with the `sqlite3` command line tool
+
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use todo::TodoList;
+
+use crate::{backup, ListWriter};
+
+pub trait ListStore {
+    /// Names of all the lists in the store
+    fn names(&self) -> Result<Vec<String>>;
+
+    fn load(&self, name: &str) -> Result<TodoList>;
+
+    /// Saves `lists` with their names, replacing the lists of the same names
+    fn save(&self, lists: &mut [(String, TodoList)]) -> Result<()>;
+}
+
+/// The lists in the main dir and the ones registered in the config
+pub struct MarkdownStore<'a> {
+    pub writer: &'a ListWriter<'a>,
+}
+
+impl ListStore for MarkdownStore<'_> {
+    fn names(&self) -> Result<Vec<String>> {
+        self.writer.config.list_names()
+    }
+
+    fn load(&self, name: &str) -> Result<TodoList> {
+        let path = self.writer.config.list_path(name);
+        TodoList::from_file(&path).with_context(|| format!("Couldn't read the list '{name}'"))
+    }
+
+    fn save(&self, lists: &mut [(String, TodoList)]) -> Result<()> {
+        let config = self.writer.config;
+        for (name, list) in lists {
+            let path = config.list_path(name);
+            if path.exists() {
+                backup::backup_list(config, name, &path)?;
+            } else if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            self.writer
+                .write(list, &path, &format!("export {name} from the DB"))
+                .with_context(|| format!("Couldn't write the list '{name}'"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists and every earlier version of them in a single DB file. Lists are kept as their
+/// markdown, so the DB mirrors the files and isn't queried for items
+pub struct SqliteStore {
+    pub path: PathBuf,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS lists (
+    name TEXT PRIMARY KEY,
+    markdown TEXT NOT NULL,
+    saved_at INTEGER NOT NULL
+);
+DROP TABLE IF EXISTS items;
+CREATE TABLE IF NOT EXISTS history (
+    list TEXT NOT NULL,
+    markdown TEXT NOT NULL,
+    saved_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS history_by_list ON history (list, saved_at);
+";
+
+impl SqliteStore {
+    /// Runs the statements in `sql` after creating the tables if needed. The rows of the last
+    /// query are returned as JSON objects
+    fn run(&self, sql: &str) -> Result<Vec<Value>> {
+        let mut child = Command::new("sqlite3")
+            .args(["-bail", "-json"])
+            .arg(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Couldn't run sqlite3. Is it installed?")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(SCHEMA.as_bytes())?;
+            stdin.write_all(sql.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!(
+                "sqlite3 failed on '{}': {}",
+                self.path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let printed = String::from_utf8_lossy(&output.stdout);
+        if printed.trim().is_empty() {
+            return Ok(vec![]);
+        }
+        serde_json::from_str(&printed).context("sqlite3 printed something that isn't JSON")
+    }
+}
+
+impl ListStore for SqliteStore {
+    fn names(&self) -> Result<Vec<String>> {
+        Ok(self
+            .run("SELECT name FROM lists ORDER BY name;")?
+            .iter()
+            .filter_map(|row| row["name"].as_str().map(str::to_string))
+            .collect())
+    }
+
+    fn load(&self, name: &str) -> Result<TodoList> {
+        let rows = self.run(&format!(
+            "SELECT markdown FROM lists WHERE name = {};",
+            quote(name)
+        ))?;
+        let Some(markdown) = rows.first().and_then(|row| row["markdown"].as_str()) else {
+            bail!("There's no list '{name}' in '{}'", self.path.display());
+        };
+        TodoList::from_reader(markdown.as_bytes())
+            .with_context(|| format!("The list '{name}' in the DB is invalid"))
+    }
+
+    /// Saves all of `lists` in one transaction, keeping the versions they replace in the history
+    fn save(&self, lists: &mut [(String, TodoList)]) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.run(&save_sql(lists, now))?;
+        Ok(())
+    }
+}
+
+/// The statements saving `lists` at the Unix time `now`
+fn save_sql(lists: &[(String, TodoList)], now: u64) -> String {
+    let mut sql = String::from("BEGIN;\n");
+    for (name, list) in lists {
+        let name = quote(name);
+        sql.push_str(&format!(
+            "INSERT INTO history SELECT name, markdown, saved_at FROM lists WHERE name = {name};\n\
+             DELETE FROM lists WHERE name = {name};\n\
+             INSERT INTO lists VALUES ({name}, {}, {now});\n",
+            quote(&list.as_markdown())
+        ));
+    }
+    sql.push_str("COMMIT;\n");
+    sql
+}
+
+/// `s` as an SQL string literal
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Copies the lists `names` from `from` to `to`, or all of them if `names` is empty. Returns
+/// how many were copied
+pub fn copy(from: &dyn ListStore, to: &dyn ListStore, names: &[String]) -> Result<usize> {
+    let names = if names.is_empty() {
+        from.names()?
+    } else {
+        names.to_vec()
+    };
+    let mut lists = names
+        .into_iter()
+        .map(|name| from.load(&name).map(|list| (name, list)))
+        .collect::<Result<Vec<_>>>()?;
+    to.save(&mut lists)?;
+    Ok(lists.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_names_and_titles() {
+        assert_eq!(quote("plain"), "'plain'");
+        assert_eq!(quote("it's"), "'it''s'");
+        assert_eq!(quote("''"), "''''''");
+        assert_eq!(quote("a\nb"), "'a\nb'");
+
+        let list = TodoList::from_reader("- [ ] call Bob's mom'); DROP TABLE lists; --".as_bytes())
+            .unwrap();
+        let sql = save_sql(&[("Bob's".to_string(), list)], 42);
+        assert_eq!(
+            sql,
+            "BEGIN;\n\
+             INSERT INTO history SELECT name, markdown, saved_at FROM lists WHERE name = 'Bob''s';\n\
+             DELETE FROM lists WHERE name = 'Bob''s';\n\
+             INSERT INTO lists VALUES ('Bob''s', '- [ ] call Bob''s mom''); DROP TABLE lists; --', 42);\n\
+             COMMIT;\n"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use std::{collections::HashMap, fmt::Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Terminal colors that can be used in a [`Style`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    /// The color named like in the config, e.g. `blue`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "white" => Color::White,
+            _ => return None,
+        })
+    }
+
+    fn fg_code(&self) -> u8 {
+        30 + *self as u8
+    }
+
+    fn bg_code(&self) -> u8 {
+        40 + *self as u8
+    }
+}
+
+/// A set of ANSI attributes applied to a piece of text
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl Style {
+    pub fn fg(color: Color) -> Self {
+        Self {
+            fg: Some(color),
+            ..Default::default()
+        }
+    }
+
+    fn codes(&self) -> String {
+        let mut codes = vec![];
+        if self.bold {
+            codes.push(1);
+        }
+        if self.dim {
+            codes.push(2);
+        }
+        if self.italic {
+            codes.push(3);
+        }
+        if self.underline {
+            codes.push(4);
+        }
+        if self.strikethrough {
+            codes.push(9);
+        }
+        if let Some(fg) = self.fg {
+            codes.push(fg.fg_code());
+        }
+        if let Some(bg) = self.bg {
+            codes.push(bg.bg_code());
+        }
+        codes.iter().fold(String::new(), |mut s, c| {
+            if !s.is_empty() {
+                s.push(';');
+            }
+            let _ = write!(s, "{c}");
+            s
+        })
+    }
+
+    /// Wraps `text` in the escape codes for this style
+    pub fn paint(&self, text: &str) -> String {
+        let codes = self.codes();
+        if codes.is_empty() {
+            return text.to_string();
+        }
+        format!("\x1b[{codes}m{text}\x1b[0m")
+    }
+}
+
+/// Styles used by the display layer for each part of the output
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Theme {
+    /// Title of done items
+    pub done: Style,
+    /// Title of items that are not done yet
+    pub open: Style,
+    pub done_marker: Style,
+    pub open_marker: Style,
+    /// Item number column
+    pub number: Style,
+    pub description: Style,
+    /// `#tag` words inside titles
+    pub tag: Style,
+    /// Styles for specific tags, keyed by the tag name. Tags not in here use `tag`
+    pub tags: HashMap<String, Style>,
+    /// URLs and markdown links
+    pub link: Style,
+    /// `` `code` `` spans in titles and descriptions
+    pub code: Style,
+    /// How done items are shown
+    pub done_items: DoneStyle,
+}
+
+/// How done items are shown, on top of the `done` style of the theme
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum DoneStyle {
+    /// Struck through, as the default `done` style does
+    #[default]
+    Strikethrough,
+    /// Dimmed but not struck through
+    Dimmed,
+    /// After the open items, keeping their numbers
+    Bottom,
+    /// With the day they were done in front of the title instead of in it
+    Dated,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let dim = Style {
+            dim: true,
+            ..Default::default()
+        };
+        Self {
+            done: Style {
+                dim: true,
+                strikethrough: true,
+                ..Default::default()
+            },
+            open: Style::default(),
+            done_marker: Style::fg(Color::Green),
+            open_marker: Style::default(),
+            number: dim.clone(),
+            description: dim,
+            tag: Style::fg(Color::Yellow),
+            tags: HashMap::new(),
+            link: Style {
+                underline: true,
+                ..Default::default()
+            },
+            code: Style::fg(Color::Cyan),
+            done_items: DoneStyle::default(),
+        }
+    }
+}
+
+impl Theme {
+    /// Style for the tag `tag` (with or without the leading `#`)
+    pub fn tag_style(&self, tag: &str) -> &Style {
+        let name = tag.strip_prefix('#').unwrap_or(tag);
+        self.tags
+            .get(name)
+            .or_else(|| self.tags.get(tag))
+            .unwrap_or(&self.tag)
+    }
+}
+
+/// Symbols used to decorate the output
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(default))]
+pub struct Glyphs {
+    /// Marker in front of done items
+    pub done: String,
+    /// Marker in front of items that are not done yet
+    pub open: String,
+    /// Appended to titles that were cut short
+    pub ellipsis: String,
+    /// Done part of progress bars. Progress is shown as a count if this is empty
+    pub bar_done: String,
+    /// Remaining part of progress bars
+    pub bar_open: String,
+}
+
+impl Default for Glyphs {
+    fn default() -> Self {
+        Self {
+            done: "✅".to_string(),
+            open: "⬜".to_string(),
+            ellipsis: "…".to_string(),
+            bar_done: "#".to_string(),
+            bar_open: ".".to_string(),
+        }
+    }
+}
+
+impl Glyphs {
+    /// Markers that display correctly on any terminal
+    pub fn ascii() -> Self {
+        Self {
+            done: "[x]".to_string(),
+            open: "[ ]".to_string(),
+            ellipsis: "...".to_string(),
+            bar_done: "#".to_string(),
+            bar_open: ".".to_string(),
+        }
+    }
+
+    /// Words instead of symbols, for screen readers and terminals without emoji
+    pub fn plain() -> Self {
+        Self {
+            done: "done:".to_string(),
+            open: "todo:".to_string(),
+            ellipsis: "...".to_string(),
+            bar_done: "".to_string(),
+            bar_open: "".to_string(),
+        }
+    }
+}
@@ -0,0 +1,542 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use todo::{
+    merge3,
+    text::{fnv1a, percent_decode, percent_encode},
+    TodoItem, TodoList,
+};
+
+use crate::{config::Config, git};
+
+/// Syncs the main dir, and every list with sync settings of its own, with `backend` or the
+/// configured backends
+pub fn run(config: &Config, backend: Option<BackendKind>) -> Result<()> {
+    let own_settings = config
+        .lists()
+        .iter()
+        .filter_map(|(name, list)| Some((name, list.settings().sync.as_ref()?)))
+        .collect::<Vec<_>>();
+    let main_dir = config.main_dir();
+    // lists in the main dir with their own settings are only synced with those
+    let skipped = own_settings
+        .iter()
+        .map(|(name, _)| config.list_path(name))
+        .filter(|path| path.parent() == Some(main_dir))
+        .filter_map(|path| Some(path.file_name()?.to_str()?.to_string()))
+        .collect::<Vec<_>>();
+    config
+        .sync()
+        .backend(backend)?
+        .sync(main_dir, &|file| !skipped.iter().any(|s| s == file))?;
+
+    for (name, settings) in own_settings {
+        let path = config.list_path(name);
+        let (Some(dir), Some(file)) = (path.parent(), path.file_name().and_then(|f| f.to_str()))
+        else {
+            continue;
+        };
+        println!("Syncing {name}");
+        settings.backend(backend)?.sync(dir, &|f| f == file)?;
+    }
+    Ok(())
+}
+
+/// How `todo sync` syncs lists, set globally or for a single list
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// backend used unless another one is passed with `--backend`
+    pub backend: BackendKind,
+    /// directory the lists are copied to and from, e.g. one kept in sync by Syncthing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory: Option<PathBuf>,
+    /// rsync destination of the lists, e.g. `me@host:todo`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rsync: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub webdav: Option<WebDavConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// Commit, pull and push with the git remote of the list directory
+    #[default]
+    Git,
+    /// Copy lists to and from `sync.directory`
+    Directory,
+    /// Copy lists to and from `sync.rsync` with rsync, usually over ssh
+    Rsync,
+    /// Upload and download lists from the WebDAV folder at `sync.webdav.url`
+    Webdav,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WebDavConfig {
+    /// URL of the folder the lists are kept in, e.g. a Nextcloud folder
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    /// environment variable holding the password. `~/.netrc` is used if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_var: Option<String>,
+}
+
+impl SyncConfig {
+    /// The backend of kind `kind`, or of the configured kind
+    pub fn backend(&self, kind: Option<BackendKind>) -> Result<Box<dyn SyncBackend>> {
+        let kind = kind.unwrap_or(self.backend);
+        let missing =
+            |key: &str| format!("Set `sync.{key}` in the config to sync with the {kind:?} backend");
+        Ok(match kind {
+            BackendKind::Git => Box::new(Git),
+            BackendKind::Directory => Box::new(Directory(
+                self.directory
+                    .clone()
+                    .with_context(|| missing("directory"))?,
+            )),
+            BackendKind::Rsync => {
+                Box::new(Rsync(self.rsync.clone().with_context(|| missing("rsync"))?))
+            }
+            BackendKind::Webdav => Box::new(WebDav(
+                self.webdav.clone().with_context(|| missing("webdav.url"))?,
+            )),
+        })
+    }
+}
+
+pub trait SyncBackend {
+    /// Brings the list files in `dir` whose file names pass `include` in line with the remote
+    fn sync(&self, dir: &Path, include: &dyn Fn(&str) -> bool) -> Result<()>;
+}
+
+/// Syncs the directory with its git remote, committing only the changes to the files
+/// `include` lets through
+struct Git;
+
+impl SyncBackend for Git {
+    fn sync(&self, dir: &Path, include: &dyn Fn(&str) -> bool) -> Result<()> {
+        git::sync(dir, include)
+    }
+}
+
+/// A place list files can be copied to and from
+trait RemoteFiles {
+    /// Identifies the remote in the sync state
+    fn id(&self) -> String;
+    /// File names of the lists on the remote
+    fn list(&self) -> Result<Vec<String>>;
+    fn download(&self, name: &str) -> Result<Option<Vec<u8>>>;
+    fn upload(&self, name: &str, contents: &[u8]) -> Result<()>;
+}
+
+impl<T: RemoteFiles> SyncBackend for T {
+    /// Copies every list that changed on one side only to the other side. Lists changed on
+    /// both sides since the last sync are merged. Without a copy of the list from the last sync
+    /// to merge with, the local version is kept and the remote one saved next to it to merge by
+    /// hand
+    fn sync(&self, dir: &Path, include: &dyn Fn(&str) -> bool) -> Result<()> {
+        let mut state = SyncState::read()?;
+        let mut names = self.list()?.into_iter().collect::<BTreeSet<_>>();
+        if dir.is_dir() {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "md") {
+                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                        names.insert(name.to_string());
+                    }
+                }
+            }
+        }
+
+        let mut conflicts = vec![];
+        for name in names.iter().filter(|name| include(name)) {
+            let path = dir.join(name);
+            let key = format!("{} {}", self.id(), path.display());
+            let local = fs::read(&path).ok();
+            let remote = self.download(name)?;
+            let last_synced = state.0.get(&key).copied();
+            let unchanged = |contents: &[u8]| last_synced == Some(hash(contents));
+
+            let synced = match (&local, &remote) {
+                (Some(local), Some(remote)) if local == remote => local,
+                (Some(local), Some(remote)) if unchanged(local) => {
+                    fs::write(&path, remote)
+                        .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+                    println!("Downloaded {name}");
+                    remote
+                }
+                (Some(local), Some(remote)) if !unchanged(remote) => {
+                    let base = fs::read(SyncState::base_path(&key)).ok();
+                    let merged = match base {
+                        Some(base) => merge_versions(name, &base, local, remote)?,
+                        None => None,
+                    };
+                    if let Some(merged) = merged {
+                        fs::write(&path, &merged)
+                            .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+                        self.upload(name, &merged)?;
+                        println!("Merged {name}");
+                        state.synced(key, &merged)?;
+                        continue;
+                    }
+                    let conflict = dir.join(format!("{name}.conflict"));
+                    fs::write(&conflict, remote)
+                        .with_context(|| format!("Couldn't write '{}'", conflict.display()))?;
+                    conflicts.push(conflict);
+                    // the local version is uploaded on the next sync
+                    state.0.insert(key, hash(remote));
+                    continue;
+                }
+                (Some(local), _) => {
+                    self.upload(name, local)?;
+                    println!("Uploaded {name}");
+                    local
+                }
+                (None, Some(remote)) => {
+                    fs::create_dir_all(dir)?;
+                    fs::write(&path, remote)
+                        .with_context(|| format!("Couldn't write '{}'", path.display()))?;
+                    println!("Downloaded {name}");
+                    remote
+                }
+                (None, None) => continue,
+            };
+            state.synced(key, synced)?;
+        }
+        state.write()?;
+
+        if !conflicts.is_empty() {
+            let mut message = String::from("These lists were changed here and on the remote:\n");
+            for conflict in &conflicts {
+                message.push_str(&format!("  {}\n", conflict.display()));
+            }
+            message.push_str(
+                "The remote versions were saved next to the lists. Copy the changes you want to \
+                keep into the lists, delete the .conflict files and run `todo sync` again",
+            );
+            bail!(message);
+        }
+        Ok(())
+    }
+}
+
+/// Merges the changes made to the list `name` since `base` here and on the remote with
+/// [`merge3`], printing the items changed on both sides, which keep the local version. `None`
+/// if one of the versions isn't a valid list
+pub fn merge_versions(
+    name: &str,
+    base: &[u8],
+    local: &[u8],
+    remote: &[u8],
+) -> Result<Option<Vec<u8>>> {
+    let (Ok(base), Ok(local), Ok(remote)) = (
+        TodoList::from_reader(base),
+        TodoList::from_reader(local),
+        TodoList::from_reader(remote),
+    ) else {
+        return Ok(None);
+    };
+    let merged = merge3(&base, &local, &remote)?;
+    let describe = |item: &Option<TodoItem>| match item {
+        Some(item) => format!("'{}'", item.as_markdown().trim()),
+        None => "removed it".to_string(),
+    };
+    for conflict in &merged.conflicts {
+        println!(
+            "'{}' in {name} changed here and on the remote. Kept {} over {}",
+            conflict.base.name,
+            describe(&conflict.ours),
+            describe(&conflict.theirs)
+        );
+    }
+    let mut contents = vec![];
+    merged.list.write_to(&mut contents)?;
+    Ok(Some(contents))
+}
+
+fn hash(contents: &[u8]) -> u64 {
+    fnv1a(contents)
+}
+
+/// Hashes of the lists as they were after their last sync, by remote and path
+#[derive(Default, Deserialize, Serialize)]
+struct SyncState(BTreeMap<String, u64>);
+
+impl SyncState {
+    fn path() -> PathBuf {
+        dirs::data_dir()
+            .expect("OS data directory not found")
+            .join(env!("CARGO_PKG_NAME"))
+            .join("sync.json")
+    }
+
+    /// Where the list synced with the key `key` is kept as it was after its last sync, to
+    /// merge changes made on both sides since then
+    fn base_path(key: &str) -> PathBuf {
+        Self::path()
+            .with_file_name("sync-base")
+            .join(format!("{:x}.md", fnv1a(key.as_bytes())))
+    }
+
+    /// Records that the list with the key `key` is `contents` on both sides now
+    fn synced(&mut self, key: String, contents: &[u8]) -> Result<()> {
+        let base = Self::base_path(&key);
+        if let Some(dir) = base.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&base, contents)
+            .with_context(|| format!("Couldn't save the synced list to '{}'", base.display()))?;
+        self.0.insert(key, hash(contents));
+        Ok(())
+    }
+
+    fn read() -> Result<Self> {
+        match fs::read_to_string(Self::path()) {
+            Ok(contents) => serde_json::from_str(&contents).context("The sync state is corrupt"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    fn write(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_string(self)?)
+            .with_context(|| format!("Couldn't save the sync state to '{}'", path.display()))
+    }
+}
+
+struct Directory(PathBuf);
+
+impl RemoteFiles for Directory {
+    fn id(&self) -> String {
+        format!("directory:{}", self.0.display())
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        if !self.0.is_dir() {
+            return Ok(vec![]);
+        }
+        Ok(fs::read_dir(&self.0)
+            .with_context(|| format!("Couldn't read '{}'", self.0.display()))?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|name| name.ends_with(".md"))
+            .collect())
+    }
+
+    fn download(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(fs::read(self.0.join(name)).ok())
+    }
+
+    fn upload(&self, name: &str, contents: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.0)?;
+        let path = self.0.join(name);
+        fs::write(&path, contents).with_context(|| format!("Couldn't write '{}'", path.display()))
+    }
+}
+
+/// An rsync destination like `host:dir`
+struct Rsync(String);
+
+impl Rsync {
+    fn remote_path(&self, name: &str) -> String {
+        format!("{}/{name}", self.0.trim_end_matches('/'))
+    }
+
+    fn rsync(args: &[&str]) -> Result<std::process::Output> {
+        Command::new("rsync")
+            .args(args)
+            .output()
+            .context("Couldn't run rsync. Is it installed?")
+    }
+}
+
+/// rsync's exit code when some files weren't found
+const RSYNC_PARTIAL_TRANSFER: i32 = 23;
+
+impl RemoteFiles for Rsync {
+    fn id(&self) -> String {
+        format!("rsync:{}", self.0)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let output = Self::rsync(&["--list-only", &self.remote_path("")])?;
+        if output.status.code() == Some(RSYNC_PARTIAL_TRANSFER) {
+            return Ok(vec![]);
+        }
+        if !output.status.success() {
+            bail!(
+                "Couldn't list the lists at '{}': {}",
+                self.0,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        // lines look like `-rw-r--r--  123 2024/01/31 12:00:00 name.md`
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.starts_with('-'))
+            .map(|line| {
+                line.split_whitespace()
+                    .skip(4)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .filter(|name| name.ends_with(".md"))
+            .collect())
+    }
+
+    fn download(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let temp = temp_path(name);
+        let output = Self::rsync(&[&self.remote_path(name), &temp.to_string_lossy()])?;
+        if output.status.code() == Some(RSYNC_PARTIAL_TRANSFER) {
+            return Ok(None);
+        }
+        if !output.status.success() {
+            bail!(
+                "Couldn't download {name}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let contents = fs::read(&temp)?;
+        let _ = fs::remove_file(&temp);
+        Ok(Some(contents))
+    }
+
+    fn upload(&self, name: &str, contents: &[u8]) -> Result<()> {
+        let temp = temp_path(name);
+        fs::write(&temp, contents)?;
+        let output = Self::rsync(&[&temp.to_string_lossy(), &self.remote_path(name)]);
+        let _ = fs::remove_file(&temp);
+        let output = output?;
+        if !output.status.success() {
+            bail!(
+                "Couldn't upload {name}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("todo-sync.{}.{name}", std::process::id()))
+}
+
+/// A WebDAV folder, accessed with curl
+struct WebDav(WebDavConfig);
+
+impl WebDav {
+    fn url(&self, name: &str) -> String {
+        format!(
+            "{}/{}",
+            self.0.url.trim_end_matches('/'),
+            percent_encode(name)
+        )
+    }
+
+    /// Runs curl with `args`, returning the HTTP status and the body. The credentials are
+    /// passed through stdin so they don't show up in the process list
+    fn curl(&self, args: &[&str]) -> Result<(u32, Vec<u8>)> {
+        let mut child = Command::new("curl")
+            .args([
+                "--silent",
+                "--show-error",
+                "--netrc-optional",
+                "--config",
+                "-",
+            ])
+            .args(["--write-out", "\n%{http_code}"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Couldn't run curl. Is it installed?")?;
+        if let (Some(user), Some(mut stdin)) = (&self.0.user, child.stdin.take()) {
+            let password = match &self.0.password_var {
+                Some(var) => env::var(var).with_context(|| format!("{var} is not set"))?,
+                None => String::new(),
+            };
+            let credentials = format!("{user}:{password}")
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+            writeln!(stdin, "user = \"{credentials}\"")?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        let mut body = output.stdout;
+        let split = body.iter().rposition(|b| *b == b'\n').unwrap_or(0);
+        let status = String::from_utf8_lossy(&body[split..]).trim().parse()?;
+        body.truncate(split);
+        Ok((status, body))
+    }
+}
+
+impl RemoteFiles for WebDav {
+    fn id(&self) -> String {
+        format!("webdav:{}", self.0.url)
+    }
+
+    fn list(&self) -> Result<Vec<String>> {
+        let (status, body) = self.curl(&[
+            "--request",
+            "PROPFIND",
+            "--header",
+            "Depth: 1",
+            &format!("{}/", self.0.url.trim_end_matches('/')),
+        ])?;
+        match status {
+            404 => return Ok(vec![]),
+            200..=299 => {}
+            _ => bail!("Couldn't list the lists at '{}': HTTP {status}", self.0.url),
+        }
+        // the entries of the folder are in `<d:href>` elements, whatever the namespace prefix
+        let body = String::from_utf8_lossy(&body);
+        Ok(body
+            .split("href>")
+            .skip(1)
+            .filter_map(|part| part.split('<').next()?.rsplit('/').next())
+            .map(percent_decode)
+            .filter(|name| name.ends_with(".md"))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect())
+    }
+
+    fn download(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let (status, body) = self
+            .curl(&[&self.url(name)])
+            .with_context(|| format!("Couldn't download {name}"))?;
+        match status {
+            404 => Ok(None),
+            200..=299 => Ok(Some(body)),
+            _ => bail!("Couldn't download {name}: HTTP {status}"),
+        }
+    }
+
+    fn upload(&self, name: &str, contents: &[u8]) -> Result<()> {
+        let temp = temp_path(name);
+        fs::write(&temp, contents)?;
+        let result = self.curl(&["--upload-file", &temp.to_string_lossy(), &self.url(name)]);
+        let _ = fs::remove_file(&temp);
+        let (status, _) = result.with_context(|| format!("Couldn't upload {name}"))?;
+        if !(200..=299).contains(&status) {
+            bail!("Couldn't upload {name}: HTTP {status}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+/// Number of terminal columns `c` takes up. Emoji and other wide symbols take two
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F
+        | 0x2600..=0x27BF
+        | 0x2B00..=0x2BFF
+        | 0x2E80..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F000..=0x1FAFF
+        | 0x20000..=0x3FFFD => 2,
+        0xFE00..=0xFE0F | 0x200B..=0x200F => 0,
+        _ => 1,
+    }
+}
+
+/// Number of terminal columns `s` takes up
+pub(crate) fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Shortens `s` to at most `width` columns, ending it with `ellipsis` if anything was cut
+pub(crate) fn truncate(s: &str, width: usize, ellipsis: &str) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    let mut truncated = String::new();
+    let mut used = display_width(ellipsis);
+    for c in s.chars() {
+        let w = char_width(c);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        truncated.push(c);
+    }
+    truncated.push_str(ellipsis);
+    truncated
+}
+
+/// Splits `s` into lines of at most `width` columns, breaking at spaces.
+/// Words longer than `width` get a line of their own.
+pub(crate) fn wrap(s: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in s.split(' ') {
+        if !line.is_empty() && display_width(&line) + 1 + display_width(word) > width {
+            lines.push(std::mem::take(&mut line));
+        } else if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines
+}
+
+/// A markdown link `[text](url)` found in a string
+pub(crate) struct Link<'a> {
+    pub text: &'a str,
+    pub url: &'a str,
+    /// Byte range of the whole link in the searched string
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Finds the first markdown link in `s`
+pub(crate) fn find_markdown_link(s: &str) -> Option<Link<'_>> {
+    let mut from = 0;
+    while let Some(open) = s[from..].find('[').map(|i| i + from) {
+        let close = s[open..].find("](").map(|i| i + open)?;
+        let url_start = close + 2;
+        if let Some(url_end) = s[url_start..].find(')').map(|i| i + url_start) {
+            let url = &s[url_start..url_end];
+            if !url.is_empty() && !url.contains(' ') {
+                return Some(Link {
+                    text: &s[open + 1..close],
+                    url,
+                    start: open,
+                    end: url_end + 1,
+                });
+            }
+        }
+        from = open + 1;
+    }
+    None
+}
+
+pub(crate) fn is_url(word: &str) -> bool {
+    ["http://", "https://"]
+        .iter()
+        .any(|scheme| word.len() > scheme.len() && word.starts_with(scheme))
+}
+
+/// Wraps `text` in an OSC 8 escape sequence so terminals show it as a link to `url`
+pub(crate) fn hyperlink(url: &str, text: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+/// Inline markdown formatting of a piece of text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Inline {
+    Plain,
+    Bold,
+    Italic,
+    Code,
+}
+
+/// Splits `s` into runs of `**bold**`, `*italic*`, `` `code` `` and plain text, with the
+/// markers removed. Unclosed markers are kept as plain text.
+pub(crate) fn inline_spans(s: &str) -> Vec<(Inline, &str)> {
+    let mut spans = vec![];
+    let mut plain_start = 0;
+    let mut i = 0;
+    while i < s.len() {
+        let rest = &s[i..];
+        let found = [
+            ("**", Inline::Bold),
+            ("`", Inline::Code),
+            ("*", Inline::Italic),
+        ]
+        .into_iter()
+        .find(|(marker, _)| rest.starts_with(marker))
+        .and_then(|(marker, kind)| {
+            let inner = &rest[marker.len()..];
+            let end = inner.find(marker)?;
+            let content = &inner[..end];
+            let trimmed = !content.is_empty() && content.trim() == content;
+            (trimmed || kind == Inline::Code && !content.is_empty())
+                .then(|| (kind, content, marker.len() * 2 + end))
+        });
+        if let Some((kind, content, len)) = found {
+            if plain_start < i {
+                spans.push((Inline::Plain, &s[plain_start..i]));
+            }
+            spans.push((kind, content));
+            i += len;
+            plain_start = i;
+        } else {
+            i += rest.chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    if plain_start < s.len() {
+        spans.push((Inline::Plain, &s[plain_start..]));
+    }
+    spans
+}
+
+/// Number of single character insertions, deletions and substitutions to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The FNV-1a hash of `bytes`, which unlike the std hasher is the same with every Rust version,
+/// so it can be saved
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Number of digits of `n` in base 10
+pub fn digits(n: usize) -> usize {
+    n.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+/// `s` in lowercase with only its letters, digits and single spaces between words
+pub fn normalize(s: &str) -> String {
+    s.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `s` on one line, with line breaks and other control characters turned into spaces. Titles
+/// from outside, like requests, go through this so they can't add lines to a list file
+pub fn single_line(s: &str) -> String {
+    s.split(char::is_control)
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether all the characters of `needle` appear in `haystack` in the same order
+pub fn is_subsequence(needle: &str, haystack: &str) -> bool {
+    let mut haystack = haystack.chars();
+    needle.chars().all(|c| haystack.any(|h| h == c))
+}
+
+/// Escapes everything but letters, digits and `-._~` as `%XX`, for use in URLs
+pub fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Turns the `%XX` escapes of a URL back into the characters they stand for
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
@@ -0,0 +1,105 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use todo::{query::Query, TodoItem};
+
+use crate::{config::Config, hooks::ItemEvent};
+
+/// A URL that is sent a JSON payload when items are added, done or removed, e.g. a Slack or Discord
+/// incoming webhook
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Webhook {
+    pub url: String,
+    /// events the webhook fires on, all of them if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on: Vec<ItemEvent>,
+    /// only fire for items matching this query, e.g. `tag:team`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// JSON sent to the URL. `{event}`, `{list}`, `{title}` and `{description}` in its strings
+    /// are replaced. Discord wants e.g. `{"content": "{title} is done"}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+}
+
+/// Sends the webhooks for `event` on `items` of the list `list`. Failures are only reported
+/// since the lists have already been written
+pub fn fire(config: &Config, event: ItemEvent, list: &str, items: &[TodoItem]) {
+    for webhook in config.webhooks() {
+        if !webhook.on.is_empty() && !webhook.on.contains(&event) {
+            continue;
+        }
+        let filter = match webhook.filter.as_deref().map(str::parse::<Query>) {
+            Some(Ok(query)) => Some(query),
+            Some(Err(e)) => {
+                eprintln!("Skipping the webhook for {}: {e}", webhook.url);
+                continue;
+            }
+            None => None,
+        };
+        for item in items {
+            if filter.as_ref().is_some_and(|query| !query.matches(item)) {
+                continue;
+            }
+            if let Err(e) = webhook.send(event, list, item) {
+                eprintln!("Couldn't send the webhook for {}: {e:#}", webhook.url);
+            }
+        }
+    }
+}
+
+impl Webhook {
+    fn send(&self, event: ItemEvent, list: &str, item: &TodoItem) -> Result<()> {
+        let template = self.payload.clone().unwrap_or_else(|| {
+            json!({
+                "event": "{event}",
+                "list": "{list}",
+                "title": "{title}",
+                "text": "{title} ({list}): {event}",
+            })
+        });
+        let description = item.description.as_deref().unwrap_or_default();
+        let payload = fill(template, &|s| {
+            s.replace("{event}", event.name())
+                .replace("{list}", list)
+                .replace("{title}", &item.name)
+                .replace("{description}", description)
+        });
+
+        let mut child = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--max-time", "10"])
+            .args(["--header", "Content-Type: application/json"])
+            .args(["--data-binary", "@-", &self.url])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Couldn't run curl. Is it installed?")?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(payload.to_string().as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(())
+    }
+}
+
+/// Applies `replace` to every string in `value`
+fn fill(value: Value, replace: &dyn Fn(&str) -> String) -> Value {
+    match value {
+        Value::String(s) => Value::String(replace(&s)),
+        Value::Array(values) => values.into_iter().map(|v| fill(v, replace)).collect(),
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(k, v)| (k, fill(v, replace)))
+            .collect(),
+        value => value,
+    }
+}